@@ -0,0 +1,31 @@
+use crate::FractalContext;
+use crate::splat;
+
+#[derive(Clone, Copy)]
+pub enum Map {
+    Clifford,
+    DeJong
+}
+
+impl Map {
+    pub fn default_params(self) -> [f64; 4] {
+        match self {
+            Map::Clifford => [-1.4, 1.6, 1.0, 0.7],
+            Map::DeJong => [1.4, -2.3, 2.4, -2.1]
+        }
+    }
+
+    fn step(self, [a, b, c, d]: [f64; 4], (x, y): (f64, f64)) -> (f64, f64) {
+        match self {
+            Map::Clifford => ((a * y).sin() + c * (a * x).cos(), (b * x).sin() + d * (b * y).cos()),
+            Map::DeJong => ((a * y).sin() - (b * x).cos(), (c * x).sin() - (d * y).cos())
+        }
+    }
+}
+
+pub fn render(ctx: &mut FractalContext, map: Map, params: [f64; 4], points: usize) {
+    splat::render(ctx, points, |p, _| {
+        *p = map.step(params, *p);
+        *p
+    });
+}
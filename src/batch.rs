@@ -0,0 +1,79 @@
+use rayon::prelude::*;
+
+use crate::bookmarks::{strings, tables, unquote};
+use crate::config::Config;
+use crate::{export, take_flag};
+
+/// Renders each `[[job]]` in the file `args` names in turn, or all at once with `--parallel`,
+/// the rest of `args` going to every job. Returns false if the arguments don't make sense.
+pub fn run(args: &mut Vec<String>, config: &Config) -> bool {
+    let parallel = take_flag(args, "--parallel");
+    if args.len() < 2 {
+        return false;
+    }
+    let path = args.remove(1);
+    let source = match std::fs::read_to_string(&path) {
+        Ok(source) => source,
+        Err(err) => {
+            println!("{}: {}", path, err);
+            return true;
+        }
+    };
+    let tables = match tables(&source, "job") {
+        Ok(tables) => tables,
+        Err(err) => {
+            println!("{}: {}", path, err);
+            return true;
+        }
+    };
+    let mut jobs = Vec::new();
+    for (n, table) in &tables {
+        match job(table, args) {
+            Ok(job) => jobs.push((*n, job)),
+            Err(err) => println!("{}: job on line {}: {}", path, n, err)
+        }
+    }
+
+    let render = |(n, mut job): (usize, Vec<String>)| {
+        let given = job[1..].join(" ");
+        if !export::run(&mut job, config) {
+            println!("{}: job on line {}: bad arguments {}", path, n, given);
+        }
+    };
+    if parallel {
+        jobs.into_par_iter().for_each(render);
+    } else {
+        jobs.into_iter().for_each(render);
+    }
+    true
+}
+
+/// The arguments `render` takes for the job in `table`, whose `key = value` lines each stand
+/// for a `--key value` option before the ones in `common`: a quoted value is one argument, a
+/// list of them several, and `true` has the option take none. The fractal goes in `fractal`,
+/// unless it's given in `common` instead.
+fn job(table: &[(String, String)], common: &[String]) -> Result<Vec<String>, String> {
+    let mut args = vec![common[0].clone()];
+    let mut fractal = Vec::new();
+    for (key, value) in table {
+        let values = match strings(value) {
+            Some(values) => values,
+            None => vec![unquote(value).unwrap_or(value.clone())]
+        };
+        match (key.as_str(), value.as_str()) {
+            ("fractal", _) => fractal = values.iter().flat_map(|value| value.split_whitespace()).map(String::from).collect(),
+            (_, "true") => args.push(format!("--{}", key)),
+            (_, "false") => {}
+            _ => {
+                args.push(format!("--{}", key));
+                args.extend(values);
+            }
+        }
+    }
+    if !table.iter().any(|(key, _)| key == "output") {
+        return Err("missing output".to_string());
+    }
+    args.extend(common[1..].iter().cloned());
+    args.extend(fractal);
+    Ok(args)
+}
@@ -0,0 +1,58 @@
+use std::time::Instant;
+
+use crate::{Fractal, FractalContext, Options};
+use crate::fixed::Center;
+
+/// Every scene renders at this size, whatever the window would be.
+const SIZE: (usize, usize) = (1280, 720);
+
+/// A view to time: the fractal, with its iteration limit fixed, and where it's looked at.
+struct Scene {
+    name: &'static str,
+    fract: Fractal,
+    center: (f64, f64),
+    scale: f64
+}
+
+fn scenes() -> Vec<Scene> {
+    vec![
+        Scene {
+            name: "seahorse valley",
+            fract: Fractal::Mandelbrot(2000),
+            center: (-0.7436438870371587, 0.1318259042053119),
+            scale: 1e5
+        },
+        Scene {
+            name: "deep zoom",
+            fract: Fractal::Mandelbrot(5000),
+            center: (-0.7436438870371587, 0.1318259042053119),
+            scale: 1e14
+        },
+        Scene {
+            name: "burning ship armada",
+            fract: Fractal::BurningShip(1000),
+            center: (-1.762, -0.028),
+            scale: 4000.
+        }
+    ]
+}
+
+/// Renders each scene to completion off screen with the renderer and settings `options`
+/// select, printing how long it took.
+pub fn run(options: &Options) {
+    println!("{} at {}x{}", FractalContext::from_options(options).renderer.name(), SIZE.0, SIZE.1);
+    for scene in scenes() {
+        let mut ctx = FractalContext::from_options(options);
+        ctx.dimensions = SIZE;
+        (ctx.pan, ctx.scale) = (Center::new(scene.center), scene.scale);
+        // the limits above are what's timed, not what the zoom would give
+        ctx.iteration_curve = None;
+
+        let start = Instant::now();
+        scene.fract.render_all(&mut ctx);
+        let elapsed = start.elapsed().as_secs_f64();
+
+        let pixels = (SIZE.0 * SIZE.1) as f64;
+        println!("{:<20} {:>9.1} ms {:>8.2} Mpixels/s", scene.name, elapsed * 1e3, pixels / elapsed / 1e6);
+    }
+}
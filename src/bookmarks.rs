@@ -0,0 +1,361 @@
+use std::path::{Path, PathBuf};
+
+use crate::coloring::{self, Coloring, Interior};
+use crate::fixed::Center;
+use crate::history::State;
+use crate::{keys, png, HOME_SCALE, ITERATION_CURVE};
+
+/// A view saved to come back to, with the coloring it had.
+#[derive(Clone)]
+pub struct Bookmark {
+    pub view: State,
+    pub coloring: Coloring,
+    pub brightness: Option<Coloring>,
+    pub interior: Interior,
+    pub slope: bool,
+    pub gamma: f64,
+    pub exposure: f64,
+    pub dither: bool,
+    /// The palette's `key`, its file or the name of a built-in one.
+    pub palette: String,
+    pub palette_offset: f64,
+    pub iteration_curve: Option<f64>
+}
+
+/// The bookmarks in `bookmarks.toml` beside `keys.toml`, which is written out again each
+/// time one is added.
+pub struct Bookmarks {
+    list: Vec<Bookmark>,
+    /// Index of the one to go to next.
+    next: usize,
+    path: Option<PathBuf>
+}
+
+impl Bookmarks {
+    pub fn load() -> Result<Bookmarks, String> {
+        let path = keys::config_path("bookmarks.toml");
+        let list = match &path {
+            Some(path) if path.exists() => {
+                let source = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+                parse(&source).map_err(|err| format!("{}: {}", path.display(), err))?
+            }
+            _ => Vec::new()
+        };
+        Ok(Bookmarks { list, next: 0, path })
+    }
+
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    /// Adds `bookmark` as the last one, saving them all.
+    pub fn add(&mut self, bookmark: Bookmark) -> Result<(), String> {
+        self.list.push(bookmark);
+        let path = self.path.as_ref().ok_or("no config directory to save bookmarks in")?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
+        }
+        std::fs::write(path, self.list.iter().map(write).collect::<String>()).map_err(|err| format!("{}: {}", path.display(), err))
+    }
+
+    /// The bookmarks one after the other, starting over after the last.
+    pub fn cycle(&mut self) -> Option<&Bookmark> {
+        if self.list.is_empty() {
+            return None;
+        }
+        let index = self.next % self.list.len();
+        self.next = index + 1;
+        Some(&self.list[index])
+    }
+}
+
+/// Writes `bookmark` alone to `path`, as the whole state of the viewer to come back to. It's
+/// a bookmarks file with just the one bookmark in it.
+pub fn save_state(path: &Path, bookmark: &Bookmark) -> Result<(), String> {
+    std::fs::write(path, write(bookmark)).map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+/// Where the state's saved on closing the viewer, for `--resume` to pick up.
+pub fn session_path() -> Option<PathBuf> {
+    keys::data_path("session.toml")
+}
+
+/// Saves `bookmark` as the session to resume, returning where it went.
+pub fn save_session(bookmark: &Bookmark) -> Result<PathBuf, String> {
+    let path = session_path().ok_or("no data directory to save the session in")?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
+    }
+    save_state(&path, bookmark)?;
+    Ok(path)
+}
+
+/// Reads the state `save_state` wrote, or the first bookmark of a bookmarks file.
+pub fn load_state(path: &Path) -> Result<Bookmark, String> {
+    let source = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    parse_state(&source).map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+/// Reads the state out of a PNG exported with its `metadata`.
+pub fn load_image_state(path: &Path) -> Result<Bookmark, String> {
+    let text = png::text(path)?;
+    let (_, source) = text.iter().find(|(keyword, _)| keyword == STATE).ok_or(format!("{}: no fractalv state in it", path.display()))?;
+    parse_state(source).map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+fn parse_state(source: &str) -> Result<Bookmark, String> {
+    let mut list = parse(source)?;
+    if list.is_empty() {
+        return Err("no [[bookmark]] in it".to_string());
+    }
+    Ok(list.remove(0))
+}
+
+/// Keyword of the text chunk exported images keep their whole state in.
+const STATE: &str = "fractalv state";
+
+/// Text for an exported image to carry, saying what it's of at a glance and, in full, how to
+/// get back to it. `maxiter` is the iteration limit it was rendered with, if it's known.
+pub fn metadata(bookmark: &Bookmark, maxiter: Option<usize>) -> Vec<(&'static str, String)> {
+    let view = &bookmark.view;
+    let (re, im) = view.pan.exact();
+    let mut text = vec![
+        ("Software", "fractalv".to_string()),
+        ("Fractal", view.fractal[1..].join(" ")),
+        ("Center", format!("{} {}", re, im)),
+        ("Zoom", format!("{:e}", view.scale / HOME_SCALE)),
+        ("Palette", bookmark.palette.clone())
+    ];
+    if let Some(maxiter) = maxiter {
+        text.push(("Iterations", maxiter.to_string()));
+    }
+    text.push((STATE, write(bookmark)));
+    text
+}
+
+/// The command line that opens the viewer on `bookmark`'s view with its palette, quoted for
+/// a shell, after a comment with the iteration limit it had if it has one.
+pub fn command(bookmark: &Bookmark, maxiter: Option<usize>) -> String {
+    let view = &bookmark.view;
+    let (re, im) = view.pan.exact();
+    let mut args = view.fractal[1..].to_vec();
+    args.extend(["--center".to_string(), re.to_string(), im.to_string(), "--zoom".to_string(), format!("{:e}", view.scale / HOME_SCALE)]);
+    if view.rotation != 0. {
+        args.extend(["--rotate".to_string(), view.rotation.to_degrees().to_string()]);
+    }
+    match bookmark.iteration_curve {
+        Some(exponent) if exponent == ITERATION_CURVE => {}
+        Some(exponent) => args.extend(["--iteration-curve".to_string(), exponent.to_string()]),
+        None => args.push("--fixed-iterations".to_string())
+    }
+    args.extend(["--palette".to_string(), bookmark.palette.clone()]);
+
+    let args: Vec<String> = args.iter().map(|arg| shell_quote(arg)).collect();
+    let command = format!("fractv {}", args.join(" "));
+    match maxiter {
+        Some(maxiter) => format!("# {} iterations\n{}", maxiter, command),
+        None => command
+    }
+}
+
+/// `text` as one word to a shell, in single quotes unless it's plain.
+fn shell_quote(text: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "+-./:=_,".contains(c);
+    if !text.is_empty() && text.chars().all(plain) {
+        text.to_string()
+    } else {
+        format!("'{}'", text.replace('\'', "'\\''"))
+    }
+}
+
+fn write(bookmark: &Bookmark) -> String {
+    let view = &bookmark.view;
+    let (re, im) = view.pan.exact();
+    let fractal: Vec<String> = view.fractal[1..].iter().map(|arg| quote(arg)).collect();
+    let mut text = format!("[[bookmark]]\nfractal = [{}]\nre = \"{}\"\nim = \"{}\"\nscale = {:e}\n", fractal.join(", "), re, im, view.scale);
+    text += &format!("rotation = {}\niteration_factor = {}\n", view.rotation.to_degrees(), view.iteration_factor);
+    if let Some(exponent) = bookmark.iteration_curve {
+        text += &format!("iteration_curve = {}\n", exponent);
+    }
+    text += &format!("coloring = \"{}\"\n", bookmark.coloring.name());
+    if let Some(brightness) = bookmark.brightness {
+        text += &format!("brightness = \"{}\"\n", brightness.name());
+    }
+    text += &format!("interior = \"{}\"\nslope = {}\n", bookmark.interior.name(), bookmark.slope);
+    text += &format!("gamma = {}\nexposure = {}\ndither = {}\n", bookmark.gamma, bookmark.exposure, bookmark.dither);
+    text += &format!("palette = {}\npalette_offset = {}\n\n", quote(&bookmark.palette), bookmark.palette_offset);
+    text
+}
+
+/// Reads `[[bookmark]]` tables of `key = value` lines the way `write` puts them.
+fn parse(source: &str) -> Result<Vec<Bookmark>, String> {
+    let tables = tables(source, "bookmark")?;
+    tables.iter().map(|(n, table)| bookmark(table).map_err(|err| format!("bookmark on line {}: {}", n, err))).collect()
+}
+
+/// The `key = value` lines of a table, in order.
+pub type Table = Vec<(String, String)>;
+
+/// Each `[[name]]` table in `source`, with the line number it starts on.
+pub fn tables(source: &str, name: &str) -> Result<Vec<(usize, Table)>, String> {
+    let header = format!("[[{}]]", name);
+    let mut tables: Vec<(usize, Table)> = Vec::new();
+    for (n, line) in source.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line == header {
+            tables.push((n + 1, Vec::new()));
+            continue;
+        }
+        let (key, value) = line.split_once('=').ok_or(format!("line {}: expected key = value", n + 1))?;
+        match tables.last_mut() {
+            Some((_, table)) => table.push((key.trim().to_string(), value.trim().to_string())),
+            None => return Err(format!("line {}: expected {} first", n + 1, header))
+        }
+    }
+    Ok(tables)
+}
+
+fn bookmark(table: &[(String, String)]) -> Result<Bookmark, String> {
+    let get = |key: &str| table.iter().rev().find(|(name, _)| name == key).map(|(_, value)| value.as_str());
+    let required = |key: &str| get(key).ok_or(format!("missing {}", key));
+    let number = |key: &str, default: f64| match get(key) {
+        Some(value) => value.parse::<f64>().map_err(|_| format!("bad {} {}", key, value)),
+        None => Ok(default)
+    };
+    let flag = |key: &str| match get(key) {
+        Some("true") => Ok(true),
+        Some("false") | None => Ok(false),
+        Some(value) => Err(format!("bad {} {}", key, value))
+    };
+
+    let fractal = strings(required("fractal")?).ok_or("bad fractal")?;
+    let (re, im) = (unquote(required("re")?).ok_or("bad re")?, unquote(required("im")?).ok_or("bad im")?);
+    let view = State {
+        fractal: std::iter::once(String::new()).chain(fractal).collect(),
+        pan: Center::parse(&re, &im).ok_or("bad center")?,
+        scale: required("scale")?.parse::<f64>().ok().filter(|scale| *scale > 0.).ok_or("bad scale")?,
+        rotation: number("rotation", 0.)?.to_radians(),
+        iteration_factor: number("iteration_factor", 1.)?
+    };
+    let iteration_curve = match get("iteration_curve") {
+        Some(_) => Some(number("iteration_curve", 0.)?),
+        None => None
+    };
+    let coloring = |key: &str| match get(key) {
+        Some(name) => unquote(name).as_deref().and_then(Coloring::from_name).map(Some).ok_or(format!("bad {} {}", key, name)),
+        None => Ok(None)
+    };
+    let interior = match get("interior") {
+        Some(name) => unquote(name).as_deref().and_then(Interior::from_name).ok_or(format!("bad interior {}", name))?,
+        None => Interior::Flat
+    };
+    Ok(Bookmark {
+        view,
+        coloring: coloring("coloring")?.unwrap_or(Coloring::Banded),
+        brightness: coloring("brightness")?,
+        interior,
+        slope: flag("slope")?,
+        gamma: number("gamma", coloring::GAMMA)?,
+        exposure: number("exposure", 0.)?,
+        dither: flag("dither")?,
+        palette: get("palette").and_then(unquote).unwrap_or_default(),
+        palette_offset: number("palette_offset", 0.)?,
+        iteration_curve
+    })
+}
+
+pub fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Reads a string `quote` wrote.
+pub fn unquote(value: &str) -> Option<String> {
+    match quoted(value)? {
+        (text, "") => Some(text),
+        _ => None
+    }
+}
+
+/// Reads a `["...", ...]` list of quoted strings.
+pub fn strings(value: &str) -> Option<Vec<String>> {
+    let mut rest = value.strip_prefix('[')?.trim_start();
+    let mut list = Vec::new();
+    while !rest.starts_with(']') {
+        let (text, after) = quoted(rest)?;
+        list.push(text);
+        rest = after.trim_start();
+        rest = rest.strip_prefix(',').unwrap_or(rest).trim_start();
+    }
+    (rest == "]").then_some(list)
+}
+
+/// The quoted string `value` starts with, and what's after it.
+fn quoted(value: &str) -> Option<(String, &str)> {
+    let mut chars = value.strip_prefix('"')?.char_indices();
+    let mut text = String::new();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((text, &value[i + 2..])),
+            '\\' => text.push(chars.next()?.1),
+            c => text.push(c)
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{load_state, save_state, write, Bookmark};
+    use crate::coloring::{Coloring, Interior};
+    use crate::fixed::Center;
+    use crate::history::State;
+
+    #[test]
+    fn states_read_back_as_they_were_saved() {
+        let mut pan = Center::parse("-0.7436438870371587047521915110493940171948", "0.1318259042053988596308").unwrap();
+        pan.shift((1.3e-41, -7e-42), 1e42);
+        let saved = Bookmark {
+            view: State {
+                fractal: ["", "custom", "z^3 + c * \"1\" \\ 2"].map(String::from).to_vec(),
+                pan,
+                scale: 1e42,
+                rotation: 0.5,
+                iteration_factor: 1.25
+            },
+            coloring: Coloring::Distance,
+            brightness: Some(Coloring::Stripe),
+            interior: Interior::Period,
+            slope: true,
+            gamma: 2.2,
+            exposure: -0.75,
+            dither: true,
+            palette: "C:\\palettes\\\"sunset\".map".to_string(),
+            palette_offset: 0.3,
+            iteration_curve: Some(0.35)
+        };
+
+        let path = std::env::temp_dir().join(format!("fractv-state-{}.toml", std::process::id()));
+        save_state(&path, &saved).unwrap();
+        let loaded = load_state(&path);
+        std::fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+
+        assert!(loaded.view == saved.view);
+        let (exact, saved_exact) = (loaded.view.pan.exact(), saved.view.pan.exact());
+        assert_eq!((exact.0.to_string(), exact.1.to_string()), (saved_exact.0.to_string(), saved_exact.1.to_string()));
+        assert_eq!(loaded.view.fractal, saved.view.fractal);
+        assert_eq!(loaded.view.rotation.to_bits(), saved.view.rotation.to_bits());
+        assert!(loaded.coloring == saved.coloring && loaded.brightness == saved.brightness && loaded.interior == saved.interior);
+        assert_eq!((loaded.slope, loaded.dither), (saved.slope, saved.dither));
+        assert_eq!((loaded.gamma, loaded.exposure, loaded.palette_offset), (saved.gamma, saved.exposure, saved.palette_offset));
+        assert_eq!((&loaded.palette, loaded.iteration_curve), (&saved.palette, saved.iteration_curve));
+        assert_eq!(write(&loaded), write(&saved));
+    }
+}
@@ -0,0 +1,93 @@
+use crate::FractalContext;
+use crate::orbit::Sample;
+
+/// Rectangles this small are computed pixel by pixel instead of split further.
+const MIN_SIZE: usize = 12;
+
+/// Mariani–Silver subdivision of the `size` rectangle of pixels at `origin`: the border of
+/// each rectangle is computed with `compute`, and when it lies entirely inside the set the
+/// pixels within are filled from it, since a closed loop in a set without holes, like the
+/// filled sets of z^n + c, can't surround anything outside; fractals without that promise
+/// aren't traced. Otherwise the rectangle is split into quarters sharing their edges.
+/// `region` holds the rectangle's samples row by row and `known` which of them already are
+/// final. With `period`, filled borders must also agree on their period. Only interior
+/// borders are filled, as no exterior coloring is constant over a band. Returns `None` if
+/// `compute` declined.
+pub fn trace<F>(
+    ctx: &FractalContext,
+    compute: &F,
+    origin: (usize, usize),
+    size: (usize, usize),
+    region: &mut [Sample],
+    known: &mut [bool],
+    period: bool
+) -> Option<()>
+where
+    F: Fn(&FractalContext, &[usize]) -> Option<Vec<Sample>>
+{
+    let stride = size.0;
+    let fills = |first: &Sample, sample: &Sample| {
+        sample.escape.is_none() && (!period || sample.period == first.period)
+    };
+
+    let mut stack = vec![(0, 0, size.0, size.1)];
+    while let Some((x0, y0, x1, y1)) = stack.pop() {
+        if x1 - x0 <= MIN_SIZE || y1 - y0 <= MIN_SIZE {
+            let points: Vec<_> = (y0..y1).flat_map(|y| (x0..x1).map(move |x| (x, y))).collect();
+            evaluate(ctx, compute, origin, stride, &points, region, known)?;
+            continue;
+        }
+
+        // edge by edge, so vector kernels iterate neighbouring pixels together
+        let border: Vec<_> = [y0, y1 - 1]
+            .into_iter()
+            .flat_map(|y| (x0..x1).map(move |x| (x, y)))
+            .chain([x0, x1 - 1].into_iter().flat_map(|x| (y0 + 1..y1 - 1).map(move |y| (x, y))))
+            .collect();
+        evaluate(ctx, compute, origin, stride, &border, region, known)?;
+
+        let first = region[border[0].0 + border[0].1 * stride];
+        if border.iter().all(|&(x, y)| fills(&first, &region[x + y * stride])) {
+            for y in y0 + 1..y1 - 1 {
+                for i in (x0 + 1..x1 - 1).map(|x| x + y * stride) {
+                    if !known[i] {
+                        region[i] = first;
+                        known[i] = true;
+                    }
+                }
+            }
+            continue;
+        }
+
+        let (mx, my) = ((x0 + x1) / 2, (y0 + y1) / 2);
+        stack.extend([(x0, y0, mx + 1, my + 1), (mx, y0, x1, my + 1), (x0, my, mx + 1, y1), (mx, my, x1, y1)]);
+    }
+    Some(())
+}
+
+/// Computes whichever of `points` aren't known yet in one batch.
+fn evaluate<F>(
+    ctx: &FractalContext,
+    compute: &F,
+    origin: (usize, usize),
+    stride: usize,
+    points: &[(usize, usize)],
+    region: &mut [Sample],
+    known: &mut [bool]
+) -> Option<()>
+where
+    F: Fn(&FractalContext, &[usize]) -> Option<Vec<Sample>>
+{
+    let width = ctx.dimensions.0;
+    let todo: Vec<usize> = points.iter().map(|&(x, y)| x + y * stride).filter(|&i| !known[i]).collect();
+    if todo.is_empty() {
+        return Some(());
+    }
+
+    let pixels: Vec<usize> = todo.iter().map(|&i| origin.0 + i % stride + (origin.1 + i / stride) * width).collect();
+    for (i, sample) in todo.into_iter().zip(compute(ctx, &pixels)?) {
+        region[i] = sample;
+        known[i] = true;
+    }
+    Some(())
+}
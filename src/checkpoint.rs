@@ -0,0 +1,83 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+use crate::bookmarks::{quote, tables};
+
+/// How far a render written out a strip at a time has got, kept beside its output so that
+/// running the same render again after it's interrupted picks up after the last strip it
+/// finished. The finished strips go in `<output>.partial` as they were fed to the image, and
+/// how many there are in `<output>.job`, with the render's arguments to tell it's the same one.
+pub struct Checkpoint {
+    data: File,
+    partial: PathBuf,
+    manifest: PathBuf,
+    /// The render's arguments, size and rows a strip, as the manifest has them.
+    job: [(&'static str, String); 3],
+    /// Strips finished, the first ones of the image.
+    pub done: usize,
+    /// Where in `data` the next strip to read or save goes.
+    offset: u64
+}
+
+impl Checkpoint {
+    /// The checkpoint of rendering `output` with `args` at `size` in strips of `strip` rows,
+    /// which starts over if there's none yet or it's of another render.
+    pub fn open(output: &str, args: &[String], (width, height): (usize, usize), strip: usize) -> Result<Checkpoint, String> {
+        let (partial, manifest) = (PathBuf::from(format!("{}.partial", output)), PathBuf::from(format!("{}.job", output)));
+        let args: Vec<String> = args.iter().map(|arg| quote(arg)).collect();
+        let job = [("job", format!("[{}]", args.join(", "))), ("size", format!("\"{}x{}\"", width, height)), ("strip", strip.to_string())];
+
+        let error = |path: &PathBuf, err: io::Error| format!("{}: {}", path.display(), err);
+        let done = match std::fs::read_to_string(&manifest) {
+            Ok(source) => {
+                let tables = tables(&source, "checkpoint").map_err(|err| format!("{}: {}", manifest.display(), err))?;
+                let done = tables.first().and_then(|(_, table)| {
+                    let get = |key: &str| table.iter().find(|(name, _)| name == key).map(|(_, value)| value.as_str());
+                    let same = job.iter().all(|(key, value)| get(key) == Some(value.as_str()));
+                    get("done").filter(|_| same)?.parse::<usize>().ok()
+                });
+                if done.is_none() {
+                    println!("{} is of another render, starting over", manifest.display());
+                }
+                done.unwrap_or(0)
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => 0,
+            Err(err) => return Err(error(&manifest, err))
+        };
+        let data = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&partial).map_err(|err| error(&partial, err))?;
+        Ok(Checkpoint { data, partial, manifest, job, done, offset: 0 })
+    }
+
+    /// The next finished strip, `length` bytes of it.
+    pub fn read(&mut self, length: usize) -> io::Result<Vec<u8>> {
+        let mut bytes = vec![0; length];
+        self.data.seek(SeekFrom::Start(self.offset))?;
+        self.data.read_exact(&mut bytes)?;
+        self.offset += length as u64;
+        Ok(bytes)
+    }
+
+    /// Keeps the strip after the ones finished, before anything else is written out.
+    pub fn save(&mut self, bytes: &[u8]) -> io::Result<()> {
+        // anything past the finished strips is from a strip that was cut short
+        self.data.set_len(self.offset)?;
+        self.data.seek(SeekFrom::Start(self.offset))?;
+        self.data.write_all(bytes)?;
+        self.data.sync_data()?;
+        self.offset += bytes.len() as u64;
+        self.done += 1;
+        let job: String = self.job.iter().map(|(key, value)| format!("{} = {}\n", key, value)).collect();
+        std::fs::write(&self.manifest, format!("[[checkpoint]]\n{}done = {}\n", job, self.done))
+    }
+
+    /// Clears the checkpoint away once the render's written out whole.
+    pub fn finish(self) -> io::Result<()> {
+        drop(self.data);
+        std::fs::remove_file(&self.partial)?;
+        match std::fs::remove_file(&self.manifest) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(())
+        }
+    }
+}
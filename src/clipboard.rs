@@ -0,0 +1,32 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Programs that put what they read on the system clipboard, tried in order, as minifb has
+/// no clipboard of its own.
+const PROGRAMS: [(&str, &[&str]); 5] = [
+    ("wl-copy", &[]),
+    ("xclip", &["-selection", "clipboard"]),
+    ("xsel", &["--clipboard", "--input"]),
+    ("pbcopy", &[]),
+    ("clip", &[])
+];
+
+/// Puts `text` on the clipboard with the first of the programs that's installed.
+pub fn copy(text: &str) -> Result<(), String> {
+    for (program, args) in PROGRAMS {
+        let mut child = match Command::new(program).args(args).stdin(Stdio::piped()).spawn() {
+            Ok(child) => child,
+            Err(_) => continue
+        };
+        // the program only takes it once its input is closed, when this is dropped
+        if let Some(mut input) = child.stdin.take() {
+            input.write_all(text.as_bytes()).map_err(|err| format!("{}: {}", program, err))?;
+        }
+        let status = child.wait().map_err(|err| format!("{}: {}", program, err))?;
+        if !status.success() {
+            return Err(format!("{} failed with {}", program, status));
+        }
+        return Ok(());
+    }
+    Err("nothing to copy to the clipboard with, install wl-clipboard, xclip or xsel".to_string())
+}
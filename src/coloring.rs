@@ -0,0 +1,316 @@
+use rayon::prelude::*;
+
+use std::f64::consts::PI;
+
+use num_complex::Complex;
+
+use crate::FractalContext;
+use crate::orbit::{Sample, Tracking, Trap};
+
+const STALK_WIDTH: f64 = 0.05;
+/// Default light direction as azimuth and elevation in degrees.
+pub const LIGHT: (f64, f64) = (225., 45.);
+pub const GAMMA: f64 = 2.;
+/// Height of the normal-mapped surface relative to its unit horizontal normal.
+const RELIEF: f64 = 1.5;
+const SPECULAR_EXPONENT: f64 = 20.;
+const BAYER: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Coloring {
+    Banded,
+    Smooth,
+    Histogram,
+    Trap,
+    Distance,
+    Stripe,
+    Tia,
+    Stalks,
+    Angle,
+    AngleShaded,
+    Lighting
+}
+
+impl Coloring {
+    pub fn from_name(name: &str) -> Option<Coloring> {
+        match name {
+            "banded" => Some(Coloring::Banded),
+            "smooth" => Some(Coloring::Smooth),
+            "histogram" => Some(Coloring::Histogram),
+            "trap" => Some(Coloring::Trap),
+            "distance" => Some(Coloring::Distance),
+            "stripe" => Some(Coloring::Stripe),
+            "tia" => Some(Coloring::Tia),
+            "stalks" => Some(Coloring::Stalks),
+            "angle" => Some(Coloring::Angle),
+            "angle-shaded" => Some(Coloring::AngleShaded),
+            "lighting" => Some(Coloring::Lighting),
+            _ => None
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Coloring::Banded => "banded",
+            Coloring::Smooth => "smooth",
+            Coloring::Histogram => "histogram",
+            Coloring::Trap => "trap",
+            Coloring::Distance => "distance",
+            Coloring::Stripe => "stripe",
+            Coloring::Tia => "tia",
+            Coloring::Stalks => "stalks",
+            Coloring::Angle => "angle",
+            Coloring::AngleShaded => "angle-shaded",
+            Coloring::Lighting => "lighting"
+        }
+    }
+
+    /// The per-orbit statistics kernels need to accumulate for this coloring.
+    pub fn tracking(self, trap: Option<Trap>) -> Tracking {
+        Tracking {
+            trap: match self {
+                Coloring::Trap => trap,
+                // Pickover stalks are a cross trap on the real and imaginary axes
+                Coloring::Stalks => Some(Trap::Cross(Complex::new(0., 0.))),
+                _ => None
+            },
+            stripes: (self == Coloring::Stripe).then_some(5.),
+            tia: self == Coloring::Tia
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Interior {
+    Flat,
+    Modulus,
+    Angle,
+    Period
+}
+
+impl Interior {
+    pub fn from_name(name: &str) -> Option<Interior> {
+        match name {
+            "flat" => Some(Interior::Flat),
+            "modulus" => Some(Interior::Modulus),
+            "angle" => Some(Interior::Angle),
+            "period" => Some(Interior::Period),
+            _ => None
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Interior::Flat => "flat",
+            Interior::Modulus => "modulus",
+            Interior::Angle => "angle",
+            Interior::Period => "period"
+        }
+    }
+
+    pub fn next(self) -> Interior {
+        match self {
+            Interior::Flat => Interior::Modulus,
+            Interior::Modulus => Interior::Angle,
+            Interior::Angle => Interior::Period,
+            Interior::Period => Interior::Flat
+        }
+    }
+
+    fn value(self, sample: &Sample) -> f64 {
+        match self {
+            Interior::Flat => 0.,
+            Interior::Modulus => (sample.last.norm() / 2.).min(1.),
+            Interior::Angle => (sample.last.arg() + PI) / (2. * PI),
+            // spread consecutive periods over the palette by the golden ratio
+            Interior::Period if sample.period > 0 => (sample.period as f64 * 0.618034).fract(),
+            Interior::Period => 0.
+        }
+    }
+}
+
+/// Maps the raw samples from the last escape-time render into `ctx.pixels`: each sample is
+/// reduced to linear intensities in `[0, 1]` for the color and, optionally, the brightness
+/// channel, which are then tone mapped and composited.
+pub fn colorize(ctx: &mut FractalContext) {
+    let maxiter = ctx.maxiter as f64;
+    let (coloring, brightness) = (ctx.coloring, ctx.brightness);
+    let palette = &ctx.palette;
+    let offset = ctx.palette_offset;
+    let scale = ctx.scale;
+    let interior = ctx.interior;
+    let (gamma, exposure) = (ctx.gamma, ctx.exposure);
+    // normals are in the plane's axes, so the light turns with the view to stay put on screen
+    let light = light_vector((ctx.light.0 + ctx.rotation.to_degrees(), ctx.light.1));
+    let ranks = if coloring == Coloring::Histogram || brightness == Some(Coloring::Histogram) {
+        escape_ranks(ctx)
+    } else {
+        Vec::new()
+    };
+
+    let smooth = |sample: &Sample| match sample.escape {
+        Some((n, modulus)) => {
+            // normalized iteration count, continuous across band boundaries
+            let nu = n as f64 + 1. - modulus.ln().log2();
+            (1. - nu / maxiter).clamp(0., 1.)
+        }
+        None => 0.
+    };
+    let interior_sample = |sample: &Sample| interior != Interior::Flat && sample.escape.is_none();
+
+    let channel = |coloring: Coloring, sample: &Sample| match coloring {
+        // angle modes take their hue from the angle, the intensity is only their brightness
+        _ if sample.escape.is_none() && matches!(coloring, Coloring::Angle | Coloring::AngleShaded) => 0.,
+        Coloring::Angle => 1.,
+        Coloring::Banded => match sample.escape {
+            Some((n, _)) => 1. - n as f64 / maxiter,
+            None => 0.
+        },
+        Coloring::Smooth | Coloring::Lighting | Coloring::AngleShaded => smooth(sample),
+        Coloring::Histogram => match sample.escape {
+            Some((n, _)) => 1. - ranks[n],
+            None => 0.
+        },
+        Coloring::Trap => (-sample.trap * 8.).exp(),
+        // distance in pixels, so filaments thinner than a pixel still show up
+        Coloring::Distance => (sample.distance * scale / 2.).clamp(0., 1.),
+        Coloring::Stripe if sample.escape.is_some() => sample.stripe,
+        Coloring::Stripe => 0.,
+        Coloring::Tia if sample.escape.is_some() => sample.tia,
+        Coloring::Tia => 0.,
+        Coloring::Stalks => (1. - sample.trap / STALK_WIDTH).max(0.)
+    };
+
+    let intensity: Vec<[f64; 2]> = ctx.samples.par_iter().map(|sample| {
+        if interior_sample(sample) {
+            return [interior.value(sample), 1.];
+        }
+        [channel(coloring, sample), brightness.map_or(1., |brightness| channel(brightness, sample))]
+    }).collect();
+
+    let (width, dither) = (ctx.dimensions.0, ctx.dither);
+    let pixels = ctx.pixels.par_iter_mut().enumerate();
+    pixels.zip(ctx.samples.par_iter().zip(intensity)).for_each(|((i, px), (sample, [value, shade]))| {
+        let value = tone_map(value, gamma, exposure);
+        let shade = if brightness.is_some() { tone_map(shade, gamma, exposure) } else { 1. };
+
+        let color = if matches!(coloring, Coloring::Angle | Coloring::AngleShaded) && !interior_sample(sample) {
+            hsv((sample.angle + PI) / (2. * PI) + offset, 1., value)
+        } else if offset == 0. {
+            palette.color(value)
+        } else {
+            palette.color((value + offset).fract())
+        };
+        *px = quantize(color.map(|channel| channel * shade), i % width, i / width, dither);
+
+        if coloring == Coloring::Lighting && sample.escape.is_some() {
+            *px = scale_rgb(*px, normal_shade(sample.normal, light));
+        }
+    });
+
+    if ctx.slope {
+        slope_shade(ctx);
+    }
+}
+
+/// Scales a linear intensity by `2^exposure` and gamma encodes it back into `[0, 1]`.
+pub fn tone_map(value: f64, gamma: f64, exposure: f64) -> f64 {
+    (value * exposure.exp2()).clamp(0., 1.).powf(1. / gamma)
+}
+
+/// Lights the smooth iteration count as a height field, using central differences between
+/// neighbouring pixels as the surface gradient.
+fn slope_shade(ctx: &mut FractalContext) {
+    let (width, height) = ctx.dimensions;
+    let maxiter = ctx.maxiter as f64;
+
+    let heights: Vec<f64> = ctx.samples.par_iter().map(|sample| match sample.escape {
+        Some((n, modulus)) => n as f64 + 1. - modulus.ln().log2(),
+        None => maxiter
+    }).collect();
+
+    let light = light_vector(ctx.light);
+
+    ctx.pixels.par_iter_mut().enumerate().for_each(|(i, px)| {
+        let (x, y) = (i % width, i / width);
+        let at = |x: usize, y: usize| heights[x.min(width - 1) + y.min(height - 1) * width];
+
+        let dx = (at(x + 1, y) - at(x.saturating_sub(1), y)) / 2.;
+        let dy = (at(x, y + 1) - at(x, y.saturating_sub(1))) / 2.;
+        let norm = (dx * dx + dy * dy + 1.).sqrt();
+        let lambert = ((-dx * light[0] - dy * light[1] + light[2]) / norm).max(0.);
+
+        *px = scale_rgb(*px, 0.3 + 0.7 * lambert);
+    });
+}
+
+/// Lambert plus Blinn-Phong specular lighting of a surface whose horizontal normal is
+/// `normal`, seen from straight above.
+fn normal_shade(normal: Complex<f64>, light: [f64; 3]) -> f64 {
+    let n = [normal.re, normal.im, RELIEF];
+    let len = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+    let dot = |v: [f64; 3]| (n[0] * v[0] + n[1] * v[1] + n[2] * v[2]) / len;
+
+    let half = [light[0], light[1], light[2] + 1.];
+    let half_len = (half[0] * half[0] + half[1] * half[1] + half[2] * half[2]).sqrt();
+    let lambert = dot(light).max(0.);
+    let specular = (dot(half) / half_len).max(0.).powf(SPECULAR_EXPONENT);
+
+    0.2 + 0.7 * lambert + 0.5 * specular
+}
+
+fn light_vector((azimuth, elevation): (f64, f64)) -> [f64; 3] {
+    let (azimuth, elevation) = (azimuth.to_radians(), elevation.to_radians());
+    [elevation.cos() * azimuth.cos(), elevation.cos() * azimuth.sin(), elevation.sin()]
+}
+
+fn scale_rgb(px: u32, factor: f64) -> u32 {
+    let channel = |shift: u32| (((px >> shift) & 0xFF) as f64 * factor).min(255.) as u32;
+    channel(16) << 16 | channel(8) << 8 | channel(0)
+}
+
+/// For each escape iteration, the fraction of escaped pixels that escaped no later than it.
+fn escape_ranks(ctx: &FractalContext) -> Vec<f64> {
+    let mut histogram = vec![0usize; ctx.maxiter];
+    for sample in &ctx.samples {
+        if let Some((n, _)) = sample.escape {
+            histogram[n] += 1;
+        }
+    }
+
+    let total = histogram.iter().sum::<usize>().max(1) as f64;
+    let mut cumulative = 0;
+    histogram
+        .into_iter()
+        .map(|count| {
+            cumulative += count;
+            cumulative as f64 / total
+        })
+        .collect()
+}
+
+/// Packs a color into a pixel, optionally adding a 4x4 ordered dither below the 8-bit step so
+/// smooth gradients don't band.
+pub fn quantize([r, g, b]: [f64; 3], x: usize, y: usize, dither: bool) -> u32 {
+    if !dither {
+        return rgb(r, g, b);
+    }
+    let threshold = (BAYER[y % 4][x % 4] as f64 + 0.5) / 16.;
+    let channel = |v: f64| (v * 255. + threshold).floor().clamp(0., 255.) as u32;
+    channel(r) << 16 | channel(g) << 8 | channel(b)
+}
+
+pub fn rgb(r: f64, g: f64, b: f64) -> u32 {
+    ((r * 255.) as u32) << 16 | ((g * 255.) as u32) << 8 | (b * 255.) as u32
+}
+
+/// Converts hue (in turns, wrapping), saturation and value to RGB components in `[0, 1]`.
+pub fn hsv(h: f64, s: f64, v: f64) -> [f64; 3] {
+    let h = h.rem_euclid(1.) * 6.;
+    let hue = [
+        ((h - 3.).abs() - 1.).clamp(0., 1.),
+        (2. - (h - 2.).abs()).clamp(0., 1.),
+        (2. - (h - 4.).abs()).clamp(0., 1.)
+    ];
+    hue.map(|channel| v * (1. - s + s * channel))
+}
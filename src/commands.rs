@@ -0,0 +1,69 @@
+use std::io::BufRead;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use fractv::fixed::Center;
+use fractv::goto;
+use fractv::keys::{self, Action};
+
+/// What a line on stdin can tell the viewer to do, with `--stdin`.
+pub enum Command {
+    /// What the keys bound to the action do, by its name in the bindings file.
+    Action(Action),
+    Center(Center),
+    /// Relative to the initial view.
+    Zoom(f64),
+    /// In degrees.
+    Rotation(f64),
+    /// A palette's name or file.
+    Palette(String),
+    /// The fractal's arguments, as the command line takes them.
+    Fractal(Vec<String>),
+    /// Save the view as a PNG once it's rendered in full.
+    Render(String),
+    Quit
+}
+
+/// Reads commands off stdin a line at a time on a thread of its own, for the viewer to take
+/// each frame. Lines that aren't commands are said so and left at that.
+pub fn listen() -> Receiver<Command> {
+    let (commands, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => return
+            };
+            match parse(&line) {
+                Ok(Some(command)) => {
+                    if commands.send(command).is_err() {
+                        return;
+                    }
+                }
+                Ok(None) => {}
+                Err(err) => println!("{}", err)
+            }
+        }
+    });
+    receiver
+}
+
+/// Reads a command, `None` for blank lines and comments.
+fn parse(line: &str) -> Result<Option<Command>, String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let bad = || format!("can't {}", line.trim());
+    let command = match words[..] {
+        [] => return Ok(None),
+        [word, ..] if word.starts_with('#') => return Ok(None),
+        ["set", "center", re, im] => Command::Center(Center::parse(re, im).ok_or_else(bad)?),
+        ["set", "zoom", zoom] => Command::Zoom(goto::parse_zoom(zoom).ok_or_else(bad)?),
+        ["set", "rotation", degrees] => Command::Rotation(degrees.parse::<f64>().ok().filter(|degrees| degrees.is_finite()).ok_or_else(bad)?),
+        ["set", "palette", _, ..] => Command::Palette(words[2..].join(" ")),
+        ["set", "fractal", _, ..] => Command::Fractal(words[2..].iter().map(|word| word.to_string()).collect()),
+        ["render", _, ..] => Command::Render(words[1..].join(" ")),
+        ["quit"] => Command::Quit,
+        [name] => Command::Action(keys::action(name).ok_or(format!("no command or action {}", name))?),
+        _ => return Err(bad())
+    };
+    Ok(Some(command))
+}
@@ -0,0 +1,72 @@
+use std::time::Duration;
+
+use crate::bookmarks::unquote;
+use crate::{keys, HEIGHT, WIDTH};
+
+/// Defaults for the viewer in `config.toml` beside `keys.toml`, for what the command line
+/// doesn't say.
+pub struct Config {
+    /// Size of the window to begin with, and of what `render` and `animate` render.
+    pub size: (usize, usize),
+    /// The fractal and its arguments, as they'd be given on the command line, to show when
+    /// none is.
+    pub fractal: Option<Vec<String>>,
+    pub palette: Option<String>,
+    /// Least time between redraws of the window.
+    pub frame_time: Duration,
+    /// Threads to render on, or one for each core.
+    pub threads: Option<usize>,
+    /// Factor each notch of the scroll wheel zooms by.
+    pub wheel_zoom: f64,
+    /// Share of the palette it's moved along by each frame it cycles.
+    pub cycle_speed: f64
+}
+
+impl Config {
+    pub fn load() -> Result<Config, String> {
+        let mut config = Config {
+            size: (WIDTH, HEIGHT),
+            fractal: None,
+            palette: None,
+            frame_time: Duration::from_micros(16600),
+            threads: None,
+            wheel_zoom: 1.1,
+            cycle_speed: 0.005
+        };
+        let path = match keys::config_path("config.toml") {
+            Some(path) if path.exists() => path,
+            _ => return Ok(config)
+        };
+        let source = std::fs::read_to_string(&path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        let mut iterations = None;
+        for (n, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: &str| format!("{}: line {}: {}", path.display(), n + 1, message);
+            let (key, value) = line.split_once('=').ok_or(error("expected key = value"))?;
+            let value = value.trim();
+            let number = || value.parse::<f64>().ok().filter(|number| number.is_finite() && *number > 0.).ok_or(error(&format!("bad {}", value)));
+            let count = || value.parse::<usize>().ok().filter(|count| *count > 0).ok_or(error(&format!("bad {}", value)));
+            let text = || unquote(value).ok_or(error(&format!("expected a quoted string, not {}", value)));
+            match key.trim() {
+                "width" => config.size.0 = count()?,
+                "height" => config.size.1 = count()?,
+                "fractal" => config.fractal = Some(text()?.split_whitespace().map(String::from).collect()),
+                "iterations" => iterations = Some(count()?),
+                "palette" => config.palette = Some(text()?),
+                "fps" => config.frame_time = Duration::from_secs_f64(1. / number()?),
+                "threads" => config.threads = Some(count()?),
+                "wheel_zoom" => config.wheel_zoom = number()?,
+                "cycle_speed" => config.cycle_speed = number()?,
+                key => return Err(error(&format!("unknown setting {}", key)))
+            }
+        }
+        // the Mandelbrot set's iterations if no fractal's given
+        if let Some(iterations) = iterations {
+            config.fractal.get_or_insert_with(|| vec!["mandelbrot".to_string()]).extend(["--iterations".to_string(), iterations.to_string()]);
+        }
+        Ok(config)
+    }
+}
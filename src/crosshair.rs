@@ -0,0 +1,23 @@
+/// Cross marking the view center, drawn over the displayed frame only, so the rendered pixels
+/// never carry it.
+pub struct Crosshair {
+    pub visible: bool,
+    /// Length of each arm in pixels, 0 for a single pixel.
+    pub size: usize,
+    pub color: u32
+}
+
+impl Crosshair {
+    pub fn draw(&self, frame: &mut [u32], (width, height): (usize, usize)) {
+        if !self.visible || width == 0 || height == 0 {
+            return;
+        }
+        let (x, y) = (width / 2, height / 2);
+        for px in x.saturating_sub(self.size)..=(x + self.size).min(width - 1) {
+            frame[px + y * width] = self.color;
+        }
+        for py in y.saturating_sub(self.size)..=(y + self.size).min(height - 1) {
+            frame[x + py * width] = self.color;
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use std::io::{self, Write};
+
+/// A CSV of each pixel's escape iteration, -1 for points that didn't escape, a line for each
+/// pixel with its `x` and `y` and, with `modulus`, the final |z|.
+pub struct Writer<W: Write> {
+    out: W,
+    width: usize,
+    modulus: bool,
+    /// The next row to write.
+    y: usize
+}
+
+impl<W: Write> Writer<W> {
+    pub fn new(mut out: W, width: usize, modulus: bool) -> io::Result<Writer<W>> {
+        writeln!(out, "x,y,iterations{}", if modulus { ",modulus" } else { "" })?;
+        Ok(Writer { out, width, modulus, y: 0 })
+    }
+
+    /// Adds the next whole rows of the image.
+    pub fn rows(&mut self, iterations: &[i32], modulus: &[f64]) -> io::Result<()> {
+        for (i, (n, z)) in iterations.iter().zip(modulus).enumerate() {
+            let (x, y) = (i % self.width, self.y + i / self.width);
+            if self.modulus {
+                writeln!(self.out, "{},{},{},{}", x, y, n, z)?;
+            } else {
+                writeln!(self.out, "{},{},{}", x, y, n)?;
+            }
+        }
+        self.y += iterations.len() / self.width.max(1);
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
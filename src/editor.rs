@@ -0,0 +1,170 @@
+use minifb::{Key, KeyRepeat, MouseButton, MouseMode, Window};
+
+use fractv::coloring::rgb;
+use fractv::palette::Palette;
+
+const MARGIN: usize = 12;
+const BAR_HEIGHT: usize = 20;
+const HANDLE: usize = 4;
+const COLOR_STEP: f64 = 1. / 16.;
+
+/// Keys nudging a channel of the selected stop's color down or up.
+const CHANNEL_KEYS: [(Key, usize, f64); 6] = [
+    (Key::Key1, 0, -COLOR_STEP),
+    (Key::Key2, 0, COLOR_STEP),
+    (Key::Key3, 1, -COLOR_STEP),
+    (Key::Key4, 1, COLOR_STEP),
+    (Key::Key5, 2, -COLOR_STEP),
+    (Key::Key6, 2, COLOR_STEP)
+];
+
+/// Gradient bar drawn along the bottom of the window, with a handle above it for each stop.
+/// Clicking the bar adds a stop, dragging a handle moves it, right-clicking or Delete removes
+/// it and the number keys recolor the selected stop.
+#[derive(Default)]
+pub struct GradientEditor {
+    pub open: bool,
+    selected: Option<usize>,
+    dragging: bool,
+    buttons: (bool, bool)
+}
+
+struct Layout {
+    left: usize,
+    span: usize,
+    bar: usize,
+    handles: usize
+}
+
+impl Layout {
+    fn new((width, height): (usize, usize)) -> Option<Layout> {
+        let span = width.checked_sub(2 * MARGIN + 1)?;
+        let bar = height.checked_sub(MARGIN + BAR_HEIGHT)?;
+        let handles = bar.checked_sub(2 * HANDLE + 3)?;
+        (span > 0).then_some(Layout { left: MARGIN, span, bar, handles })
+    }
+
+    fn x(&self, pos: f64) -> usize {
+        self.left + (pos * self.span as f64).round() as usize
+    }
+
+    fn pos(&self, x: f32) -> f64 {
+        ((x as f64 - self.left as f64) / self.span as f64).clamp(0., 1.)
+    }
+
+    fn on_bar(&self, x: f32, y: f32) -> bool {
+        let (x, y) = (x as usize, y as usize);
+        x >= self.left && x <= self.left + self.span && y >= self.bar && y < self.bar + BAR_HEIGHT
+    }
+
+    fn on_handle(&self, stop_x: usize, x: f32, y: f32) -> bool {
+        (x - stop_x as f32).abs() <= HANDLE as f32 + 1.
+            && y >= self.handles as f32
+            && y <= (self.handles + 2 * HANDLE) as f32
+    }
+}
+
+impl GradientEditor {
+    /// Applies this frame's mouse and key input to `palette`, returning whether it changed.
+    pub fn handle(&mut self, window: &Window, palette: &mut Palette, dimensions: (usize, usize)) -> bool {
+        let layout = match Layout::new(dimensions) {
+            Some(layout) => layout,
+            None => return false
+        };
+        let buttons = (window.get_mouse_down(MouseButton::Left), window.get_mouse_down(MouseButton::Right));
+        let pressed = (buttons.0 && !self.buttons.0, buttons.1 && !self.buttons.1);
+        self.buttons = buttons;
+        if !buttons.0 {
+            self.dragging = false;
+        }
+
+        let mut changed = false;
+        if let Some((x, y)) = window.get_mouse_pos(MouseMode::Discard) {
+            let hit = (0..palette.stop_count()).find(|&i| layout.on_handle(layout.x(palette.stop(i).0), x, y));
+
+            if pressed.0 {
+                match hit {
+                    Some(i) => self.selected = Some(i),
+                    None if layout.on_bar(x, y) => {
+                        let pos = layout.pos(x);
+                        self.selected = Some(palette.insert_stop(pos, palette.color(pos)));
+                        changed = true;
+                    }
+                    None => self.selected = None
+                }
+                self.dragging = self.selected.is_some();
+            } else if self.dragging {
+                if let Some(i) = self.selected {
+                    let (pos, color) = palette.stop(i);
+                    if layout.pos(x) != pos {
+                        self.selected = Some(palette.set_stop(i, layout.pos(x), color));
+                        changed = true;
+                    }
+                }
+            }
+
+            if pressed.1 {
+                if let Some(i) = hit {
+                    changed |= self.remove(palette, i);
+                }
+            }
+        }
+
+        if let Some(i) = self.selected {
+            if window.is_key_pressed(Key::Delete, KeyRepeat::No) {
+                changed |= self.remove(palette, i);
+            } else {
+                for (key, channel, step) in CHANNEL_KEYS {
+                    if window.is_key_pressed(key, KeyRepeat::Yes) {
+                        let (pos, mut color) = palette.stop(i);
+                        color[channel] = (color[channel] + step).clamp(0., 1.);
+                        self.selected = Some(palette.set_stop(i, pos, color));
+                        changed = true;
+                    }
+                }
+            }
+        }
+        changed
+    }
+
+    fn remove(&mut self, palette: &mut Palette, i: usize) -> bool {
+        // a gradient needs both ends
+        if palette.stop_count() <= 2 {
+            return false;
+        }
+        palette.remove_stop(i);
+        self.selected = None;
+        self.dragging = false;
+        true
+    }
+
+    pub fn draw(&self, frame: &mut [u32], dimensions: (usize, usize), palette: &Palette) {
+        let layout = match Layout::new(dimensions) {
+            Some(layout) => layout,
+            None => return
+        };
+        let width = dimensions.0;
+        let mut fill = |x0: usize, y0: usize, x1: usize, y1: usize, color: u32| {
+            for y in y0..y1 {
+                for x in x0..x1.min(width) {
+                    frame[x + y * width] = color;
+                }
+            }
+        };
+
+        let (white, black) = (rgb(1., 1., 1.), rgb(0., 0., 0.));
+        fill(layout.left - 1, layout.bar - 1, layout.left + layout.span + 2, layout.bar + BAR_HEIGHT + 1, white);
+        for x in 0..=layout.span {
+            let [r, g, b] = palette.color(x as f64 / layout.span as f64);
+            fill(layout.left + x, layout.bar, layout.left + x + 1, layout.bar + BAR_HEIGHT, rgb(r, g, b));
+        }
+
+        for i in 0..palette.stop_count() {
+            let (pos, [r, g, b]) = palette.stop(i);
+            let x = layout.x(pos);
+            let border = if self.selected == Some(i) { white } else { black };
+            fill(x - HANDLE - 1, layout.handles - 1, x + HANDLE + 2, layout.handles + 2 * HANDLE + 2, border);
+            fill(x - HANDLE, layout.handles, x + HANDLE + 1, layout.handles + 2 * HANDLE + 1, rgb(r, g, b));
+        }
+    }
+}
@@ -0,0 +1,412 @@
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::ops::Range;
+use std::time::Instant;
+
+use crate::bookmarks::{self, Bookmark};
+use crate::checkpoint::Checkpoint;
+use crate::fixed::Center;
+use crate::config::Config;
+use crate::{csv, exr, gif, goto, npy};
+use crate::keyframes::Tour;
+use crate::orbit::Sample;
+use crate::palette::Palette;
+use crate::{parse_fractal, parse_options, parse_size, png, rotate, take_flag, take_option, take_values, timestamp, Fractal, FractalContext};
+use crate::{HEIGHT, HOME_SCALE, WIDTH};
+
+/// Images with more pixels than this are rendered a strip at a time unless `--strip` says
+/// how, as a whole one wouldn't fit in memory with its samples.
+const LARGE: usize = 4096 * 4096;
+/// Rows in each strip of a large image.
+const STRIP: usize = 256;
+
+/// Frames a zoom has unless `--frames` says otherwise.
+const FRAMES: usize = 100;
+/// Frames a second of a tour unless `--fps` says otherwise.
+const FPS: f64 = 30.;
+
+/// The size of the images to render and how many rows of them to render at a time, taken
+/// from `--size` and `--strip`. Ones being `checkpointed` are in strips unless they're small.
+fn take_size(args: &mut Vec<String>, config: &Config, checkpointed: bool) -> Option<((usize, usize), usize)> {
+    let size = match take_option(args, "--size") {
+        Some(value) => parse_size(&value)?,
+        None => config.size
+    };
+    let strip = match take_option(args, "--strip") {
+        Some(value) => value.parse::<usize>().ok().filter(|rows| *rows > 0)?,
+        None if size.0 * size.1 > LARGE || checkpointed => STRIP.min(size.1),
+        None => size.1
+    };
+    Some((size, strip))
+}
+
+/// Renders the view the rest of `args` give to completion at the size `--size` asks for,
+/// without a window, and writes it to `--output`, in strips of `--strip` rows if it's given.
+/// With `--checkpoint` the strips are kept as they're finished, for the same command to carry
+/// on from if it's interrupted, and with `--modulus` iteration data has the final |z| too.
+/// Returns false if the arguments don't make sense.
+pub fn run(args: &mut Vec<String>, config: &Config) -> bool {
+    let checkpointed = take_flag(args, "--checkpoint");
+    let modulus = take_flag(args, "--modulus");
+    let job = args.clone();
+    let (size, strip) = match take_size(args, config, checkpointed) {
+        Some(size) => size,
+        None => return false
+    };
+    let output = take_option(args, "--output").unwrap_or_else(|| format!("fractalv-{}.png", timestamp()));
+    let (options, fract) = match parse_options(args, config).zip(parse_fractal(args)) {
+        Some(parsed) => parsed,
+        None => return false
+    };
+
+    let mut ctx = FractalContext::from_options(&options);
+    ctx.place(&fract, &options);
+    let state = ctx.bookmark(args.clone());
+    fit(&mut ctx, size);
+    let checkpoint = if checkpointed {
+        match Checkpoint::open(&output, &job, size, strip) {
+            Ok(checkpoint) => Some(checkpoint),
+            Err(err) => {
+                println!("{}", err);
+                return true;
+            }
+        }
+    } else {
+        None
+    };
+    let start = Instant::now();
+    match Image::create(&output, size, modulus).and_then(|image| write(image, &fract, &mut ctx, size, strip, &state, checkpoint)) {
+        Ok(()) => println!("rendered {}x{} in {:.1} s, saved {}", size.0, size.1, start.elapsed().as_secs_f64(), output),
+        Err(err) => println!("{}: {}", output, err)
+    }
+    true
+}
+
+/// What changes from one frame of an animation to the next.
+enum Motion {
+    /// The view moves through the tour, the frames this many seconds apart.
+    Tour(Tour, f64),
+    /// The view stays where it is and the palette goes round once.
+    Cycle(Bookmark)
+}
+
+/// Renders the `--keyframes` tour at `--fps` frames a second, or else `--frames` images
+/// zooming from `--from <re> <im> <zoom>`, or the fractal's initial view, to the view the
+/// rest of `args` give, or of that view `--cycle`ing the palette. They're written as
+/// `--output` followed by the frame number, or all to it if it's a GIF. Returns false if the
+/// arguments don't make sense.
+pub fn animate(args: &mut Vec<String>, config: &Config) -> bool {
+    let (size, strip) = match take_size(args, config, false) {
+        Some(size) => size,
+        None => return false
+    };
+    let frames = match take_option(args, "--frames") {
+        Some(value) => match value.parse::<usize>() {
+            Ok(frames) if frames > 0 => frames,
+            _ => return false
+        },
+        None => FRAMES
+    };
+    let fps = match take_option(args, "--fps") {
+        Some(value) => match value.parse::<f64>() {
+            Ok(fps) if fps > 0. && fps.is_finite() => fps,
+            _ => return false
+        },
+        None => FPS
+    };
+    let from = match take_values(args, "--from", 3) {
+        Some(values) => match Center::parse(&values[0], &values[1]).zip(goto::parse_zoom(&values[2])) {
+            Some((pan, zoom)) => Some((pan, HOME_SCALE * zoom)),
+            None => return false
+        },
+        None => None
+    };
+    let cycle = take_flag(args, "--cycle");
+    let output = take_option(args, "--output").unwrap_or_else(|| "frame-".to_string());
+    let (mut options, fract) = match parse_options(args, config).zip(parse_fractal(args)) {
+        Some(parsed) => parsed,
+        None => return false
+    };
+
+    let mut ctx = FractalContext::from_options(&options);
+    ctx.place(&fract, &options);
+    let (motion, frames) = match options.tour.take() {
+        _ if cycle => {
+            let state = ctx.bookmark(args.clone());
+            fit(&mut ctx, size);
+            (Motion::Cycle(state), frames)
+        }
+        Some(tour) => {
+            let frames = (tour.duration() * fps).floor() as usize + 1;
+            (Motion::Tour(tour, 1. / fps), frames)
+        }
+        None => {
+            let to = (ctx.pan.clone(), ctx.scale, ctx.rotation);
+            let tour = Tour::zoom(from.unwrap_or_else(|| fract.home()), to, ctx.iteration_factor);
+            (Motion::Tour(tour, if frames > 1 { 1. / (frames - 1) as f64 } else { 1. }), frames)
+        }
+    };
+    let animation = Animation { motion, frames, size, strip, output, fps, fractal: args.clone() };
+    let start = Instant::now();
+    match animation.render(&fract, &mut ctx, &options.palettes) {
+        Ok(()) => println!("rendered {} frames in {:.1} s", frames, start.elapsed().as_secs_f64()),
+        Err(err) => println!("{}", err)
+    }
+    true
+}
+
+struct Animation {
+    motion: Motion,
+    frames: usize,
+    size: (usize, usize),
+    strip: usize,
+    output: String,
+    fps: f64,
+    /// The fractal's arguments, for the state each frame keeps.
+    fractal: Vec<String>
+}
+
+impl Animation {
+    fn render(&self, fract: &Fractal, ctx: &mut FractalContext, palettes: &[Palette]) -> io::Result<()> {
+        let mut gif = if self.output.ends_with(".gif") {
+            Some(gif::Writer::new(BufWriter::new(File::create(&self.output)?), self.size)?)
+        } else {
+            None
+        };
+        // GIF times frames in hundredths of a second, and players slow down ones under two
+        let delay = (100. / self.fps).round().clamp(2., u16::MAX as f64) as u16;
+        let offset = ctx.palette_offset;
+        for frame in 0..self.frames {
+            let (state, recolor) = match &self.motion {
+                Motion::Tour(tour, step) => {
+                    let view = tour.at(frame as f64 * step);
+                    (ctx.pan, ctx.scale, ctx.rotation, ctx.iteration_factor) = (view.pan, view.scale, view.rotation, view.iteration_factor);
+                    if let Some(palette) = view.palette {
+                        ctx.palette = palettes[palette].clone();
+                    }
+                    ctx.palette_offset = view.palette_offset;
+                    let state = ctx.bookmark(self.fractal.clone());
+                    fit(ctx, self.size);
+                    (state, false)
+                }
+                Motion::Cycle(still) => {
+                    ctx.palette_offset = (offset + frame as f64 / self.frames as f64).fract();
+                    let state = Bookmark { palette_offset: ctx.palette_offset, ..still.clone() };
+                    // the first frame leaves the samples of the whole image behind, unless it's in strips
+                    (state, frame > 0 && (gif.is_some() || self.strip >= self.size.1))
+                }
+            };
+            let recolored = recolor && Fractal::recolor(ctx);
+            match &mut gif {
+                Some(gif) => {
+                    if !recolored {
+                        render_rows(fract, ctx, self.size, (0, self.size.1));
+                    }
+                    gif.frame(&ctx.pixels, delay)?;
+                    println!("frame {} of {}", frame + 1, self.frames);
+                }
+                None => {
+                    let path = format!("{}{:05}.png", self.output, frame);
+                    let result = if recolored {
+                        std::fs::write(&path, png::encode(&ctx.pixels, self.size, &metadata(&state, ctx)))
+                    } else {
+                        Image::create(&path, self.size, false).and_then(|image| write(image, fract, ctx, self.size, self.strip, &state, None))
+                    };
+                    result.map_err(|err| io::Error::new(err.kind(), format!("{}: {}", path, err)))?;
+                    println!("frame {} of {}, saved {}", frame + 1, self.frames, path);
+                }
+            }
+        }
+        if let Some(gif) = gif {
+            gif.finish()?;
+            println!("saved {}", self.output);
+        }
+        Ok(())
+    }
+}
+
+/// Renders the image `strip` rows at a time, writing each out to `writer` before starting the
+/// next, a PNG with `state` in it. Strips the `checkpoint` has are taken from it rather than
+/// rendered again, and the rest are added to it.
+fn write(mut writer: Image, fract: &Fractal, ctx: &mut FractalContext, size: (usize, usize), strip: usize, state: &Bookmark, mut checkpoint: Option<Checkpoint>) -> io::Result<()> {
+    let strips = size.1.div_ceil(strip);
+    for n in 0..strips {
+        let (top, bottom) = (n * strip, ((n + 1) * strip).min(size.1));
+        let rows = match &mut checkpoint {
+            Some(checkpoint) if n < checkpoint.done => {
+                let rows = checkpoint.read((bottom - top) * size.0 * writer.pixel_bytes())?;
+                println!("strip {} of {} from the checkpoint", n + 1, strips);
+                rows
+            }
+            _ => {
+                let range = render_rows(fract, ctx, size, (top, bottom));
+                let rows = writer.take(ctx, range)?;
+                if let Some(checkpoint) = &mut checkpoint {
+                    checkpoint.save(&rows)?;
+                }
+                if strips > 1 {
+                    println!("strip {} of {}", n + 1, strips);
+                }
+                rows
+            }
+        };
+        writer.rows(&rows)?;
+    }
+    match writer {
+        Image::Png(writer) => writer.finish(&metadata(state, ctx))?,
+        Image::Exr(writer) => writer.finish()?,
+        Image::Npy(writer) => writer.finish()?,
+        Image::Csv(writer) => writer.finish()?
+    }
+    match checkpoint {
+        Some(checkpoint) => checkpoint.finish(),
+        None => Ok(())
+    }
+}
+
+/// The text a PNG of `state` keeps, once `ctx` has rendered it.
+fn metadata(state: &Bookmark, ctx: &FractalContext) -> Vec<(&'static str, String)> {
+    // only escape-time fractals have an iteration limit
+    bookmarks::metadata(state, Some(ctx.maxiter).filter(|&maxiter| maxiter > 0))
+}
+
+/// Where an image is written, by its extension: the float channels of its samples in an EXR,
+/// their iterations in a NumPy array or a CSV, and otherwise its pixels in a PNG.
+enum Image {
+    Png(png::Writer<BufWriter<File>>),
+    Exr(exr::Writer<BufWriter<File>>),
+    Npy(npy::Writer<BufWriter<File>>),
+    Csv(csv::Writer<BufWriter<File>>)
+}
+
+impl Image {
+    /// Starts the image at `output`, with the final |z| beside the iterations if `modulus`.
+    fn create(output: &str, size: (usize, usize), modulus: bool) -> io::Result<Image> {
+        let out = BufWriter::new(File::create(output)?);
+        Ok(match output.rsplit_once('.').map(|(_, extension)| extension) {
+            Some("exr") => Image::Exr(exr::Writer::new(out, size, &CHANNELS)?),
+            Some("npy") => Image::Npy(npy::Writer::new(out, size, modulus)?),
+            Some("csv") => Image::Csv(csv::Writer::new(out, size.0, modulus)?),
+            _ => Image::Png(png::Writer::new(out, size)?)
+        })
+    }
+
+    /// Bytes a pixel takes between rendering and writing out, in a checkpoint too.
+    fn pixel_bytes(&self) -> usize {
+        match self {
+            Image::Png(_) => 4,
+            Image::Exr(_) => 4 * CHANNELS.len(),
+            Image::Npy(_) | Image::Csv(_) => 4 + 8
+        }
+    }
+
+    /// What the image takes of the pixels or samples `range` of `ctx`, as bytes: the pixels,
+    /// or each channel in turn.
+    fn take(&self, ctx: &FractalContext, range: Range<usize>) -> io::Result<Vec<u8>> {
+        if let Image::Png(_) = self {
+            return Ok(ctx.pixels[range].iter().flat_map(|pixel| pixel.to_le_bytes()).collect());
+        }
+        let samples = ctx.samples.get(range).ok_or_else(|| io::Error::other("only escape-time renders on the CPU keep samples to export"))?;
+        Ok(match self {
+            Image::Exr(_) => channels(samples, ctx.scale).iter().flatten().flat_map(|value| value.to_le_bytes()).collect(),
+            _ => {
+                let (iterations, modulus) = iterations(samples);
+                let iterations = iterations.iter().flat_map(|n| n.to_le_bytes());
+                iterations.chain(modulus.iter().flat_map(|z| z.to_le_bytes())).collect()
+            }
+        })
+    }
+
+    /// Adds the next rows to the image, given as `take` gave them.
+    fn rows(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let words = |bytes: &[u8]| bytes.chunks_exact(4).map(|word| [word[0], word[1], word[2], word[3]]).collect::<Vec<_>>();
+        match self {
+            Image::Png(writer) => writer.rows(&words(bytes).into_iter().map(u32::from_le_bytes).collect::<Vec<_>>()),
+            Image::Exr(writer) => {
+                let values: Vec<f32> = words(bytes).into_iter().map(f32::from_le_bytes).collect();
+                let channels: Vec<Vec<f32>> = values.chunks(values.len() / CHANNELS.len()).map(<[f32]>::to_vec).collect();
+                writer.rows(&channels)
+            }
+            Image::Npy(writer) => {
+                let (iterations, modulus) = unzip(bytes);
+                writer.rows(&iterations, &modulus)
+            }
+            Image::Csv(writer) => {
+                let (iterations, modulus) = unzip(bytes);
+                writer.rows(&iterations, &modulus)
+            }
+        }
+    }
+}
+
+/// The channels of an EXR export, in the order `channels` gives them.
+const CHANNELS: [&str; 2] = ["distance", "iterations"];
+
+/// The distance estimate of each sample, in pixels, and the smooth iteration count, -1 for
+/// points that didn't escape.
+fn channels(samples: &[Sample], scale: f64) -> [Vec<f32>; 2] {
+    let distance = samples.iter().map(|sample| if sample.escape.is_some() { (sample.distance * scale) as f32 } else { 0. }).collect();
+    let iterations = samples
+        .iter()
+        .map(|sample| match sample.escape {
+            Some((n, modulus)) => (n as f64 + 1. - modulus.ln().log2()) as f32,
+            None => -1.
+        })
+        .collect();
+    [distance, iterations]
+}
+
+/// The iteration each sample escaped at, -1 for ones that didn't, and |z| then or at the
+/// last iteration.
+fn iterations(samples: &[Sample]) -> (Vec<i32>, Vec<f64>) {
+    samples
+        .iter()
+        .map(|sample| match sample.escape {
+            Some((n, modulus)) => (n as i32, modulus),
+            None => (-1, sample.last.norm())
+        })
+        .unzip()
+}
+
+/// The iterations and |z| of the samples `take` gave the bytes of.
+fn unzip(bytes: &[u8]) -> (Vec<i32>, Vec<f64>) {
+    let (iterations, modulus) = bytes.split_at(bytes.len() / 3);
+    let iterations = iterations.chunks_exact(4).map(|word| i32::from_le_bytes([word[0], word[1], word[2], word[3]])).collect();
+    let modulus = modulus.chunks_exact(8).map(|word| f64::from_le_bytes(std::array::from_fn(|i| word[i]))).collect();
+    (iterations, modulus)
+}
+
+/// Has `ctx`, set up for a view at the window's initial size, cover the same part of the
+/// plane at `dimensions` with the same iteration limit.
+fn fit(ctx: &mut FractalContext, dimensions: (usize, usize)) {
+    let factor = (dimensions.0 as f64 / WIDTH as f64).min(dimensions.1 as f64 / HEIGHT as f64);
+    // the iteration curve would take the finer pixels for a deeper zoom
+    if let Some(exponent) = ctx.iteration_curve {
+        let zoom = ctx.scale / HOME_SCALE;
+        ctx.iteration_factor *= (zoom.max(1.) / (zoom * factor).max(1.)).powf(exponent);
+    }
+    ctx.scale *= factor;
+    ctx.dimensions = dimensions;
+    ctx.frame_budget = None;
+}
+
+/// Renders rows `top..bottom` of the `size` image `ctx` is fitted to, leaving `ctx` as it
+/// was but for its pixels and samples, and returns where in those the rows are. Strips are
+/// rendered a row over on each side where there is one, so slope shading has the neighbors
+/// it'd have in the whole image.
+fn render_rows(fract: &Fractal, ctx: &mut FractalContext, size: (usize, usize), (top, bottom): (usize, usize)) -> Range<usize> {
+    let (above, below) = (top.min(1), (size.1 - bottom).min(1));
+    let (first, last) = (top - above, bottom + below);
+    let pan = ctx.pan.clone();
+    if (first, last) != (0, size.1) {
+        // the strip's center, relative to the image's
+        let offset = rotate((0., (first + last) as f64 / 2. - size.1 as f64 / 2.), ctx.rotation);
+        ctx.pan.shift((offset.0 / ctx.scale, offset.1 / ctx.scale), ctx.scale);
+    }
+    ctx.dimensions = (size.0, last - first);
+    ctx.updated = true;
+    fract.render_all(ctx);
+    ctx.pan = pan;
+    ctx.dimensions = size;
+    above * size.0..(above + bottom - top) * size.0
+}
@@ -0,0 +1,85 @@
+use std::io::{self, Write};
+
+/// Pixel type of a channel of 32-bit floats.
+const FLOAT: i32 = 2;
+
+/// An uncompressed scanline OpenEXR of 32-bit float channels, written a few rows at a time.
+/// Rows all take the same room, so the table of where each one starts can go out first.
+pub struct Writer<W: Write> {
+    out: W,
+    width: usize,
+    /// Number of channels, in the alphabetical order EXR keeps them in.
+    channels: usize,
+    /// The next row to write.
+    y: usize
+}
+
+impl<W: Write> Writer<W> {
+    /// Starts an EXR with the `channels` named, which have to be in alphabetical order.
+    pub fn new(mut out: W, (width, height): (usize, usize), channels: &[&str]) -> io::Result<Writer<W>> {
+        let mut header = Vec::new();
+        header.extend([0x76, 0x2F, 0x31, 0x01, 2, 0, 0, 0]);
+
+        let mut list = Vec::new();
+        for name in channels {
+            list.extend(name.as_bytes());
+            list.push(0);
+            list.extend(FLOAT.to_le_bytes());
+            // not perceptually linear, three reserved bytes, no subsampling
+            list.extend([0, 0, 0, 0]);
+            list.extend(1i32.to_le_bytes());
+            list.extend(1i32.to_le_bytes());
+        }
+        list.push(0);
+        attribute(&mut header, "channels", "chlist", &list);
+        attribute(&mut header, "compression", "compression", &[0]);
+        let window: Vec<u8> = [0, 0, width as i32 - 1, height as i32 - 1].iter().flat_map(|n| n.to_le_bytes()).collect();
+        attribute(&mut header, "dataWindow", "box2i", &window);
+        attribute(&mut header, "displayWindow", "box2i", &window);
+        // rows from the top down
+        attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+        attribute(&mut header, "pixelAspectRatio", "float", &1f32.to_le_bytes());
+        attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+        attribute(&mut header, "screenWindowWidth", "float", &1f32.to_le_bytes());
+        header.push(0);
+
+        // each row is its y and byte count followed by its floats channel by channel
+        let row = 8 + width * channels.len() * 4;
+        let start = header.len() + height * 8;
+        for y in 0..height {
+            header.extend(((start + y * row) as u64).to_le_bytes());
+        }
+        out.write_all(&header)?;
+        Ok(Writer { out, width, channels: channels.len(), y: 0 })
+    }
+
+    /// Adds the next whole rows of the image, one slice of them for each channel.
+    pub fn rows(&mut self, channels: &[Vec<f32>]) -> io::Result<()> {
+        let rows = channels.first().map_or(0, |channel| channel.len() / self.width.max(1));
+        let mut data = Vec::with_capacity(self.width * self.channels * 4);
+        for row in 0..rows {
+            data.clear();
+            for channel in channels {
+                data.extend(channel[row * self.width..(row + 1) * self.width].iter().flat_map(|value| value.to_le_bytes()));
+            }
+            self.out.write_all(&(self.y as i32).to_le_bytes())?;
+            self.out.write_all(&(data.len() as i32).to_le_bytes())?;
+            self.out.write_all(&data)?;
+            self.y += 1;
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+fn attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    for text in [name, kind] {
+        header.extend(text.as_bytes());
+        header.push(0);
+    }
+    header.extend((value.len() as i32).to_le_bytes());
+    header.extend(value);
+}
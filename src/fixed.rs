@@ -0,0 +1,257 @@
+use std::fmt;
+use std::ops::{Add, Mul, Sub};
+
+use num_bigint::{BigInt, BigUint, Sign};
+
+/// Fractional bits needed to place things a pixel apart at `scale`, and as many again as an
+/// f64 holds so rounding building up over many operations stays below that.
+pub fn bits_for(scale: f64) -> u64 {
+    scale.log2().max(0.) as u64 + 64
+}
+
+/// A signed fixed point number with `bits` fractional bits, for the few values needed to
+/// more precision than an f64 holds. Operations keep the larger precision of the two.
+#[derive(Clone, Debug)]
+pub struct Fixed {
+    value: BigInt,
+    bits: u64
+}
+
+impl Fixed {
+    /// Converts `x` exactly, as long as `bits` covers its lowest set bit.
+    pub fn from_f64(x: f64, bits: u64) -> Fixed {
+        // a finite f64 is its 53 bit mantissa times a power of two
+        let raw = x.to_bits();
+        let biased = ((raw >> 52) & 0x7ff) as i64;
+        let mut mantissa = (raw & ((1 << 52) - 1)) as i64;
+        if biased != 0 {
+            mantissa |= 1 << 52;
+        }
+        if x.is_sign_negative() {
+            mantissa = -mantissa;
+        }
+
+        let shift = biased.max(1) - 1075 + bits as i64;
+        let value = BigInt::from(mantissa);
+        let value = if shift >= 0 { value << shift as usize } else { value >> (-shift) as usize };
+        Fixed { value, bits }
+    }
+
+    /// Reads a decimal like `-0.7436438870371587047521915` or `1.5e-20`, with at least
+    /// `bits` fractional bits and more if that's what it takes to keep every digit.
+    pub fn parse(text: &str, bits: u64) -> Option<Fixed> {
+        let (mantissa, exponent) = match text.split_once(['e', 'E']) {
+            Some((mantissa, exponent)) => (mantissa, exponent.parse::<i64>().ok().filter(|e| e.abs() <= 10000)?),
+            None => (text, 0)
+        };
+        let (negative, mantissa) = match mantissa.strip_prefix('-') {
+            Some(mantissa) => (true, mantissa),
+            None => (false, mantissa.strip_prefix('+').unwrap_or(mantissa))
+        };
+        let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        if !whole.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let digits: BigUint = format!("{}{}", whole, fraction).parse().ok()?;
+
+        // the value is digits / 10^places
+        let places = fraction.len() as i64 - exponent;
+        let (magnitude, bits) = if places <= 0 {
+            ((digits * BigUint::from(10u32).pow((-places) as u32)) << bits as usize, bits)
+        } else {
+            let bits = bits.max(decimal_bits(places as u32));
+            (from_decimal(&digits, places as u32, bits), bits)
+        };
+        let sign = if negative { Sign::Minus } else { Sign::Plus };
+        Some(Fixed { value: BigInt::from_biguint(sign, magnitude), bits })
+    }
+
+    /// Rounds to the nearest f64 below, which is as close as f64 arithmetic needs.
+    pub fn to_f64(&self) -> f64 {
+        let magnitude = self.value.magnitude();
+        let shift = magnitude.bits().saturating_sub(64);
+        let top = (magnitude >> shift).iter_u64_digits().next().unwrap_or(0);
+        let value = top as f64 * 2f64.powi(shift as i32 - self.bits as i32);
+        if self.value.sign() == Sign::Minus { -value } else { value }
+    }
+
+    /// What's left over after rounding to `to_f64`, to carry more of the value into f64
+    /// based formats.
+    pub fn remainder(&self) -> f64 {
+        (self - &Fixed::from_f64(self.to_f64(), self.bits)).to_f64()
+    }
+
+    /// The value scaled to `bits` fractional bits, which must be at least as many as it has.
+    fn widened(&self, bits: u64) -> BigInt {
+        &self.value << (bits - self.bits) as usize
+    }
+}
+
+/// `value / 2^bits` in units of `10^-places`, rounded to nearest.
+fn to_decimal(value: &BigUint, bits: u64, places: u32) -> BigUint {
+    (((value * BigUint::from(10u32).pow(places)) >> (bits - 1) as usize) + 1u32) >> 1
+}
+
+/// Fractional bits that keep every one of `places` decimals.
+fn decimal_bits(places: u32) -> u64 {
+    (places as f64 * std::f64::consts::LOG2_10).ceil() as u64
+}
+
+/// `digits / 10^places` in units of `2^-bits`, rounded to nearest.
+fn from_decimal(digits: &BigUint, places: u32, bits: u64) -> BigUint {
+    (((digits << (bits + 1) as usize) / BigUint::from(10u32).pow(places)) + 1u32) >> 1
+}
+
+/// Writes the value to the given number of decimal places, or without one to the fewest
+/// that read back to the same value, so coordinates can be copied out of the viewer and
+/// into it again exactly.
+impl fmt::Display for Fixed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let magnitude = self.value.magnitude();
+        let places = match f.precision() {
+            Some(places) => places as u32,
+            // reading more decimals keeps more bits, so check at those, and the exact
+            // expansion at `bits` places always reads back
+            None => (0..)
+                .find(|&places| {
+                    let bits = self.bits.max(decimal_bits(places));
+                    let read = from_decimal(&to_decimal(magnitude, self.bits, places), places, bits);
+                    read == magnitude << (bits - self.bits) as usize
+                })
+                .unwrap_or(0)
+        };
+        let digits = to_decimal(magnitude, self.bits, places);
+        let sign = if self.value.sign() == Sign::Minus && digits.bits() > 0 {
+            "-"
+        } else if f.sign_plus() {
+            "+"
+        } else {
+            ""
+        };
+        let digits = format!("{:0>width$}", digits.to_string(), width = places as usize + 1);
+        let (whole, fraction) = digits.split_at(digits.len() - places as usize);
+        if places == 0 {
+            write!(f, "{}{}", sign, whole)
+        } else {
+            write!(f, "{}{}.{}", sign, whole, fraction)
+        }
+    }
+}
+
+impl Add for &Fixed {
+    type Output = Fixed;
+
+    fn add(self, other: &Fixed) -> Fixed {
+        let bits = self.bits.max(other.bits);
+        Fixed { value: self.widened(bits) + other.widened(bits), bits }
+    }
+}
+
+impl Sub for &Fixed {
+    type Output = Fixed;
+
+    fn sub(self, other: &Fixed) -> Fixed {
+        let bits = self.bits.max(other.bits);
+        Fixed { value: self.widened(bits) - other.widened(bits), bits }
+    }
+}
+
+impl Mul for &Fixed {
+    type Output = Fixed;
+
+    fn mul(self, other: &Fixed) -> Fixed {
+        let bits = self.bits.max(other.bits);
+        Fixed { value: (self.widened(bits) * other.widened(bits)) >> bits as usize, bits }
+    }
+}
+
+/// The view center, kept exactly so it can move by less than an f64 can resolve at deep
+/// zooms, along with the nearest f64s for the per-pixel math.
+#[derive(Clone, Debug)]
+pub struct Center {
+    re: Fixed,
+    im: Fixed,
+    approx: (f64, f64)
+}
+
+impl Center {
+    /// A center at `(re, im)`, exact for coordinates down to about 1e-22.
+    pub fn new((re, im): (f64, f64)) -> Center {
+        Center { re: Fixed::from_f64(re, 128), im: Fixed::from_f64(im, 128), approx: (re, im) }
+    }
+
+    /// Reads a center written as two decimals, keeping every digit of them.
+    pub fn parse(re: &str, im: &str) -> Option<Center> {
+        let (re, im) = (Fixed::parse(re, 128)?, Fixed::parse(im, 128)?);
+        Some(Center { approx: (re.to_f64(), im.to_f64()), re, im })
+    }
+
+    pub fn exact(&self) -> (&Fixed, &Fixed) {
+        (&self.re, &self.im)
+    }
+
+    pub fn approx(&self) -> (f64, f64) {
+        self.approx
+    }
+
+    /// Moves the center by `(dx, dy)`, keeping enough precision for `scale`.
+    pub fn shift(&mut self, (dx, dy): (f64, f64), scale: f64) {
+        let bits = bits_for(scale);
+        self.re = &self.re + &Fixed::from_f64(dx, bits);
+        self.im = &self.im + &Fixed::from_f64(dy, bits);
+        self.approx = (self.re.to_f64(), self.im.to_f64());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::BigInt;
+
+    use super::{Center, Fixed};
+
+    /// Whether `a` and `b` are the same number, whatever their precision.
+    fn same(a: &Fixed, b: &Fixed) -> bool {
+        (a - b).value == BigInt::from(0)
+    }
+
+    /// Writes `center` out and reads it back, checking it comes back as it was.
+    fn round_trip(center: &Center) {
+        let (re, im) = center.exact();
+        let (text_re, text_im) = (re.to_string(), im.to_string());
+        let read = Center::parse(&text_re, &text_im).unwrap();
+        assert!(same(read.exact().0, re) && same(read.exact().1, im), "{} {}", text_re, text_im);
+        assert_eq!((read.exact().0.to_string(), read.exact().1.to_string()), (text_re, text_im));
+        assert_eq!(read.approx(), center.approx());
+    }
+
+    #[test]
+    fn decimals_read_back_exactly() {
+        let cases = [
+            ("-0.7436438870371587047521915110493940171948", "0.1318259042053988596308"),
+            ("1.5e-20", "-2.5E+3"),
+            ("-1e-60", "123456789012345678901234567890.0625"),
+            ("0.1", "-0")
+        ];
+        for (re, im) in cases {
+            round_trip(&Center::parse(re, im).unwrap());
+        }
+
+        let written = |text: &str| Fixed::parse(text, 128).unwrap().to_string();
+        assert_eq!(written("0.1"), "0.1");
+        assert_eq!(written("-2.5E+3"), "-2500");
+        assert_eq!(written("1.5e-20"), "0.000000000000000000015");
+        assert_eq!(written("-0"), "0");
+        assert_eq!(written("-0.7436438870371587047521915110493940171948"), "-0.7436438870371587047521915110493940171948");
+        assert!(Fixed::parse("1.2.3", 128).is_none() && Fixed::parse("1e", 128).is_none() && Fixed::parse("0x1", 128).is_none());
+    }
+
+    #[test]
+    fn shifted_centers_read_back_exactly() {
+        let mut center = Center::parse("-1.7685046211376149999", "0.0009241604213175").unwrap();
+        for scale in [1e3, 1e15, 1e30, 1e45] {
+            center.shift((0.37 / scale, -1.3 / scale), scale);
+            round_trip(&center);
+        }
+        round_trip(&Center::new((-0.75, 0.1)));
+    }
+}
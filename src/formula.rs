@@ -0,0 +1,316 @@
+use num_complex::Complex;
+
+type Compiled = Box<dyn Fn(Complex<f64>, Complex<f64>) -> Complex<f64> + Send + Sync>;
+
+enum Expr {
+    Z,
+    C,
+    Const(Complex<f64>),
+    Neg(Box<Expr>),
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    Div(Box<Expr>, Box<Expr>),
+    Pow(Box<Expr>, Box<Expr>),
+    Call(fn(Complex<f64>) -> Complex<f64>, Box<Expr>)
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Token {
+    Num(f64),
+    Ident(usize, usize),
+    Op(char)
+}
+
+/// A user-supplied iteration formula `z -> f(z, c)`, compiled to a closure tree.
+pub struct Formula {
+    compiled: Compiled
+}
+
+impl Formula {
+    pub fn parse(source: &str) -> Result<Formula, String> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser { source, tokens: &tokens, pos: 0 };
+
+        let expr = parser.expr()?;
+        if let Some(token) = parser.peek() {
+            return Err(format!("unexpected {} in formula", parser.describe(token)));
+        }
+
+        Ok(Formula {
+            compiled: compile(expr)
+        })
+    }
+
+    pub fn eval(&self, z: Complex<f64>, c: Complex<f64>) -> Complex<f64> {
+        (self.compiled)(z, c)
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let ch = bytes[i] as char;
+        if ch.is_ascii_whitespace() {
+            i += 1;
+        } else if ch.is_ascii_digit() || ch == '.' {
+            let start = i;
+            while i < bytes.len() && (bytes[i].is_ascii_digit() || bytes[i] == b'.') {
+                i += 1;
+            }
+            let num = source[start..i].parse().map_err(|_| format!("invalid number '{}'", &source[start..i]))?;
+            tokens.push(Token::Num(num));
+        } else if ch.is_ascii_alphabetic() {
+            let start = i;
+            while i < bytes.len() && bytes[i].is_ascii_alphanumeric() {
+                i += 1;
+            }
+            tokens.push(Token::Ident(start, i));
+        } else if "+-*/^()".contains(ch) {
+            tokens.push(Token::Op(ch));
+            i += 1;
+        } else {
+            return Err(format!("unexpected character '{}' in formula", ch));
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    tokens: &'a [Token],
+    pos: usize
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<Token> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.peek();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, op: char) -> bool {
+        if self.peek() == Some(Token::Op(op)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn describe(&self, token: Token) -> String {
+        match token {
+            Token::Num(n) => format!("'{}'", n),
+            Token::Ident(start, end) => format!("'{}'", &self.source[start..end]),
+            Token::Op(op) => format!("'{}'", op)
+        }
+    }
+
+    // expr := term (('+' | '-') term)*
+    fn expr(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.term()?;
+        loop {
+            if self.eat('+') {
+                lhs = Expr::Add(Box::new(lhs), Box::new(self.term()?));
+            } else if self.eat('-') {
+                lhs = Expr::Sub(Box::new(lhs), Box::new(self.term()?));
+            } else {
+                return Ok(lhs);
+            }
+        }
+    }
+
+    // term := unary (('*' | '/') unary)*
+    fn term(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.unary()?;
+        loop {
+            if self.eat('*') {
+                lhs = Expr::Mul(Box::new(lhs), Box::new(self.unary()?));
+            } else if self.eat('/') {
+                lhs = Expr::Div(Box::new(lhs), Box::new(self.unary()?));
+            } else {
+                return Ok(lhs);
+            }
+        }
+    }
+
+    // unary := '-' unary | power
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat('-') {
+            Ok(Expr::Neg(Box::new(self.unary()?)))
+        } else {
+            self.power()
+        }
+    }
+
+    // power := atom ('^' unary)?
+    fn power(&mut self) -> Result<Expr, String> {
+        let base = self.atom()?;
+        if self.eat('^') {
+            Ok(Expr::Pow(Box::new(base), Box::new(self.unary()?)))
+        } else {
+            Ok(base)
+        }
+    }
+
+    fn atom(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Num(n)) => Ok(Expr::Const(Complex::new(n, 0.))),
+            Some(Token::Op('(')) => {
+                let inner = self.expr()?;
+                if !self.eat(')') {
+                    return Err("missing ')' in formula".to_string());
+                }
+                Ok(inner)
+            }
+            Some(Token::Ident(start, end)) => {
+                let name = &self.source[start..end];
+                match name {
+                    "z" => return Ok(Expr::Z),
+                    "c" => return Ok(Expr::C),
+                    "i" => return Ok(Expr::Const(Complex::new(0., 1.))),
+                    _ => {}
+                }
+
+                let func = function(name).ok_or_else(|| format!("unknown name '{}' in formula", name))?;
+                if !self.eat('(') {
+                    return Err(format!("expected '(' after '{}'", name));
+                }
+                let arg = self.expr()?;
+                if !self.eat(')') {
+                    return Err(format!("missing ')' after arguments to '{}'", name));
+                }
+                Ok(Expr::Call(func, Box::new(arg)))
+            }
+            Some(token) => Err(format!("unexpected {} in formula", self.describe(token))),
+            None => Err("unexpected end of formula".to_string())
+        }
+    }
+}
+
+fn function(name: &str) -> Option<fn(Complex<f64>) -> Complex<f64>> {
+    Some(match name {
+        "sin" => |z: Complex<f64>| z.sin(),
+        "cos" => |z: Complex<f64>| z.cos(),
+        "tan" => |z: Complex<f64>| z.tan(),
+        "sinh" => |z: Complex<f64>| z.sinh(),
+        "cosh" => |z: Complex<f64>| z.cosh(),
+        "tanh" => |z: Complex<f64>| z.tanh(),
+        "exp" => |z: Complex<f64>| z.exp(),
+        "ln" | "log" => |z: Complex<f64>| z.ln(),
+        "sqrt" => |z: Complex<f64>| z.sqrt(),
+        "conj" => |z: Complex<f64>| z.conj(),
+        "abs" => |z: Complex<f64>| Complex::new(z.re.abs(), z.im.abs()),
+        "re" => |z: Complex<f64>| Complex::new(z.re, 0.),
+        "im" => |z: Complex<f64>| Complex::new(z.im, 0.),
+        _ => return None
+    })
+}
+
+fn compile(expr: Expr) -> Compiled {
+    match expr {
+        Expr::Z => Box::new(|z, _| z),
+        Expr::C => Box::new(|_, c| c),
+        Expr::Const(k) => Box::new(move |_, _| k),
+        Expr::Neg(a) => {
+            let a = compile(*a);
+            Box::new(move |z, c| -a(z, c))
+        }
+        Expr::Add(a, b) => {
+            let (a, b) = (compile(*a), compile(*b));
+            Box::new(move |z, c| a(z, c) + b(z, c))
+        }
+        Expr::Sub(a, b) => {
+            let (a, b) = (compile(*a), compile(*b));
+            Box::new(move |z, c| a(z, c) - b(z, c))
+        }
+        Expr::Mul(a, b) => {
+            let (a, b) = (compile(*a), compile(*b));
+            Box::new(move |z, c| a(z, c) * b(z, c))
+        }
+        Expr::Div(a, b) => {
+            let (a, b) = (compile(*a), compile(*b));
+            Box::new(move |z, c| a(z, c) / b(z, c))
+        }
+        Expr::Pow(a, b) => {
+            let a = compile(*a);
+            match *b {
+                // integer powers are far cheaper and exact via repeated multiplication
+                Expr::Const(k) if k.im == 0. && k.re.fract() == 0. && k.re.abs() <= i32::MAX as f64 => {
+                    let n = k.re as i32;
+                    Box::new(move |z, c| a(z, c).powi(n))
+                }
+                b => {
+                    let b = compile(b);
+                    Box::new(move |z, c| a(z, c).powc(b(z, c)))
+                }
+            }
+        }
+        Expr::Call(func, a) => {
+            let a = compile(*a);
+            Box::new(move |z, c| func(a(z, c)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num_complex::Complex;
+
+    use super::Formula;
+
+    #[test]
+    fn evaluates_with_precedence_and_associativity() {
+        let (z, c) = (Complex::new(2., 0.), Complex::new(1., 0.));
+        let cases = [
+            ("1 + 2 * 3", 7.),
+            ("(1 + 2) * 3", 9.),
+            ("10 - 2 - 3", 5.),
+            ("8 / 4 / 2", 1.),
+            ("2 * 3 ^ 2", 18.),
+            ("2 ^ 3 ^ 2", 512.),
+            ("-2 ^ 2", -4.),
+            ("--z", 2.),
+            ("2 ^ -1", 0.5),
+            ("-z * 3 + c", -5.),
+            ("z * z + c", 5.),
+            ("i * i", -1.),
+            ("re(z) + im(c)", 2.)
+        ];
+        for (source, expected) in cases {
+            let formula = Formula::parse(source).unwrap_or_else(|err| panic!("{}: {}", source, err));
+            let value = formula.eval(z, c);
+            assert!((value - Complex::new(expected, 0.)).norm() < 1e-12, "{} gave {}, not {}", source, value, expected);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_formulas() {
+        let cases = [
+            ("", "unexpected end of formula"),
+            ("z +", "unexpected end of formula"),
+            ("(z + c", "missing ')' in formula"),
+            ("z c", "unexpected 'c' in formula"),
+            ("z ) + c", "unexpected ')' in formula"),
+            ("foo(z)", "unknown name 'foo' in formula"),
+            ("sin z", "expected '(' after 'sin'"),
+            ("sin(z", "missing ')' after arguments to 'sin'"),
+            ("z % c", "unexpected character '%' in formula"),
+            ("1.2.3", "invalid number '1.2.3'")
+        ];
+        for (source, expected) in cases {
+            match Formula::parse(source) {
+                Ok(_) => panic!("{} parsed", source),
+                Err(err) => assert_eq!(err, expected, "for {}", source)
+            }
+        }
+    }
+}
@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::png::Bits;
+
+/// Colors a GIF frame can have.
+const COLORS: usize = 256;
+/// Bits of each channel colors are told apart by when there are too many to keep them all.
+const CHANNEL_BITS: u32 = 5;
+/// Widest LZW code, after which the table starts over.
+const MAX_CODE: u32 = 4096;
+
+/// An animated GIF that loops forever, written a frame at a time with each frame carrying its
+/// own 256 colors, so palettes can change along the way.
+pub struct Writer<W: Write> {
+    out: W,
+    width: usize
+}
+
+impl<W: Write> Writer<W> {
+    pub fn new(mut out: W, (width, height): (usize, usize)) -> io::Result<Writer<W>> {
+        out.write_all(b"GIF89a")?;
+        out.write_all(&(width as u16).to_le_bytes())?;
+        out.write_all(&(height as u16).to_le_bytes())?;
+        // no global color table, background color 0, square pixels
+        out.write_all(&[0, 0, 0])?;
+        // the application extension that has the frames loop, 0 for forever
+        out.write_all(b"\x21\xFF\x0BNETSCAPE2.0\x03\x01\x00\x00\x00")?;
+        Ok(Writer { out, width })
+    }
+
+    /// Adds a frame of `0xRRGGBB` pixels covering the whole image, shown for `delay`
+    /// hundredths of a second.
+    pub fn frame(&mut self, pixels: &[u32], delay: u16) -> io::Result<()> {
+        let height = pixels.len() / self.width.max(1);
+        let (colors, indices) = quantize(pixels);
+
+        // graphic control, leaving the frame in place for the next to cover
+        self.out.write_all(&[0x21, 0xF9, 4, 1 << 2])?;
+        self.out.write_all(&delay.to_le_bytes())?;
+        self.out.write_all(&[0, 0])?;
+
+        self.out.write_all(&[0x2C, 0, 0, 0, 0])?;
+        self.out.write_all(&(self.width as u16).to_le_bytes())?;
+        self.out.write_all(&(height as u16).to_le_bytes())?;
+        // a local color table of 2^8 entries
+        self.out.write_all(&[0x80 | 7])?;
+        let mut table = vec![0; COLORS * 3];
+        for (entry, color) in table.chunks_mut(3).zip(&colors) {
+            entry.copy_from_slice(&[(color >> 16) as u8, (color >> 8) as u8, *color as u8]);
+        }
+        self.out.write_all(&table)?;
+
+        self.out.write_all(&[8])?;
+        for block in lzw(&indices).chunks(255) {
+            self.out.write_all(&[block.len() as u8])?;
+            self.out.write_all(block)?;
+        }
+        self.out.write_all(&[0])
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.out.write_all(&[0x3B])?;
+        self.out.flush()
+    }
+}
+
+/// At most 256 colors for `pixels`, and each pixel's index among them. Frames with few enough
+/// colors keep them exactly, the rest are cut down by median cut.
+fn quantize(pixels: &[u32]) -> (Vec<u32>, Vec<u8>) {
+    let mut exact: HashMap<u32, u8> = HashMap::new();
+    for &pixel in pixels {
+        let next = exact.len();
+        if next > COLORS {
+            break;
+        }
+        exact.entry(pixel & 0xFFFFFF).or_insert(next as u8);
+    }
+    if exact.len() <= COLORS {
+        let mut colors = vec![0; exact.len()];
+        for (&color, &index) in &exact {
+            colors[index as usize] = color;
+        }
+        return (colors, pixels.iter().map(|pixel| exact[&(pixel & 0xFFFFFF)]).collect());
+    }
+
+    // how many pixels fall in each bin of the coarser colors, and what they add up to
+    let key = |pixel: u32| {
+        let channel = |shift: u32| (pixel >> (shift + 8 - CHANNEL_BITS)) as usize & ((1 << CHANNEL_BITS) - 1);
+        channel(16) << (2 * CHANNEL_BITS) | channel(8) << CHANNEL_BITS | channel(0)
+    };
+    let mut counts = vec![0u64; 1 << (3 * CHANNEL_BITS)];
+    let mut sums = vec![[0u64; 3]; 1 << (3 * CHANNEL_BITS)];
+    for &pixel in pixels {
+        let bin = key(pixel);
+        counts[bin] += 1;
+        for (n, sum) in sums[bin].iter_mut().enumerate() {
+            *sum += (pixel >> (16 - 8 * n)) as u64 & 0xFF;
+        }
+    }
+
+    let mut bins: Vec<usize> = (0..counts.len()).filter(|&bin| counts[bin] > 0).collect();
+    let channel = |bin: usize, n: usize| (bin >> ((2 - n) as u32 * CHANNEL_BITS)) & ((1 << CHANNEL_BITS) - 1);
+    // ranges of `bins`, each becoming a color
+    let mut boxes = vec![(0, bins.len())];
+    while boxes.len() < COLORS {
+        // the box with the most pixels spread over the widest range, along that range
+        let widest = |&(start, end): &(usize, usize)| {
+            let slice = &bins[start..end];
+            (0..3)
+                .map(|n| {
+                    let values = slice.iter().map(|&bin| channel(bin, n));
+                    (values.clone().max().unwrap_or(0) - values.min().unwrap_or(0), n)
+                })
+                .max()
+                .unwrap_or((0, 0))
+        };
+        let weight = |range: &(usize, usize)| bins[range.0..range.1].iter().map(|&bin| counts[bin]).sum::<u64>() * widest(range).0 as u64;
+        let index = match (0..boxes.len()).filter(|&i| boxes[i].1 - boxes[i].0 > 1).max_by_key(|&i| weight(&boxes[i])) {
+            Some(index) => index,
+            None => break
+        };
+        let (start, end) = boxes[index];
+        let (_, n) = widest(&boxes[index]);
+        bins[start..end].sort_unstable_by_key(|&bin| channel(bin, n));
+        // split where half the box's pixels are on either side
+        let total: u64 = bins[start..end].iter().map(|&bin| counts[bin]).sum();
+        let mut seen = 0;
+        let mut middle = start + 1;
+        for (i, &bin) in bins[start..end - 1].iter().enumerate() {
+            seen += counts[bin];
+            middle = start + i + 1;
+            if 2 * seen >= total {
+                break;
+            }
+        }
+        boxes[index] = (start, middle);
+        boxes.push((middle, end));
+    }
+
+    let mut lookup = vec![0u8; counts.len()];
+    let colors = boxes
+        .iter()
+        .enumerate()
+        .map(|(index, &(start, end))| {
+            let (mut count, mut sum) = (0, [0u64; 3]);
+            for &bin in &bins[start..end] {
+                lookup[bin] = index as u8;
+                count += counts[bin];
+                for n in 0..3 {
+                    sum[n] += sums[bin][n];
+                }
+            }
+            let mean = |n: usize| (sum[n] + count / 2) / count.max(1);
+            (mean(0) << 16 | mean(1) << 8 | mean(2)) as u32
+        })
+        .collect();
+    (colors, pixels.iter().map(|&pixel| lookup[key(pixel)]).collect())
+}
+
+/// GIF's LZW compression of 8-bit `indices`, starting the table over whenever it fills.
+fn lzw(indices: &[u8]) -> Vec<u8> {
+    let (clear, end) = (1u32 << 8, (1u32 << 8) + 1);
+    let mut bits = Bits::default();
+    let mut table: HashMap<(u32, u8), u32> = HashMap::new();
+    let (mut next, mut width) = (end + 1, 9);
+    bits.write(clear, width);
+
+    let (mut prefix, rest) = match indices.split_first() {
+        Some((&first, rest)) => (first as u32, rest),
+        None => {
+            bits.write(end, width);
+            return bits.finish();
+        }
+    };
+    for &index in rest {
+        if let Some(&code) = table.get(&(prefix, index)) {
+            prefix = code;
+            continue;
+        }
+        bits.write(prefix, width);
+        if next < MAX_CODE {
+            table.insert((prefix, index), next);
+            next += 1;
+            // the decoder is a code behind, so it widens only once the next is past the width
+            if next > 1 << width && width < 12 {
+                width += 1;
+            }
+        } else {
+            bits.write(clear, width);
+            table.clear();
+            (next, width) = (end + 1, 9);
+        }
+        prefix = index as u32;
+    }
+    bits.write(prefix, width);
+    bits.write(end, width);
+    bits.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{lzw, quantize, COLORS, MAX_CODE};
+
+    /// Decodes 8-bit LZW codes back into indices, along with how many times the table was
+    /// cleared and the widest code read.
+    fn unlzw(bytes: &[u8]) -> (Vec<u8>, usize, u8) {
+        let (clear, end) = (256, 257);
+        let mut at = 0;
+        let mut read = |width: u8| {
+            let mut code = 0;
+            for n in 0..width {
+                code |= ((bytes[at / 8] >> (at % 8)) as usize & 1) << n;
+                at += 1;
+            }
+            code
+        };
+        let fresh = || (0..=end).map(|n| vec![n as u8]).collect::<Vec<_>>();
+        let (mut table, mut width, mut widest, mut clears) = (fresh(), 9, 9, 0);
+        let mut previous: Option<usize> = None;
+        let mut out = Vec::new();
+        loop {
+            let code = read(width);
+            if code == clear {
+                (table, width, previous) = (fresh(), 9, None);
+                clears += 1;
+                continue;
+            }
+            if code == end {
+                break;
+            }
+            let entry = match (table.get(code), previous) {
+                (Some(entry), _) => entry.clone(),
+                (None, Some(previous)) if code == table.len() => {
+                    let entry = &table[previous];
+                    [&entry[..], &entry[..1]].concat()
+                }
+                _ => panic!("code {} before it's in the table", code)
+            };
+            out.extend_from_slice(&entry);
+            if let Some(previous) = previous {
+                if table.len() < MAX_CODE as usize {
+                    table.push([&table[previous][..], &entry[..1]].concat());
+                }
+            }
+            if table.len() == 1 << width && width < 12 {
+                width += 1;
+                widest = widest.max(width);
+            }
+            previous = Some(code);
+        }
+        (out, clears, widest)
+    }
+
+    #[test]
+    fn lzw_decodes_back() {
+        for indices in [&[][..], &[7], &[1, 1, 1, 1, 1, 1, 1], &[0, 255, 0, 255, 0, 255, 0]] {
+            assert_eq!(unlzw(&lzw(indices)).0, indices);
+        }
+
+        // noise with runs, enough to widen the codes to 12 bits and fill the table a few times
+        let mut seed = 7u32;
+        let indices: Vec<u8> = (0..100_000)
+            .map(|n| {
+                seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+                if n % 64 < 24 { (n / 64) as u8 } else { (seed >> 24) as u8 }
+            })
+            .collect();
+        let (decoded, clears, widest) = unlzw(&lzw(&indices));
+        assert_eq!(decoded, indices);
+        assert!(clears > 2, "{} clears", clears);
+        assert_eq!(widest, 12);
+    }
+
+    #[test]
+    fn few_colors_are_kept_exactly() {
+        let pixels: Vec<u32> = (0..1000).map(|n| 0xFF000000 | ((n % 200) * 0x010203)).collect();
+        let (colors, indices) = quantize(&pixels);
+        assert_eq!(colors.len(), 200);
+        for (pixel, index) in pixels.iter().zip(indices) {
+            assert_eq!(colors[index as usize], pixel & 0xFFFFFF);
+        }
+    }
+
+    #[test]
+    fn many_colors_come_out_close() {
+        let pixels: Vec<u32> = (0..1 << 14).map(|n| (n & 0x7F) << 17 | (n >> 7 & 0x7F) << 9 | ((n * 37) & 0xFF)).collect();
+        let (colors, indices) = quantize(&pixels);
+        assert!(colors.len() <= COLORS);
+        let channel = |color: u32, shift: u32| (color >> shift & 0xFF) as i32;
+        let worst = pixels
+            .iter()
+            .zip(indices)
+            .map(|(&pixel, index)| [16, 8, 0].map(|shift| (channel(pixel, shift) - channel(colors[index as usize], shift)).abs()).into_iter().max().unwrap_or(0))
+            .max()
+            .unwrap_or(0);
+        assert!(worst <= 48, "off by {}", worst);
+    }
+}
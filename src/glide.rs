@@ -0,0 +1,71 @@
+use std::time::Instant;
+
+use fractv::worker::Request;
+
+/// How many times over the zoom keys zoom in or out a second.
+const ZOOM_SPEED: f64 = 8.;
+/// Pixels a second the pan keys move the view by.
+const PAN_SPEED: f64 = 240.;
+/// Time constant in seconds of the easing, in which the view covers all but 1/e of the way
+/// still to go.
+const EASE: f64 = 0.08;
+/// Zoom, as a log factor, and pan in pixels left to go below which the glide is over.
+const DONE: (f64, f64) = (1e-4, 0.5);
+
+/// Zooming and panning from the keyboard, which move the view toward a target the held keys
+/// push along and ease it in, so the motion is smooth whatever the frame rate.
+pub struct Glide {
+    /// Log of the zoom factor still to apply.
+    zoom: f64,
+    /// Pixels still to pan by.
+    pan: (f64, f64),
+    /// What's been eased in of the pan short of a whole pixel.
+    carry: (f64, f64),
+    /// Where the glide left the view, to drop the rest when something else moves it.
+    left: Option<((f64, f64), f64)>,
+    last: Instant
+}
+
+impl Glide {
+    pub fn new() -> Glide {
+        Glide { zoom: 0., pan: (0., 0.), carry: (0., 0.), left: None, last: Instant::now() }
+    }
+
+    /// Moves `request` on for the time since the last frame, with `zoom` -1, 0 or 1 for the
+    /// zoom keys held and `pan` the same for the pan keys in each direction. Returns whether
+    /// the view is still gliding.
+    pub fn update(&mut self, request: &mut Request, zoom: f64, pan: (f64, f64)) -> bool {
+        // a stall, like the window being dragged, shouldn't send the view flying
+        let dt = self.last.elapsed().as_secs_f64().min(0.1);
+        self.last = Instant::now();
+        if self.left != Some((request.pan.approx(), request.scale)) {
+            (self.zoom, self.pan, self.carry) = (0., (0., 0.), (0., 0.));
+        }
+
+        self.zoom += zoom * ZOOM_SPEED.ln() * dt;
+        self.pan = (self.pan.0 + pan.0 * PAN_SPEED * dt, self.pan.1 + pan.1 * PAN_SPEED * dt);
+        let gliding = zoom != 0. || pan != (0., 0.) || self.zoom.abs() > DONE.0 || self.pan.0.abs().max(self.pan.1.abs()) > DONE.1;
+        if gliding {
+            let share = 1. - (-dt / EASE).exp();
+            let step = self.zoom * share;
+            if step != 0. {
+                request.scale *= step.exp();
+                request.updated = true;
+                self.zoom -= step;
+            }
+            let step = (self.pan.0 * share, self.pan.1 * share);
+            self.pan = (self.pan.0 - step.0, self.pan.1 - step.1);
+            self.carry = (self.carry.0 + step.0, self.carry.1 + step.1);
+            // only whole pixels, so a finished render can be shifted along
+            let (dx, dy) = (self.carry.0.round(), self.carry.1.round());
+            if (dx, dy) != (0., 0.) {
+                request.pan_pixels(dx as isize, dy as isize);
+                self.carry = (self.carry.0 - dx, self.carry.1 - dy);
+            }
+        } else {
+            (self.zoom, self.pan, self.carry) = (0., (0., 0.), (0., 0.));
+        }
+        self.left = Some((request.pan.approx(), request.scale));
+        gliding
+    }
+}
@@ -0,0 +1,104 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use minifb::{InputCallback, Key, KeyRepeat, Window};
+
+use crate::fixed::Center;
+use crate::worker::Request;
+use crate::{hud, HOME_SCALE};
+
+/// Collects the characters typed into the window, for the prompt to take.
+struct Typed(Rc<RefCell<String>>);
+
+impl InputCallback for Typed {
+    fn add_char(&mut self, c: u32) {
+        if let Some(c) = char::from_u32(c) {
+            self.0.borrow_mut().push(c);
+        }
+    }
+}
+
+/// A prompt for coordinates to jump to, typed as `re im` with an optional zoom after them,
+/// the same way `--center` and `--zoom` take them.
+pub struct Goto {
+    pub open: bool,
+    text: String,
+    typed: Rc<RefCell<String>>
+}
+
+impl Goto {
+    pub fn new(window: &mut Window) -> Goto {
+        let goto = Goto { open: false, text: String::new(), typed: Rc::new(RefCell::new(String::new())) };
+        goto.attach(window);
+        goto
+    }
+
+    /// Takes typing from `window`, for when the one it was given is replaced.
+    pub fn attach(&self, window: &mut Window) {
+        window.set_input_callback(Box::new(Typed(self.typed.clone())));
+    }
+
+    pub fn open(&mut self) {
+        self.open = true;
+        self.text.clear();
+        self.typed.borrow_mut().clear();
+    }
+
+    /// Takes this frame's typing, jumping `request` to the coordinates on Enter.
+    pub fn handle(&mut self, window: &Window, request: &mut Request) {
+        let typed = std::mem::take(&mut *self.typed.borrow_mut());
+        if !self.open {
+            return;
+        }
+        self.text.extend(typed.chars().filter(|&c| c.is_ascii_digit() || ".-+eE ".contains(c)));
+        if window.is_key_pressed(Key::Backspace, KeyRepeat::Yes) {
+            self.text.pop();
+        }
+        if window.is_key_pressed(Key::Escape, KeyRepeat::No) {
+            self.open = false;
+        }
+        if window.is_key_pressed(Key::Enter, KeyRepeat::No) {
+            match parse(&self.text) {
+                Some((pan, zoom)) => {
+                    request.pan = pan;
+                    if let Some(zoom) = zoom {
+                        request.scale = HOME_SCALE * zoom;
+                    }
+                    request.updated = true;
+                    self.open = false;
+                }
+                None => println!("can't go to {}, expected <re> <im> [zoom]", self.text.trim())
+            }
+        }
+    }
+
+    pub fn draw(&self, frame: &mut [u32], dimensions: (usize, usize)) {
+        if self.open {
+            hud::draw_bottom(frame, dimensions, &[format!("goto {}_", self.text)]);
+        }
+    }
+}
+
+/// Writes out `pan` and the zoom of `scale` the way the prompt takes them, the coordinates
+/// to as many digits as they have.
+pub fn format(pan: &Center, scale: f64) -> String {
+    let (re, im) = pan.exact();
+    format!("{} {} {:e}", re, im, scale / HOME_SCALE)
+}
+
+fn parse(text: &str) -> Option<(Center, Option<f64>)> {
+    let parts: Vec<&str> = text.split_whitespace().collect();
+    let zoom = match parts.get(2) {
+        Some(zoom) => Some(parse_zoom(zoom)?),
+        None => None
+    };
+    match parts[..] {
+        [re, im] | [re, im, _] => Some((Center::parse(re, im)?, zoom)),
+        _ => None
+    }
+}
+
+/// Reads a zoom factor relative to the initial view, which has to be positive.
+pub fn parse_zoom(text: &str) -> Option<f64> {
+    text.parse::<f64>().ok().filter(|zoom| zoom.is_finite() && *zoom > 0.)
+}
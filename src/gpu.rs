@@ -0,0 +1,180 @@
+use num_complex::Complex;
+use wgpu::util::DeviceExt;
+
+use crate::{simd, FractalContext, Seed};
+use crate::orbit::Sample;
+use crate::renderer::{self, Renderer};
+
+const WORKGROUP: u32 = 8;
+
+/// `z^2 + c` with derivative tracking in single precision; each pixel writes its escape
+/// iteration (or -1) with the final z, then the final derivative.
+const SHADER: &str = r#"
+struct Params {
+    center: vec2<f32>,
+    c: vec2<f32>,
+    scale: f32,
+    maxiter: u32,
+    width: u32,
+    height: u32,
+    julia: u32,
+    rotation: vec2<f32>,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read_write> results: array<vec4<f32>>;
+
+@compute @workgroup_size(8, 8)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= params.width || id.y >= params.height {
+        return;
+    }
+    let pixel = vec2<f32>(f32(id.x) - f32(params.width) / 2.0, f32(id.y) - f32(params.height) / 2.0);
+    let offset = vec2<f32>(
+        pixel.x * params.rotation.x - pixel.y * params.rotation.y,
+        pixel.x * params.rotation.y + pixel.y * params.rotation.x
+    );
+    let point = offset / params.scale + params.center;
+
+    var z = vec2<f32>(0.0, 0.0);
+    var c = point;
+    var dz = vec2<f32>(0.0, 0.0);
+    var dc = vec2<f32>(1.0, 0.0);
+    if params.julia != 0u {
+        z = point;
+        c = params.c;
+        dz = vec2<f32>(1.0, 0.0);
+        dc = vec2<f32>(0.0, 0.0);
+    }
+
+    var escape = -1.0;
+    for (var i = 0u; i < params.maxiter; i++) {
+        dz = 2.0 * vec2<f32>(z.x * dz.x - z.y * dz.y, z.x * dz.y + z.y * dz.x) + dc;
+        z = vec2<f32>(z.x * z.x - z.y * z.y, 2.0 * z.x * z.y) + c;
+        if dot(z, z) > 4.0 {
+            escape = f32(i);
+            break;
+        }
+    }
+
+    let i = 2u * (id.x + id.y * params.width);
+    results[i] = vec4<f32>(escape, z, 0.0);
+    results[i + 1u] = vec4<f32>(dz, 0.0, 0.0);
+}
+"#;
+
+/// Compute-shader backend for the quadratic kernel.
+pub struct Gpu {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::ComputePipeline
+}
+
+impl Gpu {
+    /// Connects to the default adapter, or `None` when there is no usable GPU.
+    pub fn new() -> Option<Gpu> {
+        let instance = wgpu::Instance::default();
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))?;
+        let (device, queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default(), None)).ok()?;
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("escape time"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into())
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("escape time"),
+            layout: None,
+            module: &module,
+            entry_point: "main"
+        });
+
+        Some(Gpu { device, queue, pipeline })
+    }
+
+    fn run(&self, ctx: &FractalContext, seed: Seed, maxiter: usize) -> Vec<f32> {
+        let (width, height) = ctx.dimensions;
+        let (julia, c) = match seed {
+            Seed::Parameter => (0, Complex::new(0., 0.)),
+            Seed::Julia(c) => (1, c)
+        };
+
+        let mut params = Vec::new();
+        let (re, im) = ctx.pan.approx();
+        for value in [re, im, c.re, c.im, ctx.scale] {
+            params.extend((value as f32).to_le_bytes());
+        }
+        for value in [maxiter as u32, width as u32, height as u32, julia, 0] {
+            params.extend(value.to_le_bytes());
+        }
+        // the rotation as its cosine and sine, 8-byte aligned after the padding
+        let (sin, cos) = ctx.rotation.sin_cos();
+        for value in [cos, sin] {
+            params.extend((value as f32).to_le_bytes());
+        }
+
+        let uniform = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: &params,
+            usage: wgpu::BufferUsages::UNIFORM
+        });
+        let size = (width * height * 2 * 16) as u64;
+        let results = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false
+        });
+        let staging = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false
+        });
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: uniform.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: results.as_entire_binding() }
+            ]
+        });
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None, timestamp_writes: None });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups((width as u32).div_ceil(WORKGROUP), (height as u32).div_ceil(WORKGROUP), 1);
+        }
+        encoder.copy_buffer_to_buffer(&results, 0, &staging, 0, size);
+        self.queue.submit(Some(encoder.finish()));
+
+        let slice = staging.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |_| {});
+        self.device.poll(wgpu::Maintain::Wait);
+
+        let data = slice.get_mapped_range();
+        let values = data.chunks_exact(4).map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap())).collect();
+        drop(data);
+        staging.unmap();
+        values
+    }
+}
+
+impl Renderer for Gpu {
+    fn name(&self) -> &'static str {
+        "wgpu"
+    }
+
+    fn progressive(&self) -> bool {
+        false
+    }
+
+    fn quadratic(&self, ctx: &FractalContext, seed: Seed, maxiter: usize, pixels: &[usize]) -> Option<Vec<Sample>> {
+        // the center goes up as f32 too, so deeper pixels would land on top of each other
+        if ctx.scale >= simd::SINGLE_SCALE {
+            return None;
+        }
+        Some(renderer::unpack(ctx, seed, &self.run(ctx, seed, maxiter), pixels))
+    }
+}
@@ -0,0 +1,43 @@
+use fractv::font;
+
+const MARGIN: usize = 12;
+const LINE_HEIGHT: usize = font::GLYPH.1 + 3;
+/// Space between a binding's action and its keys, and between columns.
+const GAP: usize = 16;
+const TITLE: u32 = 0xFFFF80;
+const TEXT: u32 = 0xFFFFFF;
+const KEYS: u32 = 0x80C0FF;
+
+/// Darkens the whole frame and lists `settings` with the `bindings` below them, in as many
+/// columns as it takes to fit.
+pub fn draw(frame: &mut [u32], dimensions: (usize, usize), settings: &[String], bindings: &[(&str, String)]) {
+    let (width, height) = dimensions;
+    for pixel in frame.iter_mut() {
+        *pixel = (*pixel >> 2) & 0x3F3F3F;
+    }
+
+    let mut y = MARGIN;
+    font::draw(frame, dimensions, (MARGIN, y), "help, F1 or ? again to close", 1, TITLE);
+    y += 2 * LINE_HEIGHT;
+    for line in settings {
+        font::draw(frame, dimensions, (MARGIN, y), line, 1, TEXT);
+        y += LINE_HEIGHT;
+    }
+    y += LINE_HEIGHT;
+
+    let rows = (height.saturating_sub(y + MARGIN) / LINE_HEIGHT).max(1);
+    let name_width = bindings.iter().map(|(name, _)| font::width(name, 1)).max().unwrap_or(0) + GAP;
+    let mut x = MARGIN;
+    for column in bindings.chunks(rows) {
+        let keys_width = column.iter().map(|(_, keys)| font::width(keys, 1)).max().unwrap_or(0);
+        if x >= width {
+            break;
+        }
+        for (n, (name, keys)) in column.iter().enumerate() {
+            let top = y + n * LINE_HEIGHT;
+            font::draw(frame, dimensions, (x, top), name, 1, TEXT);
+            font::draw(frame, dimensions, (x + name_width, top), keys, 1, KEYS);
+        }
+        x += name_width + keys_width + GAP;
+    }
+}
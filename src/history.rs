@@ -0,0 +1,79 @@
+use std::time::Instant;
+
+use crate::fixed::Center;
+use crate::SETTLE;
+
+/// Views older than this many back are forgotten.
+const LIMIT: usize = 256;
+
+/// Somewhere navigation went: the fractal, as the command line arguments that give it, and
+/// the view of it.
+#[derive(Clone)]
+pub struct State {
+    pub fractal: Vec<String>,
+    pub pan: Center,
+    pub scale: f64,
+    pub rotation: f64,
+    pub iteration_factor: f64
+}
+
+impl PartialEq for State {
+    fn eq(&self, other: &State) -> bool {
+        self.fractal == other.fractal
+            && self.pan.approx() == other.pan.approx()
+            && self.scale == other.scale
+            && self.rotation == other.rotation
+            && self.iteration_factor == other.iteration_factor
+    }
+}
+
+/// The views navigation came to rest at, to step back and forth through. A view counts once
+/// it's been left alone for as long as a preview takes to settle, so a zoom held down or a
+/// drag is one step rather than one per frame.
+pub struct History {
+    back: Vec<State>,
+    forward: Vec<State>,
+    /// The last view that came to rest.
+    settled: State,
+    /// The view as of the last frame, and when it last changed.
+    last: (State, Instant)
+}
+
+impl History {
+    pub fn new(state: State) -> History {
+        History { back: Vec::new(), forward: Vec::new(), settled: state.clone(), last: (state, Instant::now()) }
+    }
+
+    /// Follows the view from frame to frame, recording it once it comes to rest somewhere new.
+    pub fn track(&mut self, state: State) {
+        if state != self.last.0 {
+            self.last = (state, Instant::now());
+        } else if state != self.settled && self.last.1.elapsed() > SETTLE {
+            self.back.push(std::mem::replace(&mut self.settled, state));
+            if self.back.len() > LIMIT {
+                self.back.remove(0);
+            }
+            self.forward.clear();
+        }
+    }
+
+    /// Where to go back to from `current`: the last view to come to rest, or the one before
+    /// it if that's still where the view is.
+    pub fn undo(&mut self, current: State) -> Option<State> {
+        let target = if current != self.settled { self.settled.clone() } else { self.back.pop()? };
+        self.forward.push(current);
+        Some(self.arrive(target))
+    }
+
+    pub fn redo(&mut self) -> Option<State> {
+        let target = self.forward.pop()?;
+        self.back.push(self.settled.clone());
+        Some(self.arrive(target))
+    }
+
+    fn arrive(&mut self, target: State) -> State {
+        self.settled = target.clone();
+        self.last = (target.clone(), Instant::now());
+        target
+    }
+}
@@ -0,0 +1,36 @@
+use crate::font;
+
+const MARGIN: usize = 8;
+const PADDING: usize = 4;
+const SCALE: usize = 2;
+const LINE_GAP: usize = 3;
+
+const LINE_HEIGHT: usize = font::GLYPH.1 * SCALE + LINE_GAP;
+
+/// Writes `lines` in the top left corner over a darkened box, so they read on any coloring.
+pub fn draw(frame: &mut [u32], dimensions: (usize, usize), lines: &[String]) {
+    panel(frame, dimensions, MARGIN, lines);
+}
+
+/// Like `draw`, in the bottom left corner.
+pub fn draw_bottom(frame: &mut [u32], dimensions: (usize, usize), lines: &[String]) {
+    let top = dimensions.1.saturating_sub(MARGIN + 2 * PADDING + lines.len() * LINE_HEIGHT);
+    panel(frame, dimensions, top, lines);
+}
+
+fn panel(frame: &mut [u32], dimensions: (usize, usize), top: usize, lines: &[String]) {
+    let (width, height) = dimensions;
+    let text_width = lines.iter().map(|line| font::width(line, SCALE)).max().unwrap_or(0);
+    let right = (MARGIN + 2 * PADDING + text_width).min(width);
+    let bottom = (top + 2 * PADDING + lines.len() * LINE_HEIGHT).min(height);
+
+    for y in top.min(bottom)..bottom {
+        for pixel in &mut frame[y * width + MARGIN.min(right)..y * width + right] {
+            *pixel = (*pixel >> 2) & 0x3F3F3F;
+        }
+    }
+    for (n, line) in lines.iter().enumerate() {
+        let origin = (MARGIN + PADDING, top + PADDING + n * LINE_HEIGHT);
+        font::draw(frame, dimensions, origin, line, SCALE, 0xFFFFFF);
+    }
+}
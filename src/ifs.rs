@@ -0,0 +1,124 @@
+use crate::{FractalContext, WIDTH, HEIGHT};
+use crate::splat::{self, Rng};
+
+/// One affine map `(x, y) -> (a x + b y + e, c x + d y + f)`, chosen with probability `p`.
+#[derive(Clone, Copy)]
+struct Affine {
+    coeffs: [f64; 6],
+    p: f64
+}
+
+impl Affine {
+    fn new(coeffs: [f64; 6], p: f64) -> Affine {
+        Affine { coeffs, p }
+    }
+
+    fn apply(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        let [a, b, c, d, e, f] = self.coeffs;
+        (a * x + b * y + e, c * x + d * y + f)
+    }
+}
+
+pub struct System {
+    maps: Vec<Affine>
+}
+
+impl System {
+    pub fn fern() -> System {
+        System::normalized(vec![
+            Affine::new([0., 0., 0., 0.16, 0., 0.], 0.01),
+            Affine::new([0.85, 0.04, -0.04, 0.85, 0., 1.6], 0.85),
+            Affine::new([0.2, -0.26, 0.23, 0.22, 0., 1.6], 0.07),
+            Affine::new([-0.15, 0.28, 0.26, 0.24, 0., 0.44], 0.07)
+        ])
+    }
+
+    pub fn sierpinski() -> System {
+        System::normalized(vec![
+            Affine::new([0.5, 0., 0., 0.5, 0., 0.], 1.),
+            Affine::new([0.5, 0., 0., 0.5, 0.5, 0.], 1.),
+            Affine::new([0.5, 0., 0., 0.5, 0.25, 0.5], 1.)
+        ])
+    }
+
+    /// Loads one map per line as `a b c d e f [p]`; `#` starts a comment. Maps without an
+    /// explicit probability are weighted by the area they cover.
+    pub fn load(path: &str) -> Result<System, String> {
+        let source = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+
+        let mut maps = Vec::new();
+        for (n, line) in source.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let values = line
+                .split_whitespace()
+                .map(|v| v.parse::<f64>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|err| format!("{}:{}: {}", path, n + 1, err))?;
+
+            let (coeffs, p) = match values.len() {
+                6 => {
+                    let det = (values[0] * values[3] - values[1] * values[2]).abs();
+                    (&values[..6], det.max(0.01))
+                }
+                7 => (&values[..6], values[6]),
+                len => return Err(format!("{}:{}: expected 6 or 7 values, found {}", path, n + 1, len))
+            };
+            maps.push(Affine::new(coeffs.try_into().unwrap(), p));
+        }
+
+        if maps.is_empty() {
+            return Err(format!("{}: no maps defined", path));
+        }
+        Ok(System::normalized(maps))
+    }
+
+    fn normalized(mut maps: Vec<Affine>) -> System {
+        let total: f64 = maps.iter().map(|map| map.p).sum();
+        maps.iter_mut().for_each(|map| map.p /= total);
+        System { maps }
+    }
+
+    fn pick(&self, r: f64) -> &Affine {
+        let mut acc = 0.;
+        for map in &self.maps {
+            acc += map.p;
+            if r < acc {
+                return map;
+            }
+        }
+        self.maps.last().unwrap()
+    }
+
+    fn step(&self, p: &mut (f64, f64), rng: &mut Rng) -> (f64, f64) {
+        *p = self.pick(rng.next_f64()).apply(*p);
+        // screen y grows downwards, IFS definitions assume it grows upwards
+        (p.0, -p.1)
+    }
+
+    /// Pan and scale framing the attractor in the default window.
+    pub fn home(&self) -> ((f64, f64), f64) {
+        let mut rng = Rng::new(1);
+        let mut p = (0., 0.);
+        let (mut min, mut max) = ((f64::MAX, f64::MAX), (f64::MIN, f64::MIN));
+
+        for i in 0..10_000 {
+            let q = self.step(&mut p, &mut rng);
+            if i >= 100 {
+                min = (min.0.min(q.0), min.1.min(q.1));
+                max = (max.0.max(q.0), max.1.max(q.1));
+            }
+        }
+
+        let size = ((max.0 - min.0).max(1e-9), (max.1 - min.1).max(1e-9));
+        let scale = 0.9 * (WIDTH as f64 / size.0).min(HEIGHT as f64 / size.1);
+        (((min.0 + max.0) / 2., (min.1 + max.1) / 2.), scale)
+    }
+}
+
+pub fn render(ctx: &mut FractalContext, system: &System, points: usize) {
+    splat::render(ctx, points, |p, rng| system.step(p, rng));
+}
@@ -0,0 +1,125 @@
+use crate::bookmarks::{tables, unquote};
+use crate::fixed::Center;
+use crate::palette::Palette;
+use crate::{goto, palette_file, HOME_SCALE};
+
+/// A view a tour passes through, and when.
+#[derive(Clone)]
+pub struct Keyframe {
+    /// Seconds from the start of the tour.
+    pub time: f64,
+    pub pan: Center,
+    pub scale: f64,
+    /// In radians.
+    pub rotation: f64,
+    pub iteration_factor: f64,
+    /// Index into the palettes of the one to show, carried on from the keyframe before if
+    /// this one doesn't say.
+    pub palette: Option<usize>,
+    pub palette_offset: f64
+}
+
+/// Keyframes to move through one after the other, in order of time.
+pub struct Tour {
+    keyframes: Vec<Keyframe>
+}
+
+impl Tour {
+    /// Reads the `[[keyframe]]` tables in the file at `path`, adding the palettes they name
+    /// that aren't in `palettes` from their files.
+    pub fn load(path: &str, palettes: &mut Vec<Palette>) -> Result<Tour, String> {
+        let source = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+        let mut keyframes: Vec<Keyframe> = Vec::new();
+        for (n, table) in tables(&source, "keyframe").map_err(|err| format!("{}: {}", path, err))? {
+            let keyframe = keyframe(&table, keyframes.last(), palettes).map_err(|err| format!("{}: keyframe on line {}: {}", path, n, err))?;
+            keyframes.push(keyframe);
+        }
+        if keyframes.is_empty() {
+            return Err(format!("{}: no [[keyframe]] tables", path));
+        }
+        Ok(Tour { keyframes })
+    }
+
+    /// A tour of just a zoom from `from` to `to`, taking a second.
+    pub fn zoom(from: (Center, f64), to: (Center, f64, f64), iteration_factor: f64) -> Tour {
+        let keyframe = |time, (pan, scale, rotation)| Keyframe { time, pan, scale, rotation, iteration_factor, palette: None, palette_offset: 0. };
+        Tour { keyframes: vec![keyframe(0., (from.0, from.1, 0.)), keyframe(1., to)] }
+    }
+
+    /// Seconds until the last keyframe.
+    pub fn duration(&self) -> f64 {
+        self.keyframes.last().map_or(0., |keyframe| keyframe.time)
+    }
+
+    /// The view `time` seconds into the tour, which holds still before the first keyframe and
+    /// after the last.
+    pub fn at(&self, time: f64) -> Keyframe {
+        let next = self.keyframes.iter().position(|keyframe| keyframe.time > time);
+        let (from, to) = match next {
+            Some(0) => return self.keyframes[0].clone(),
+            Some(next) => (&self.keyframes[next - 1], &self.keyframes[next]),
+            None => return self.keyframes[self.keyframes.len() - 1].clone()
+        };
+        let t = (time - from.time) / (to.time - from.time);
+        let (pan, scale) = between((&from.pan, from.scale), (&to.pan, to.scale), t);
+        Keyframe {
+            time,
+            pan,
+            scale,
+            rotation: from.rotation + (to.rotation - from.rotation) * t,
+            iteration_factor: from.iteration_factor * (to.iteration_factor / from.iteration_factor).powf(t),
+            palette: from.palette,
+            palette_offset: from.palette_offset + (to.palette_offset - from.palette_offset) * t
+        }
+    }
+}
+
+fn keyframe(table: &[(String, String)], before: Option<&Keyframe>, palettes: &mut Vec<Palette>) -> Result<Keyframe, String> {
+    let get = |key: &str| table.iter().rev().find(|(name, _)| name == key).map(|(_, value)| value.as_str());
+    let required = |key: &str| get(key).ok_or(format!("missing {}", key));
+    let number = |key: &str, default: f64| match get(key) {
+        Some(value) => value.parse::<f64>().ok().filter(|number| number.is_finite()).ok_or(format!("bad {} {}", key, value)),
+        None => Ok(default)
+    };
+
+    let time = number("time", 0.)?;
+    if before.is_some_and(|before| time <= before.time) {
+        return Err(format!("time {} isn't after the keyframe before", time));
+    }
+    // numbers too long for f64 have to be quoted, but plain ones will do for the rest
+    let text = |key: &str| required(key).map(|value| unquote(value).unwrap_or(value.to_string()));
+    let pan = Center::parse(&text("re")?, &text("im")?).ok_or("bad center")?;
+    let zoom = required("zoom")?;
+    let palette = match get("palette") {
+        Some(name) => {
+            let name = unquote(name).ok_or(format!("bad palette {}", name))?;
+            Some(palette_file::find(palettes, &name)?)
+        }
+        None => before.and_then(|before| before.palette)
+    };
+    Ok(Keyframe {
+        time,
+        pan,
+        scale: HOME_SCALE * goto::parse_zoom(zoom).ok_or(format!("bad zoom {}", zoom))?,
+        rotation: number("rotation", 0.)?.to_radians(),
+        iteration_factor: number("iterations", 1.)?,
+        palette,
+        palette_offset: number("palette_offset", 0.)?
+    })
+}
+
+/// The view a fraction `t` of the way from `from` to `to`, the scale growing by the same
+/// factor in the same time throughout and the center moving so that where the zoom ends up
+/// closes in on the middle of the view steadily rather than flying past and coming back.
+pub fn between((start, s0): (&Center, f64), (end, s1): (&Center, f64), t: f64) -> (Center, f64) {
+    let scale = s0 * (s1 / s0).powf(t);
+    // the share of the way still to go, measured in the view's width at each scale
+    let rest = if s0 == s1 { 1. - t } else { (1. / scale - 1. / s1) / (1. / s0 - 1. / s1) };
+    let (exact, end_exact) = (start.exact(), end.exact());
+    let distance = ((end_exact.0 - exact.0).to_f64(), (end_exact.1 - exact.1).to_f64());
+    // from whichever end is nearer, so deep in what's left to go is small enough for f64 to
+    // hold exactly, and the first and last frames are the views themselves
+    let (mut pan, share) = if rest < 0.5 { (end.clone(), -rest) } else { (start.clone(), 1. - rest) };
+    pan.shift((distance.0 * share, distance.1 * share), scale);
+    (pan, scale)
+}
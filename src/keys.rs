@@ -0,0 +1,300 @@
+use std::path::PathBuf;
+
+use minifb::{Key, KeyRepeat, Window};
+
+/// What the viewer can be told to do from the keyboard.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Action {
+    ZoomIn,
+    ZoomOut,
+    PanUp,
+    PanDown,
+    PanLeft,
+    PanRight,
+    RotateLeft,
+    RotateRight,
+    IterationCurve,
+    IterationsUp,
+    IterationsDown,
+    /// Back to the initial view and iteration limit.
+    Reset,
+    NextPalette,
+    NextInterior,
+    Slope,
+    Dither,
+    GammaUp,
+    GammaDown,
+    ExposureUp,
+    ExposureDown,
+    Editor,
+    PaletteCycle,
+    /// Switch to the fractal at this index of the number key list.
+    Fractal(usize),
+    Undo,
+    Redo,
+    Bookmark,
+    NextBookmark,
+    /// Open the Julia set of the point under the mouse.
+    Julia,
+    /// Show the Julia set of the point under the mouse beside the view.
+    Split,
+    Minimap,
+    Goto,
+    Hud,
+    Crosshair,
+    Fullscreen,
+    /// Save the view without overlays as a PNG.
+    Screenshot,
+    /// Copy the center and zoom to the clipboard.
+    CopyLocation,
+    /// Print the command line that opens the view.
+    PrintLocation,
+    /// Show the key bindings and settings.
+    Help,
+    /// Play the keyframe tour from the start, or stop it.
+    Tour,
+    /// Write the whole state of the viewer to a file, or read it back.
+    SaveState,
+    LoadState,
+    Quit
+}
+
+impl Action {
+    /// Whether the action pans, zooms or turns the view.
+    pub fn moves(self) -> bool {
+        matches!(
+            self,
+            Action::ZoomIn | Action::ZoomOut | Action::PanUp | Action::PanDown | Action::PanLeft | Action::PanRight
+                | Action::RotateLeft | Action::RotateRight
+        )
+    }
+}
+
+/// When a bound key sets off its action.
+#[derive(Clone, Copy)]
+enum Trigger {
+    /// Every frame it's held.
+    Held,
+    /// Once per press.
+    Pressed,
+    /// Once per press, then at the key repeat rate.
+    Repeated
+}
+
+/// Every action with its name in the bindings file, trigger and default keys.
+const ACTIONS: [(Action, &str, Trigger, &[Chord]); 50] = [
+    (Action::ZoomIn, "zoom-in", Trigger::Held, &[Chord::plain(Key::I)]),
+    (Action::ZoomOut, "zoom-out", Trigger::Held, &[Chord::plain(Key::O)]),
+    (Action::PanUp, "pan-up", Trigger::Held, &[Chord::plain(Key::Up)]),
+    (Action::PanDown, "pan-down", Trigger::Held, &[Chord::plain(Key::Down)]),
+    (Action::PanLeft, "pan-left", Trigger::Held, &[Chord::plain(Key::Left)]),
+    (Action::PanRight, "pan-right", Trigger::Held, &[Chord::plain(Key::Right)]),
+    (Action::RotateLeft, "rotate-left", Trigger::Held, &[Chord::plain(Key::Comma)]),
+    (Action::RotateRight, "rotate-right", Trigger::Held, &[Chord::plain(Key::Period)]),
+    (Action::IterationCurve, "iteration-curve", Trigger::Pressed, &[Chord::plain(Key::A)]),
+    (Action::IterationsUp, "iterations-up", Trigger::Repeated, &[Chord::plain(Key::RightBracket)]),
+    (Action::IterationsDown, "iterations-down", Trigger::Repeated, &[Chord::plain(Key::LeftBracket)]),
+    (Action::Reset, "reset", Trigger::Pressed, &[Chord::plain(Key::R)]),
+    (Action::NextPalette, "next-palette", Trigger::Pressed, &[Chord::plain(Key::P)]),
+    (Action::NextInterior, "next-interior", Trigger::Pressed, &[Chord::plain(Key::N)]),
+    (Action::Slope, "slope", Trigger::Pressed, &[Chord::plain(Key::L)]),
+    (Action::Dither, "dither", Trigger::Pressed, &[Chord::shifted(Key::B)]),
+    (Action::GammaUp, "gamma-up", Trigger::Repeated, &[Chord::plain(Key::G)]),
+    (Action::GammaDown, "gamma-down", Trigger::Repeated, &[Chord::plain(Key::F)]),
+    (Action::ExposureUp, "exposure-up", Trigger::Repeated, &[Chord::plain(Key::E)]),
+    (Action::ExposureDown, "exposure-down", Trigger::Repeated, &[Chord::plain(Key::D)]),
+    (Action::Editor, "editor", Trigger::Pressed, &[Chord::plain(Key::Tab)]),
+    (Action::PaletteCycle, "palette-cycle", Trigger::Pressed, &[Chord::plain(Key::C)]),
+    (Action::Fractal(0), "fractal-1", Trigger::Pressed, &[Chord::plain(Key::Key1)]),
+    (Action::Fractal(1), "fractal-2", Trigger::Pressed, &[Chord::plain(Key::Key2)]),
+    (Action::Fractal(2), "fractal-3", Trigger::Pressed, &[Chord::plain(Key::Key3)]),
+    (Action::Fractal(3), "fractal-4", Trigger::Pressed, &[Chord::plain(Key::Key4)]),
+    (Action::Fractal(4), "fractal-5", Trigger::Pressed, &[Chord::plain(Key::Key5)]),
+    (Action::Fractal(5), "fractal-6", Trigger::Pressed, &[Chord::plain(Key::Key6)]),
+    (Action::Fractal(6), "fractal-7", Trigger::Pressed, &[Chord::plain(Key::Key7)]),
+    (Action::Fractal(7), "fractal-8", Trigger::Pressed, &[Chord::plain(Key::Key8)]),
+    (Action::Fractal(8), "fractal-9", Trigger::Pressed, &[Chord::plain(Key::Key9)]),
+    (Action::Julia, "julia", Trigger::Pressed, &[Chord::plain(Key::J)]),
+    (Action::Split, "split", Trigger::Pressed, &[Chord::plain(Key::S)]),
+    (Action::Minimap, "minimap", Trigger::Pressed, &[Chord::plain(Key::M)]),
+    (Action::Goto, "goto", Trigger::Pressed, &[Chord::plain(Key::Slash)]),
+    (Action::Undo, "undo", Trigger::Repeated, &[Chord::plain(Key::Backspace)]),
+    (Action::Redo, "redo", Trigger::Repeated, &[Chord::shifted(Key::Backspace)]),
+    (Action::Bookmark, "bookmark", Trigger::Pressed, &[Chord::plain(Key::B)]),
+    (Action::NextBookmark, "next-bookmark", Trigger::Pressed, &[Chord::plain(Key::V)]),
+    (Action::Hud, "hud", Trigger::Pressed, &[Chord::plain(Key::H)]),
+    (Action::Crosshair, "crosshair", Trigger::Pressed, &[Chord::plain(Key::X)]),
+    (Action::Fullscreen, "fullscreen", Trigger::Pressed, &[Chord::plain(Key::F11)]),
+    (Action::Screenshot, "screenshot", Trigger::Pressed, &[Chord::shifted(Key::S)]),
+    (Action::CopyLocation, "copy-location", Trigger::Pressed, &[Chord::shifted(Key::C)]),
+    (Action::PrintLocation, "print-location", Trigger::Pressed, &[Chord::shifted(Key::L)]),
+    (Action::Help, "help", Trigger::Pressed, &[Chord::plain(Key::F1), Chord::shifted(Key::Slash)]),
+    (Action::Tour, "tour", Trigger::Pressed, &[Chord::plain(Key::T)]),
+    (Action::SaveState, "save-state", Trigger::Pressed, &[Chord::plain(Key::F5)]),
+    (Action::LoadState, "load-state", Trigger::Pressed, &[Chord::plain(Key::F9)]),
+    (Action::Quit, "quit", Trigger::Pressed, &[Chord::plain(Key::Escape)])
+];
+
+/// A key, and whether shift has to be held with it.
+#[derive(Clone, Copy, PartialEq)]
+struct Chord {
+    key: Key,
+    shift: bool
+}
+
+impl Chord {
+    const fn plain(key: Key) -> Chord {
+        Chord { key, shift: false }
+    }
+
+    const fn shifted(key: Key) -> Chord {
+        Chord { key, shift: true }
+    }
+}
+
+/// Names keys go by in the bindings file, those of minifb's `Key`.
+const KEY_NAMES: [(&str, Key); 74] = [
+    ("A", Key::A), ("B", Key::B), ("C", Key::C), ("D", Key::D), ("E", Key::E), ("F", Key::F),
+    ("G", Key::G), ("H", Key::H), ("I", Key::I), ("J", Key::J), ("K", Key::K), ("L", Key::L),
+    ("M", Key::M), ("N", Key::N), ("O", Key::O), ("P", Key::P), ("Q", Key::Q), ("R", Key::R),
+    ("S", Key::S), ("T", Key::T), ("U", Key::U), ("V", Key::V), ("W", Key::W), ("X", Key::X),
+    ("Y", Key::Y), ("Z", Key::Z),
+    ("Key0", Key::Key0), ("Key1", Key::Key1), ("Key2", Key::Key2), ("Key3", Key::Key3), ("Key4", Key::Key4),
+    ("Key5", Key::Key5), ("Key6", Key::Key6), ("Key7", Key::Key7), ("Key8", Key::Key8), ("Key9", Key::Key9),
+    ("F1", Key::F1), ("F2", Key::F2), ("F3", Key::F3), ("F4", Key::F4), ("F5", Key::F5), ("F6", Key::F6),
+    ("F7", Key::F7), ("F8", Key::F8), ("F9", Key::F9), ("F10", Key::F10), ("F11", Key::F11), ("F12", Key::F12),
+    ("Up", Key::Up), ("Down", Key::Down), ("Left", Key::Left), ("Right", Key::Right),
+    ("Space", Key::Space), ("Tab", Key::Tab), ("Escape", Key::Escape), ("Enter", Key::Enter),
+    ("Backspace", Key::Backspace), ("Delete", Key::Delete), ("Insert", Key::Insert), ("Home", Key::Home),
+    ("End", Key::End), ("PageUp", Key::PageUp), ("PageDown", Key::PageDown),
+    ("Minus", Key::Minus), ("Equal", Key::Equal), ("LeftBracket", Key::LeftBracket),
+    ("RightBracket", Key::RightBracket), ("Comma", Key::Comma), ("Period", Key::Period),
+    ("Slash", Key::Slash), ("Backslash", Key::Backslash), ("Semicolon", Key::Semicolon),
+    ("Apostrophe", Key::Apostrophe), ("Backquote", Key::Backquote)
+];
+
+/// The keys bound to each action.
+pub struct Bindings {
+    keys: Vec<(Action, Trigger, Vec<Chord>)>
+}
+
+impl Default for Bindings {
+    fn default() -> Bindings {
+        Bindings { keys: ACTIONS.iter().map(|&(action, _, trigger, chords)| (action, trigger, chords.to_vec())).collect() }
+    }
+}
+
+impl Bindings {
+    /// The default bindings with those in the user's `keys.toml` replacing them, if there
+    /// is one.
+    pub fn load() -> Result<Bindings, String> {
+        let mut bindings = Bindings::default();
+        let path = match config_path("keys.toml") {
+            Some(path) if path.exists() => path,
+            _ => return Ok(bindings)
+        };
+        let source = std::fs::read_to_string(&path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        bindings.parse(&source).map_err(|err| format!("{}: {}", path.display(), err))?;
+        Ok(bindings)
+    }
+
+    /// Reads `action = "Key"` or `action = ["Key", "Shift+Key", ...]` lines, the part of TOML
+    /// a list of bindings needs.
+    fn parse(&mut self, source: &str) -> Result<(), String> {
+        for (n, line) in source.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() || line.starts_with('[') {
+                continue;
+            }
+
+            let (name, value) = line.split_once('=').ok_or(format!("line {}: expected action = key", n + 1))?;
+            let name = name.trim().trim_matches('"');
+            let index = ACTIONS
+                .iter()
+                .position(|&(_, action, _, _)| action == name)
+                .ok_or(format!("line {}: unknown action {}", n + 1, name))?;
+
+            let value = value.trim();
+            let list = value.strip_prefix('[').and_then(|value| value.strip_suffix(']')).unwrap_or(value);
+            let keys = list
+                .split(',')
+                .map(str::trim)
+                .filter(|key| !key.is_empty())
+                .map(|key| {
+                    let key = key.trim_matches('"');
+                    let (shift, name) = match key.strip_prefix("Shift+") {
+                        Some(name) => (true, name),
+                        None => (false, key)
+                    };
+                    match KEY_NAMES.iter().find(|&&(known, _)| known == name) {
+                        Some(&(_, key)) => Ok(Chord { key, shift }),
+                        None => Err(format!("line {}: unknown key {}", n + 1, key))
+                    }
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            self.keys[index].2 = keys;
+        }
+        Ok(())
+    }
+
+    /// The actions this frame's keys set off, in the order they're listed above. Keys bound
+    /// without shift work with it held too, unless something's bound to them with it.
+    pub fn triggered(&self, window: &Window) -> Vec<Action> {
+        let shift = window.is_key_down(Key::LeftShift) || window.is_key_down(Key::RightShift);
+        let shifted = |key| self.keys.iter().any(|(_, _, chords)| chords.contains(&Chord::shifted(key)));
+        self.keys
+            .iter()
+            .filter(|(_, trigger, chords)| chords.iter().any(|&chord| {
+                let fired = match trigger {
+                    Trigger::Held => window.is_key_down(chord.key),
+                    Trigger::Pressed => window.is_key_pressed(chord.key, KeyRepeat::No),
+                    Trigger::Repeated => window.is_key_pressed(chord.key, KeyRepeat::Yes)
+                };
+                fired && (chord.shift == shift || (!chord.shift && !shifted(chord.key)))
+            }))
+            .map(|&(action, _, _)| action)
+            .collect()
+    }
+
+    /// Each action's name with the keys bound to it, the way the bindings file writes them.
+    pub fn list(&self) -> Vec<(&'static str, String)> {
+        self.keys
+            .iter()
+            .zip(ACTIONS)
+            .map(|((_, _, chords), (_, name, _, _))| {
+                let keys: Vec<String> = chords
+                    .iter()
+                    .map(|chord| {
+                        let key = KEY_NAMES.iter().find(|&&(_, key)| key == chord.key).map_or("?", |&(name, _)| name);
+                        if chord.shift { format!("Shift+{}", key) } else { key.to_string() }
+                    })
+                    .collect();
+                (name, keys.join(", "))
+            })
+            .collect()
+    }
+}
+
+/// The action `name` stands for in the bindings file.
+pub fn action(name: &str) -> Option<Action> {
+    ACTIONS.iter().find(|(_, action_name, _, _)| *action_name == name).map(|&(action, _, _, _)| action)
+}
+
+/// `file` in the fractalv directory of the user's config directory.
+pub fn config_path(file: &str) -> Option<PathBuf> {
+    let config = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(std::env::var_os("HOME")?).join(".config")
+    };
+    Some(config.join("fractalv").join(file))
+}
+
+/// `file` in the fractalv directory of the user's data directory, for what the viewer keeps
+/// for itself rather than for editing.
+pub fn data_path(file: &str) -> Option<PathBuf> {
+    let data = match std::env::var_os("XDG_DATA_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(std::env::var_os("HOME")?).join(".local").join("share")
+    };
+    Some(data.join("fractalv").join(file))
+}
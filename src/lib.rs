@@ -0,0 +1,1574 @@
+extern crate rayon;
+extern crate num_complex;
+
+pub mod attractor;
+pub mod batch;
+pub mod bench;
+pub mod bookmarks;
+pub mod boundary;
+pub mod checkpoint;
+pub mod coloring;
+pub mod config;
+pub mod csv;
+pub mod double;
+pub mod export;
+pub mod exr;
+pub mod fixed;
+pub mod font;
+pub mod formula;
+pub mod gif;
+pub mod goto;
+pub mod history;
+#[cfg(feature = "gpu")]
+pub mod gpu;
+pub mod hud;
+pub mod ifs;
+pub mod keys;
+pub mod keyframes;
+pub mod location;
+pub mod minimap;
+pub mod npy;
+pub mod oklab;
+#[cfg(feature = "opencl")]
+pub mod opencl;
+pub mod orbit;
+pub mod palette;
+pub mod palette_file;
+pub mod perturbation;
+pub mod plugin;
+pub mod png;
+pub mod renderer;
+pub mod simd;
+pub mod splat;
+pub mod worker;
+
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use rayon::prelude::*;
+use num_complex::Complex;
+use coloring::{Coloring, Interior};
+use config::Config;
+use bookmarks::Bookmark;
+use history::State;
+use fixed::Center;
+pub use formula::Formula;
+use keyframes::Tour;
+use location::Location;
+use orbit::{Orbit, Sample, Tracking, Trap};
+use palette::Palette;
+pub use renderer::Renderer;
+
+/// Size of the view each fractal's initial view is framed for, and of the window unless
+/// the config says otherwise.
+pub const WIDTH: usize = 640;
+pub const HEIGHT: usize = 360;
+pub const COARSE_BLOCK: usize = 8;
+pub const TILE: usize = 64;
+pub const FRAME_BUDGET: Duration = Duration::from_millis(50);
+/// Divisor of the window size the view is previewed at while it's being panned or zoomed.
+pub const PREVIEW: usize = 4;
+/// How long input has to pause before the preview is replaced by the full resolution view.
+pub const SETTLE: Duration = Duration::from_millis(200);
+/// Arm length of the center crosshair unless given on the command line.
+pub const CROSSHAIR_SIZE: usize = 4;
+/// Size of the window in fullscreen, when `--screen` doesn't say, as minifb can't tell.
+pub const SCREEN: (usize, usize) = (1920, 1080);
+/// Scale of the initial view, where the iteration limit given on the command line applies.
+pub const HOME_SCALE: f64 = 100.;
+/// By default deeper zooms get `zoom^0.2` times the iterations, 16 times at a million.
+pub const ITERATION_CURVE: f64 = 0.2;
+/// Scale past which f64 pixel coordinates run out of precision, so the Mandelbrot set is
+/// rendered by perturbation and Julia sets, having no single reference orbit, in the much
+/// slower double-double. That lasts until about 1e30.
+pub const DEEP_SCALE: f64 = 1e12;
+
+pub struct FractalContext {
+    pub dimensions: (usize, usize),
+    pub pan: Center,
+    pub scale: f64,
+    /// Angle the view is turned by, in radians.
+    pub rotation: f64,
+    pub updated: bool,
+    coloring: Coloring,
+    brightness: Option<Coloring>,
+    interior: Interior,
+    slope: bool,
+    light: (f64, f64),
+    gamma: f64,
+    exposure: f64,
+    dither: bool,
+    palette: Palette,
+    palette_offset: f64,
+    tracking: Tracking,
+    /// The shape orbits are trapped by for colorings that need one.
+    trap: Option<Trap>,
+    pub renderer: Box<dyn Renderer + Send + Sync>,
+    /// Fill the inside of rectangles bordered by the set instead of iterating it.
+    tracing: bool,
+    /// Whether the fractal being rendered is one tracing can fill, set as it renders.
+    traceable: bool,
+    /// Skip iterating the main cardioid and period 2 bulb of the Mandelbrot set.
+    bulbs: bool,
+    /// Exponent of the zoom the iteration limit is multiplied by, or `None` to keep it fixed.
+    iteration_curve: Option<f64>,
+    /// What the iteration limit is multiplied by on top of the curve, halved and doubled from
+    /// the keyboard.
+    iteration_factor: f64,
+    /// Divisor of the window size the view is rendered at, above 1 for quick previews.
+    resolution: usize,
+    /// Reference orbit of the view center for deep zooms, computed once per view.
+    reference: Option<perturbation::Reference>,
+    /// Whole pixels the view was panned by since the last render.
+    moved: (isize, isize),
+    /// Pixels left to compute after shifting the samples along with a pan.
+    exposed: Vec<usize>,
+    /// Grid spacing of the current refinement pass, 0 before the first.
+    block: usize,
+    /// Tiles of the current pass still to render.
+    pending: Vec<usize>,
+    /// How long a pass renders for before it shows what it has, `None` to finish each pass
+    /// when nothing's shown in between.
+    frame_budget: Option<Duration>,
+    /// Set by the UI thread to stop a refinement pass for a view that's out of date from
+    /// starting further tiles.
+    pub cancel: Arc<AtomicBool>,
+    pub maxiter: usize,
+    pub samples: Vec<Sample>,
+    pub pixels: Vec<u32>
+}
+
+impl FractalContext {
+    pub fn new() -> FractalContext {
+        FractalContext {
+            dimensions: (WIDTH, HEIGHT),
+            pan: Center::new((0.0, 0.0)),
+            scale: HOME_SCALE,
+            rotation: 0.,
+            updated: true,
+            coloring: Coloring::Banded,
+            brightness: None,
+            interior: Interior::Flat,
+            slope: false,
+            light: coloring::LIGHT,
+            gamma: coloring::GAMMA,
+            exposure: 0.,
+            dither: false,
+            palette: Palette::builtin().remove(0),
+            palette_offset: 0.,
+            tracking: Tracking::default(),
+            trap: None,
+            renderer: Box::new(renderer::Cpu { simd: simd::available() }),
+            tracing: true,
+            traceable: false,
+            bulbs: true,
+            iteration_curve: Some(ITERATION_CURVE),
+            iteration_factor: 1.,
+            resolution: 1,
+            reference: None,
+            moved: (0, 0),
+            exposed: Vec::new(),
+            block: 0,
+            pending: Vec::new(),
+            frame_budget: Some(FRAME_BUDGET),
+            cancel: Arc::new(AtomicBool::new(false)),
+            maxiter: 0,
+            samples: Vec::new(),
+            pixels: vec![0; WIDTH * HEIGHT]
+        }
+    }
+
+    /// A context set up as the command line options ask.
+    pub fn from_options(options: &Options) -> FractalContext {
+        let mut ctx = FractalContext::new();
+        ctx.coloring = options.coloring;
+        ctx.interior = options.interior;
+        ctx.slope = options.slope;
+        ctx.light = options.light;
+        ctx.gamma = options.gamma;
+        ctx.exposure = options.exposure;
+        ctx.dither = options.dither;
+        ctx.renderer = renderer::select(options.gpu, options.opencl, options.simd);
+        ctx.tracing = options.tracing;
+        ctx.bulbs = options.bulbs;
+        ctx.iteration_curve = options.adaptive.then_some(options.iteration_curve);
+        // one at the origin if none's given, so trap coloring can be switched to later
+        ctx.trap = options.trap.or(Some(Trap::Point(Complex::new(0., 0.))));
+        ctx.set_coloring(options.coloring, options.brightness);
+        ctx.palette = options.palettes[options.palette].clone();
+        ctx.palette_offset = options.palette_offset;
+        ctx
+    }
+
+    /// Puts the view where the command line options say, at the fractal's initial view
+    /// unless they say otherwise.
+    pub fn place(&mut self, fract: &Fractal, options: &Options) {
+        (self.pan, self.scale) = fract.home();
+        if let Some(view) = &options.view {
+            (self.pan, self.scale, self.rotation) = (view.pan.clone(), view.scale, view.rotation);
+            self.iteration_factor = view.iteration_factor;
+        }
+        if let Some(location) = &options.location {
+            (self.pan, self.scale) = (location.center.clone(), location.scale);
+            // its iteration limit is for its own view rather than the initial one
+            if let Some(exponent) = self.iteration_curve {
+                self.iteration_factor = 1. / (self.scale / HOME_SCALE).max(1.).powf(exponent);
+            }
+        }
+        if let Some(center) = &options.center {
+            self.pan = center.clone();
+        }
+        if let Some(zoom) = options.zoom {
+            self.scale = HOME_SCALE * zoom;
+        }
+        if let Some(degrees) = options.rotation {
+            self.rotation = degrees.to_radians();
+        }
+    }
+
+    /// Where the view is and how it's colored, for `fractal`'s arguments.
+    fn bookmark(&self, fractal: Vec<String>) -> Bookmark {
+        Bookmark {
+            view: State { fractal, pan: self.pan.clone(), scale: self.scale, rotation: self.rotation, iteration_factor: self.iteration_factor },
+            coloring: self.coloring,
+            brightness: self.brightness,
+            interior: self.interior,
+            slope: self.slope,
+            gamma: self.gamma,
+            exposure: self.exposure,
+            dither: self.dither,
+            palette: self.palette.key().to_string(),
+            palette_offset: self.palette_offset,
+            iteration_curve: self.iteration_curve
+        }
+    }
+
+    /// Colors with `coloring`, by `brightness` too if there's one, tracking what they need.
+    fn set_coloring(&mut self, coloring: Coloring, brightness: Option<Coloring>) {
+        (self.coloring, self.brightness) = (coloring, brightness);
+        self.tracking = match brightness {
+            Some(brightness) => coloring.tracking(self.trap).union(brightness.tracking(self.trap)),
+            None => coloring.tracking(self.trap)
+        };
+    }
+
+    /// Whether interior pixels color the same whatever their orbit, other than its period,
+    /// so they can be filled in without iterating them.
+    fn interior_constant(&self) -> bool {
+        self.tracking.is_empty() && matches!(self.interior, Interior::Flat | Interior::Period)
+    }
+
+    /// Scales the fractal's iteration limit for the current zoom and `iteration_factor`. Views
+    /// zoomed out from the initial one keep the limit as it is, and previews get the limit of
+    /// the full view.
+    fn iterations(&self) -> impl Fn(usize) -> usize {
+        let zoom = self.scale * self.resolution as f64 / HOME_SCALE;
+        let factor = match self.iteration_curve {
+            Some(exponent) => zoom.max(1.).powf(exponent),
+            None => 1.
+        } * self.iteration_factor;
+        // rounded, as a location's factor and the curve only cancel to within a few ulps
+        move |maxiter| ((maxiter as f64 * factor).round() as usize).max(1)
+    }
+
+    pub fn refining(&self) -> bool {
+        self.block > 1 || !self.pending.is_empty()
+    }
+
+    fn point(&self, i: usize) -> Complex<f64> {
+        let (re, im) = self.pan.approx();
+        self.offset(i) + Complex::new(re, im)
+    }
+
+    /// Position of pixel `i` relative to the view center.
+    fn offset(&self, i: usize) -> Complex<f64> {
+        let (x, y) = ((i % self.dimensions.0) as f64 - (self.dimensions.0 as f64 / 2.), (i / self.dimensions.0) as f64 - (self.dimensions.1 as f64 / 2.));
+        let (x, y) = rotate((x, y), self.rotation);
+
+        Complex::new(x / self.scale, y / self.scale)
+    }
+}
+
+impl Default for FractalContext {
+    fn default() -> FractalContext {
+        FractalContext::new()
+    }
+}
+
+/// Turns the window offset `(x, y)` by `angle`, giving the offset in the plane's axes.
+pub fn rotate((x, y): (f64, f64), angle: f64) -> (f64, f64) {
+    let (sin, cos) = angle.sin_cos();
+    (x * cos - y * sin, x * sin + y * cos)
+}
+
+#[derive(Clone, Copy)]
+pub enum Seed {
+    Parameter,
+    Julia(Complex<f64>)
+}
+
+impl Seed {
+    fn start(self, point: Complex<f64>) -> (Complex<f64>, Complex<f64>) {
+        match self {
+            Seed::Parameter => (Complex::new(0., 0.), point),
+            Seed::Julia(c) => (point, c)
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Step {
+    Mandelbrot,
+    BurningShip,
+    Tricorn,
+    Celtic,
+    Buffalo,
+    PerpendicularBurningShip
+}
+
+impl Step {
+    fn from_letter(letter: char) -> Option<Step> {
+        match letter.to_ascii_uppercase() {
+            'M' => Some(Step::Mandelbrot),
+            'B' => Some(Step::BurningShip),
+            'T' => Some(Step::Tricorn),
+            'C' => Some(Step::Celtic),
+            'F' => Some(Step::Buffalo),
+            'P' => Some(Step::PerpendicularBurningShip),
+            _ => None
+        }
+    }
+
+    fn apply(self, z: Complex<f64>, c: Complex<f64>) -> Complex<f64> {
+        match self {
+            Step::Mandelbrot => z * z + c,
+            Step::BurningShip => {
+                let abs_z = Complex::new(z.re.abs(), z.im.abs());
+                abs_z * abs_z + c
+            }
+            Step::Tricorn => z.conj() * z.conj() + c,
+            Step::Celtic => {
+                let sq = z * z;
+                Complex::new(sq.re.abs(), sq.im) + c
+            }
+            Step::Buffalo => {
+                let sq = z * z;
+                Complex::new(sq.re.abs(), sq.im.abs()) + c
+            }
+            Step::PerpendicularBurningShip => {
+                let folded = Complex::new(z.re, z.im.abs());
+                folded * folded + c
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Transcendental {
+    Exp,
+    Sin,
+    Cos
+}
+
+impl Transcendental {
+    fn start(self) -> Complex<f64> {
+        match self {
+            // sin has a fixed point at 0, so use its critical point instead
+            Transcendental::Sin => Complex::new(std::f64::consts::FRAC_PI_2, 0.),
+            Transcendental::Exp | Transcendental::Cos => Complex::new(0., 0.)
+        }
+    }
+
+    fn apply(self, z: Complex<f64>, c: Complex<f64>) -> Complex<f64> {
+        match self {
+            Transcendental::Exp => c * z.exp(),
+            Transcendental::Sin => c * z.sin(),
+            Transcendental::Cos => c * z.cos()
+        }
+    }
+
+    fn escaped(self, z: Complex<f64>) -> bool {
+        match self {
+            Transcendental::Exp => z.re > 50.,
+            Transcendental::Sin | Transcendental::Cos => z.im.abs() > 50.
+        }
+    }
+}
+
+pub enum Fractal {
+    Mandelbrot(usize),
+    BurningShip(usize),
+    Julia(Complex<f64>, usize),
+    Tricorn(usize),
+    Multibrot { power: f64, maxiter: usize },
+    Newton { degree: u32, maxiter: usize },
+    Phoenix { p: f64, maxiter: usize },
+    Celtic(usize),
+    Buffalo(usize),
+    PerpendicularBurningShip(usize),
+    Magnet { kind: u8, maxiter: usize },
+    Nova { relaxation: f64, power: f64, maxiter: usize },
+    BurningShipJulia(Complex<f64>, usize),
+    Custom(Formula, usize),
+    Hybrid(Vec<Step>, usize),
+    Lambda(usize),
+    Spider(usize),
+    Manowar(usize),
+    Collatz(usize),
+    Transcendental(Transcendental, usize),
+    Attractor { map: attractor::Map, params: [f64; 4], points: usize },
+    Ifs(ifs::System, usize),
+    /// A kernel a plugin or the program embedding the renderer registered.
+    Plugin(Arc<dyn plugin::Kernel>, usize)
+}
+
+/// A Julia set variant with the iteration limit to give it, all a seed is needed for.
+#[derive(Clone, Copy)]
+pub struct JuliaFamily {
+    /// Its name on the command line.
+    name: &'static str,
+    variant: fn(Complex<f64>, usize) -> Fractal,
+    maxiter: usize
+}
+
+impl JuliaFamily {
+    pub fn at(self, c: Complex<f64>) -> Fractal {
+        (self.variant)(c, self.maxiter)
+    }
+
+    /// The command line arguments that give `at(c)`.
+    pub fn spec(self, c: Complex<f64>) -> Vec<String> {
+        vec![String::new(), self.name.to_string(), c.re.to_string(), c.im.to_string(), self.maxiter.to_string()]
+    }
+}
+
+impl Fractal {
+    /// For fractals over the parameter plane, the Julia sets their points are the seeds of.
+    pub fn julia(&self) -> Option<JuliaFamily> {
+        match *self {
+            Fractal::Mandelbrot(maxiter) => Some(JuliaFamily { name: "julia", variant: Fractal::Julia, maxiter }),
+            Fractal::BurningShip(maxiter) => Some(JuliaFamily { name: "burning-ship-julia", variant: Fractal::BurningShipJulia, maxiter }),
+            _ => None
+        }
+    }
+
+    /// The view a fractal starts at: the whole of an IFS attractor, or the initial scale
+    /// around the origin.
+    pub fn home(&self) -> (Center, f64) {
+        match self {
+            Fractal::Ifs(system, _) => {
+                let (pan, scale) = system.home();
+                (Center::new(pan), scale)
+            }
+            _ => (Center::new((0., 0.)), HOME_SCALE)
+        }
+    }
+
+    /// Renders the view through to the last refinement pass, for when nothing's shown until
+    /// it's done.
+    pub fn render_all(&self, ctx: &mut FractalContext) {
+        self.render(ctx);
+        while ctx.refining() {
+            self.render(ctx);
+        }
+    }
+
+    /// Whether boundary tracing can fill the fractal's interior. The filled sets of z^n + c
+    /// for whole n, and of the logistic map conjugate to z^2 + c, have no holes, so nothing
+    /// outside can be inside a border of interior pixels. Other powers cut the plane along a
+    /// branch of z^n, and folding with abs, or iterating anything else, gives no such promise.
+    fn traceable(&self) -> bool {
+        match self {
+            Fractal::Multibrot { power, .. } => power.fract() == 0. && *power >= 2.,
+            _ => matches!(self, Fractal::Mandelbrot(_) | Fractal::Julia(..) | Fractal::Lambda(_))
+        }
+    }
+
+    /// Renders the next refinement pass, or starts over at the coarsest pass if the view
+    /// changed. Fractals that aren't sampled per pixel always render completely.
+    pub fn render(&self, ctx: &mut FractalContext) {
+        ctx.pixels.resize(ctx.dimensions.0 * ctx.dimensions.1, 0);
+        ctx.traceable = self.traceable();
+        let moved = std::mem::take(&mut ctx.moved);
+        if ctx.updated || (moved != (0, 0) && !Self::shift(ctx, moved)) {
+            ctx.block = 0;
+            ctx.pending.clear();
+        }
+
+        let iterations = ctx.iterations();
+        match self {
+            Fractal::Mandelbrot(max) => {
+                Self::mandelbrot(ctx, Seed::Parameter, iterations(*max));
+            }
+            Fractal::BurningShip(max) => {
+                Self::abs_family(ctx, Seed::Parameter, iterations(*max), |z| Complex::new(z.re.abs(), z.im.abs()), |z| z);
+            }
+            Fractal::Julia(c, max) => {
+                Self::mandelbrot(ctx, Seed::Julia(*c), iterations(*max));
+            }
+            Fractal::Tricorn(max) => {
+                Self::tricorn(ctx, iterations(*max));
+            }
+            Fractal::Multibrot { power, maxiter } => {
+                Self::multibrot(ctx, *power, iterations(*maxiter));
+            }
+            Fractal::Newton { degree, maxiter } => {
+                Self::newton(ctx, *degree, iterations(*maxiter));
+            }
+            Fractal::Phoenix { p, maxiter } => {
+                Self::phoenix(ctx, *p, iterations(*maxiter));
+            }
+            Fractal::Celtic(max) => {
+                Self::abs_family(ctx, Seed::Parameter, iterations(*max), |z| z, |z| Complex::new(z.re.abs(), z.im));
+            }
+            Fractal::Buffalo(max) => {
+                Self::abs_family(ctx, Seed::Parameter, iterations(*max), |z| z, |z| Complex::new(z.re.abs(), z.im.abs()));
+            }
+            Fractal::PerpendicularBurningShip(max) => {
+                Self::abs_family(ctx, Seed::Parameter, iterations(*max), |z| Complex::new(z.re, z.im.abs()), |z| z);
+            }
+            Fractal::Magnet { kind, maxiter } => {
+                Self::magnet(ctx, *kind, iterations(*maxiter));
+            }
+            Fractal::Nova { relaxation, power, maxiter } => {
+                Self::nova(ctx, *relaxation, *power, iterations(*maxiter));
+            }
+            Fractal::BurningShipJulia(c, max) => {
+                Self::abs_family(ctx, Seed::Julia(*c), iterations(*max), |z| Complex::new(z.re.abs(), z.im.abs()), |z| z);
+            }
+            Fractal::Custom(formula, max) => {
+                Self::custom(ctx, formula, iterations(*max));
+            }
+            Fractal::Hybrid(steps, max) => {
+                Self::hybrid(ctx, steps, iterations(*max));
+            }
+            Fractal::Lambda(max) => {
+                Self::lambda(ctx, iterations(*max));
+            }
+            Fractal::Spider(max) => {
+                Self::spider(ctx, iterations(*max));
+            }
+            Fractal::Manowar(max) => {
+                Self::manowar(ctx, iterations(*max));
+            }
+            Fractal::Collatz(max) => {
+                Self::collatz(ctx, iterations(*max));
+            }
+            Fractal::Transcendental(func, max) => {
+                Self::transcendental(ctx, *func, iterations(*max));
+            }
+            Fractal::Attractor { map, params, points } => {
+                attractor::render(ctx, *map, *params, *points);
+            }
+            Fractal::Ifs(system, points) => {
+                ifs::render(ctx, system, *points);
+            }
+            Fractal::Plugin(kernel, max) => {
+                Self::plugin(ctx, kernel.as_ref(), iterations(*max));
+            }
+        }
+
+        if ctx.samples.is_empty() {
+            ctx.block = 1;
+        }
+
+        ctx.updated = false;
+    }
+
+    /// Recolors the last escape-time render without iterating again. Returns false if the
+    /// current pixels weren't produced from samples and so can't be recolored.
+    fn recolor(ctx: &mut FractalContext) -> bool {
+        if ctx.samples.is_empty() {
+            return false;
+        }
+
+        coloring::colorize(ctx);
+        true
+    }
+
+    /// Moves the samples of a finished render along with a pan of `(dx, dy)` pixels, leaving
+    /// only the strips it exposed to be computed. Returns false if there's nothing to reuse.
+    fn shift(ctx: &mut FractalContext, (dx, dy): (isize, isize)) -> bool {
+        let (width, height) = ctx.dimensions;
+        let finished = ctx.block == 1 && ctx.pending.is_empty() && ctx.samples.len() == width * height;
+        if !finished || dx.unsigned_abs() >= width || dy.unsigned_abs() >= height {
+            return false;
+        }
+
+        let old = std::mem::take(&mut ctx.samples);
+        let source = |i: usize| {
+            let (x, y) = ((i % width) as isize + dx, (i / width) as isize + dy);
+            let inside = (0..width as isize).contains(&x) && (0..height as isize).contains(&y);
+            inside.then(|| x as usize + y as usize * width)
+        };
+        ctx.exposed = (0..width * height).filter(|&i| source(i).is_none()).collect();
+        ctx.samples = (0..width * height).map(|i| source(i).map_or(Sample::default(), |j| old[j])).collect();
+        true
+    }
+
+    fn for_each_point<F>(ctx: &mut FractalContext, kernel: F)
+    where
+        F: Fn(Complex<f64>) -> u32 + Sync
+    {
+        ctx.samples.clear();
+
+        // taken out of the context for it to be written while the context is read
+        let mut pixels = std::mem::take(&mut ctx.pixels);
+        pixels.par_iter_mut().enumerate().for_each(|(i, pixel)| *pixel = kernel(ctx.point(i)));
+        ctx.pixels = pixels;
+    }
+
+    /// Computes the pixels a pan exposed if there are any, otherwise works on the current
+    /// refinement pass, or starts the next one. Progressive renders
+    /// start on an 8 pixel grid and halve it each pass, reusing the samples of earlier
+    /// passes. A pass is split into tiles rendered in parallel; each tile samples its grid
+    /// points with `compute`, then fills the pixels in between from the nearest sample above
+    /// and to the left; the final pass instead subdivides each tile by boundary tracing.
+    /// Tiles are taken from the view center outward, so the middle of the view resolves first.
+    /// Once the frame budget runs out, or the view is cancelled, the remaining tiles are left
+    /// pending for the next call, so progress is shown and new requests are picked up
+    /// between. Returns false if `compute` declined.
+    fn refine<F>(ctx: &mut FractalContext, maxiter: usize, progressive: bool, compute: F) -> bool
+    where
+        F: Fn(&FractalContext, &[usize]) -> Option<Vec<Sample>> + Sync
+    {
+        if !ctx.exposed.is_empty() {
+            let exposed = std::mem::take(&mut ctx.exposed);
+            let computed = match compute(ctx, &exposed) {
+                Some(computed) => computed,
+                None => {
+                    ctx.exposed = exposed;
+                    return false;
+                }
+            };
+            for (i, sample) in exposed.into_iter().zip(computed) {
+                ctx.samples[i] = sample;
+            }
+            coloring::colorize(ctx);
+            return true;
+        }
+
+        let (width, height) = ctx.dimensions;
+        let len = width * height;
+        let tile = if progressive { TILE } else { width.max(height) };
+        let tiles: Vec<(usize, usize)> = (0..height.div_ceil(tile))
+            .flat_map(|ty| (0..width.div_ceil(tile)).map(move |tx| (tx * tile, ty * tile)))
+            .collect();
+        let mut order: Vec<usize> = (0..tiles.len()).collect();
+        order.sort_by_key(|&t| {
+            // twice the offset of the tile's middle from the view's
+            let (x, y) = tiles[t];
+            let dx = (x + (x + tile).min(width)) as isize - width as isize;
+            let dy = (y + (y + tile).min(height)) as isize - height as isize;
+            dx * dx + dy * dy
+        });
+
+        // put back if `compute` declines, for whatever renders instead, which may split the
+        // view differently
+        let previous = (ctx.block, ctx.pending.clone());
+        if !progressive || ctx.block == 0 || ctx.samples.len() != len {
+            ctx.block = if progressive { COARSE_BLOCK } else { 1 };
+            ctx.pending = order;
+            ctx.samples.resize(len, Sample::default());
+        } else if ctx.pending.is_empty() {
+            ctx.block /= 2;
+            ctx.pending = order;
+        }
+
+        let block = ctx.block;
+        let fresh = !progressive || block == COARSE_BLOCK;
+        // the first pass always completes, so there's never a half-rendered frame to show
+        let deadline = ctx.frame_budget.filter(|_| !fresh).map(|budget| Instant::now() + budget);
+        let on_grid = |x: usize, y: usize, block: usize| x.is_multiple_of(block) && y.is_multiple_of(block);
+        // the final pass traces boundaries, where the samples it builds on allow filling
+        let trace = progressive && block == 1 && ctx.tracing && ctx.traceable && ctx.interior_constant();
+
+        let declined = AtomicBool::new(false);
+        let ctx_ref = &*ctx;
+        let render = |t: usize| -> Option<Vec<(usize, Sample)>> {
+            let expired = deadline.is_some_and(|deadline| Instant::now() > deadline);
+            let cancelled = !fresh && ctx_ref.cancel.load(Ordering::Relaxed);
+            if expired || cancelled || declined.load(Ordering::Relaxed) {
+                return None;
+            }
+
+            let decline = || {
+                declined.store(true, Ordering::Relaxed);
+                None
+            };
+            let (x0, y0) = tiles[t];
+            let (x1, y1) = ((x0 + tile).min(width), (y0 + tile).min(height));
+            let indices = || (y0..y1).flat_map(move |y| (x0..x1).map(move |x| x + y * width));
+
+            if trace {
+                let mut region: Vec<Sample> = indices().map(|i| ctx_ref.samples[i]).collect();
+                let mut known: Vec<bool> = indices().map(|i| on_grid(i % width, i / width, 2)).collect();
+                let size = (x1 - x0, y1 - y0);
+                let period = ctx_ref.interior == Interior::Period;
+                if boundary::trace(ctx_ref, &compute, (x0, y0), size, &mut region, &mut known, period).is_none() {
+                    return decline();
+                }
+                return Some(indices().zip(region).collect());
+            }
+
+            let pixels: Vec<usize> = (y0..y1)
+                .flat_map(|y| (x0..x1).map(move |x| (x, y)))
+                .filter(|&(x, y)| on_grid(x, y, block) && (fresh || !on_grid(x, y, block * 2)))
+                .map(|(x, y)| x + y * width)
+                .collect();
+            let computed = match compute(ctx_ref, &pixels) {
+                Some(computed) => computed,
+                None => return decline()
+            };
+            if block == 1 {
+                return Some(pixels.into_iter().zip(computed).collect());
+            }
+
+            // tiles are aligned to the coarsest grid, so every source pixel is in this tile
+            let mut region: Vec<Sample> = indices().map(|i| ctx_ref.samples[i]).collect();
+            let local = |i: usize| (i % width - x0) + (i / width - y0) * (x1 - x0);
+            for (i, sample) in pixels.into_iter().zip(computed) {
+                region[local(i)] = sample;
+            }
+            let filled = (y0..y1)
+                .flat_map(|y| (x0..x1).map(move |x| (x, y)))
+                .map(|(x, y)| (x + y * width, region[local(x - x % block + (y - y % block) * width)]))
+                .collect();
+            Some(filled)
+        };
+
+        // rayon would split the list between threads by index, so instead every thread takes
+        // the next tile in order until none are left
+        let next = AtomicUsize::new(0);
+        let pending = &ctx.pending;
+        let mut results: Vec<_> = (0..rayon::current_num_threads())
+            .into_par_iter()
+            .flat_map_iter(|_| std::iter::from_fn(|| {
+                let k = next.fetch_add(1, Ordering::Relaxed);
+                (k < pending.len()).then(|| (k, render(pending[k])))
+            }))
+            .collect();
+        results.sort_by_key(|&(k, _)| k);
+
+        if declined.into_inner() {
+            (ctx.block, ctx.pending) = previous;
+            return false;
+        }
+        let mut pending = Vec::new();
+        for (&t, (_, result)) in ctx.pending.iter().zip(results) {
+            match result {
+                Some(filled) => filled.into_iter().for_each(|(i, sample)| ctx.samples[i] = sample),
+                None => pending.push(t)
+            }
+        }
+
+        ctx.pending = pending;
+        ctx.maxiter = maxiter;
+        coloring::colorize(ctx);
+        true
+    }
+
+    fn escape_time<F>(ctx: &mut FractalContext, seed: Seed, maxiter: usize, kernel: F)
+    where
+        F: Fn(Complex<f64>, Complex<f64>, &mut Orbit) + Sync
+    {
+        Self::refine(ctx, maxiter, true, |ctx, pixels| Some(Self::orbits(ctx, seed, pixels, &kernel)));
+    }
+
+    fn orbits<F>(ctx: &FractalContext, seed: Seed, pixels: &[usize], kernel: &F) -> Vec<Sample>
+    where
+        F: Fn(Complex<f64>, Complex<f64>, &mut Orbit) + Sync
+    {
+        pixels.par_iter().map(|&i| {
+            let (z, c) = seed.start(ctx.point(i));
+            let mut orbit = Orbit::new(ctx.tracking, z, c);
+            kernel(z, c, &mut orbit);
+            orbit.sample()
+        }).collect()
+    }
+
+    /// Samples the pixels inside the main cardioid or the period 2 bulb without iterating
+    /// them, passing the rest to `compute`.
+    fn skip_bulbs<F>(ctx: &FractalContext, seed: Seed, pixels: &[usize], compute: F) -> Option<Vec<Sample>>
+    where
+        F: FnOnce(&[usize]) -> Option<Vec<Sample>>
+    {
+        if !ctx.bulbs || !matches!(seed, Seed::Parameter) || !ctx.interior_constant() {
+            return compute(pixels);
+        }
+
+        let periods: Vec<usize> = pixels.par_iter().map(|&i| bulb_period(ctx.point(i))).collect();
+        let rest: Vec<usize> = pixels.iter().zip(&periods).filter(|(_, &period)| period == 0).map(|(&i, _)| i).collect();
+        let mut computed = compute(&rest)?.into_iter();
+        Some(periods.into_iter().map(|period| match period {
+            0 => computed.next().unwrap(),
+            period => Sample { trap: f64::INFINITY, period, ..Sample::default() }
+        }).collect())
+    }
+
+    fn mandelbrot(ctx: &mut FractalContext, seed: Seed, maxiter: usize) {
+        let deep = ctx.scale > DEEP_SCALE && ctx.tracking.is_empty();
+        if deep && matches!(seed, Seed::Parameter) {
+            // a shifted view has a new center to take the reference from
+            if ctx.block == 0 || ctx.reference.is_none() || !ctx.exposed.is_empty() {
+                ctx.reference = Some(perturbation::Reference::new(ctx, maxiter));
+            }
+            let perturbed = |ctx: &FractalContext, pixels: &[usize]| {
+                let reference = ctx.reference.as_ref()?;
+                Some(perturbation::quadratic(ctx, reference, maxiter, pixels))
+            };
+            Self::refine(ctx, maxiter, true, perturbed);
+            return;
+        }
+        if deep {
+            Self::refine(ctx, maxiter, true, |ctx, pixels| Some(double::quadratic(ctx, seed, maxiter, pixels)));
+            return;
+        }
+
+        let progressive = ctx.renderer.progressive();
+        let accelerated = |ctx: &FractalContext, pixels: &[usize]| {
+            Self::skip_bulbs(ctx, seed, pixels, |pixels| ctx.renderer.quadratic(ctx, seed, maxiter, pixels))
+        };
+        if ctx.tracking.is_empty() && Self::refine(ctx, maxiter, progressive, accelerated) {
+            return;
+        }
+
+        // derivative with respect to c for the parameter plane, z0 for Julia sets
+        let (dz0, dc) = match seed {
+            Seed::Parameter => (Complex::new(0., 0.), Complex::new(1., 0.)),
+            Seed::Julia(_) => (Complex::new(1., 0.), Complex::new(0., 0.))
+        };
+
+        let kernel = |mut z: Complex<f64>, c, orbit: &mut Orbit| {
+            let mut dz = dz0;
+            for i in 0..maxiter {
+                dz = z * dz * 2. + dc;
+                z = z * z + c;
+                orbit.track_derivative(dz);
+                if orbit.visit(i, z, z.norm_sqr() > 4.) {
+                    break;
+                }
+            }
+        };
+        Self::refine(ctx, maxiter, true, |ctx, pixels| {
+            Self::skip_bulbs(ctx, seed, pixels, |pixels| Some(Self::orbits(ctx, seed, pixels, &kernel)))
+        });
+    }
+
+    fn abs_family(
+        ctx: &mut FractalContext,
+        seed: Seed,
+        maxiter: usize,
+        pre: fn(Complex<f64>) -> Complex<f64>,
+        post: fn(Complex<f64>) -> Complex<f64>
+    ) {
+        Self::escape_time(ctx, seed, maxiter, |mut z, c, orbit| {
+            for i in 0..maxiter {
+                let folded = pre(z);
+                z = post(folded * folded) + c;
+                if orbit.visit(i, z, z.norm_sqr() > 4.) {
+                    break;
+                }
+            }
+        });
+    }
+
+    fn tricorn(ctx: &mut FractalContext, maxiter: usize) {
+        Self::escape_time(ctx, Seed::Parameter, maxiter, |mut z, c, orbit| {
+            for i in 0..maxiter {
+                z = z.conj() * z.conj() + c;
+                if orbit.visit(i, z, z.norm_sqr() > 4.) {
+                    break;
+                }
+            }
+        });
+    }
+
+    fn multibrot(ctx: &mut FractalContext, power: f64, maxiter: usize) {
+        Self::escape_time(ctx, Seed::Parameter, maxiter, |mut z, c, orbit| {
+            // |z| > max(|c|, 2^(1/(d-1))) guarantees divergence for z^d + c
+            let radius = if power > 1. {
+                2f64.powf(1. / (power - 1.)).max(c.norm())
+            } else {
+                2.
+            };
+            let bailout = radius * radius;
+
+            for i in 0..maxiter {
+                z = z.powf(power) + c;
+                if orbit.visit(i, z, z.norm_sqr() > bailout) {
+                    break;
+                }
+            }
+        });
+    }
+
+    fn custom(ctx: &mut FractalContext, formula: &Formula, maxiter: usize) {
+        Self::escape_time(ctx, Seed::Parameter, maxiter, |mut z, c, orbit| {
+            for i in 0..maxiter {
+                z = formula.eval(z, c);
+                if orbit.visit(i, z, z.norm_sqr() > 4.) {
+                    break;
+                }
+            }
+        });
+    }
+
+    fn plugin(ctx: &mut FractalContext, kernel: &dyn plugin::Kernel, maxiter: usize) {
+        let bailout = kernel.bailout() * kernel.bailout();
+        Self::escape_time(ctx, Seed::Parameter, maxiter, |mut z, c, orbit| {
+            for i in 0..maxiter {
+                z = kernel.step(z, c);
+                if orbit.visit(i, z, z.norm_sqr() > bailout) {
+                    break;
+                }
+            }
+        });
+    }
+
+    fn hybrid(ctx: &mut FractalContext, steps: &[Step], maxiter: usize) {
+        Self::escape_time(ctx, Seed::Parameter, maxiter, |mut z, c, orbit| {
+            for i in 0..maxiter {
+                z = steps[i % steps.len()].apply(z, c);
+                if orbit.visit(i, z, z.norm_sqr() > 4.) {
+                    break;
+                }
+            }
+        });
+    }
+
+    fn lambda(ctx: &mut FractalContext, maxiter: usize) {
+        Self::escape_time(ctx, Seed::Parameter, maxiter, |_, c, orbit| {
+            // z = 0 is a fixed point of the logistic map, so start from the critical point instead
+            let mut z = Complex::new(0.5, 0.);
+
+            for i in 0..maxiter {
+                z = c * z * (1. - z);
+                if orbit.visit(i, z, z.norm_sqr() > 16.) {
+                    break;
+                }
+            }
+        });
+    }
+
+    fn spider(ctx: &mut FractalContext, maxiter: usize) {
+        Self::escape_time(ctx, Seed::Parameter, maxiter, |mut z, mut c, orbit| {
+            for i in 0..maxiter {
+                z = z * z + c;
+                c = c / 2. + z;
+                if orbit.visit(i, z, z.norm_sqr() > 4.) {
+                    break;
+                }
+            }
+        });
+    }
+
+    fn manowar(ctx: &mut FractalContext, maxiter: usize) {
+        Self::escape_time(ctx, Seed::Parameter, maxiter, |_, c, orbit| {
+            let mut z = c;
+            let mut prev = c;
+
+            for i in 0..maxiter {
+                let next = z * z + prev + c;
+                prev = z;
+                z = next;
+                if orbit.visit(i, z, z.norm_sqr() > 4.) {
+                    break;
+                }
+            }
+        });
+    }
+
+    fn collatz(ctx: &mut FractalContext, maxiter: usize) {
+        Self::escape_time(ctx, Seed::Parameter, maxiter, |_, mut z, orbit| {
+            for i in 0..maxiter {
+                // smooth interpolation of n/2 for even n and (3n + 1)/2 for odd n
+                z = (2. + z * 7. - (2. + z * 5.) * (z * std::f64::consts::PI).cos()) / 4.;
+                if orbit.visit(i, z, z.norm_sqr() > 1e4) {
+                    break;
+                }
+            }
+        });
+    }
+
+    fn transcendental(ctx: &mut FractalContext, func: Transcendental, maxiter: usize) {
+        Self::escape_time(ctx, Seed::Parameter, maxiter, |_, c, orbit| {
+            let mut z = func.start();
+
+            for i in 0..maxiter {
+                z = func.apply(z, c);
+                if orbit.visit(i, z, func.escaped(z)) {
+                    break;
+                }
+            }
+        });
+    }
+
+    fn newton(ctx: &mut FractalContext, degree: u32, maxiter: usize) {
+        let n = degree as f64;
+        let roots: Vec<Complex<f64>> = (0..degree)
+            .map(|k| Complex::from_polar(1., 2. * std::f64::consts::PI * k as f64 / n))
+            .collect();
+
+        Self::for_each_point(ctx, |mut z| {
+            for i in 0..maxiter {
+                // Newton step for p(z) = z^n - 1
+                z -= (z.powu(degree) - 1.) / (n * z.powu(degree - 1));
+
+                if let Some(k) = roots.iter().position(|root| (z - root).norm_sqr() < 1e-6) {
+                    let shade = 1. - (i as f64 / maxiter as f64).sqrt();
+                    let [r, g, b] = coloring::hsv(k as f64 / n, 1., shade);
+
+                    return coloring::rgb(r, g, b);
+                }
+            }
+            0
+        });
+    }
+
+    fn magnet(ctx: &mut FractalContext, kind: u8, maxiter: usize) {
+        Self::for_each_point(ctx, |c| {
+            let mut z = Complex::new(0., 0.);
+            let one = Complex::new(1., 0.);
+
+            for i in 0..maxiter {
+                let q = if kind == 1 {
+                    (z * z + c - 1.) / (z * 2. + c - 2.)
+                } else {
+                    let (a, b) = (c - 1., c - 2.);
+                    (z * z * z + z * a * 3. + a * b) / (z * z * 3. + z * b * 3. + a * b + 1.)
+                };
+                z = q * q;
+
+                let shade = (1. - i as f64 / maxiter as f64).sqrt();
+                if z.norm_sqr() > 100. {
+                    return coloring::rgb(shade, shade, shade);
+                }
+                if (z - one).norm_sqr() < 1e-6 {
+                    return coloring::rgb(shade * 0.2, shade * 0.5, shade);
+                }
+            }
+            0
+        });
+    }
+
+    fn nova(ctx: &mut FractalContext, relaxation: f64, power: f64, maxiter: usize) {
+        Self::for_each_point(ctx, |c| {
+            // z = 1 is a critical point of the Newton map for z^p - 1
+            let mut z = Complex::new(1., 0.);
+
+            for i in 0..maxiter {
+                let prev = z;
+                z -= (z.powf(power) - 1.) / (z.powf(power - 1.) * power) * relaxation;
+                z += c;
+
+                let shade = (1. - i as f64 / maxiter as f64).sqrt();
+                if z.norm_sqr() > 100. {
+                    return coloring::rgb(shade, shade, shade);
+                }
+                if (z - prev).norm_sqr() < 1e-10 {
+                    return coloring::rgb(shade, shade * 0.6, shade * 0.2);
+                }
+            }
+            0
+        });
+    }
+
+    fn phoenix(ctx: &mut FractalContext, p: f64, maxiter: usize) {
+        Self::escape_time(ctx, Seed::Parameter, maxiter, |mut z, c, orbit| {
+            let mut prev = Complex::new(0., 0.);
+
+            for i in 0..maxiter {
+                let next = z * z + c + prev * p;
+                prev = z;
+                z = next;
+                if orbit.visit(i, z, z.norm_sqr() > 4.) {
+                    break;
+                }
+            }
+        });
+    }
+}
+
+/// The period of the Mandelbrot set's main cardioid (1) or the bulb left of it (2)
+/// containing `c`, or 0 if it's in neither.
+pub fn bulb_period(c: Complex<f64>) -> usize {
+    let x = c.re - 0.25;
+    let q = x * x + c.im * c.im;
+    if q * (q + x) <= 0.25 * c.im * c.im {
+        1
+    } else if (c.re + 1.).powi(2) + c.im * c.im <= 1. / 16. {
+        2
+    } else {
+        0
+    }
+}
+
+/// The time now in UTC as `YYYYMMDD-HHMMSS`, for naming files that shouldn't overwrite
+/// earlier ones.
+pub fn timestamp() -> String {
+    let seconds = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |time| time.as_secs());
+    let (days, time) = ((seconds / 86400) as i64, seconds % 86400);
+    // civil date from days since 1970, after Howard Hinnant's days_from_civil inverse
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    format!("{:04}{:02}{:02}-{:02}{:02}{:02}", year, month, day, time / 3600, time / 60 % 60, time % 60)
+}
+
+pub fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    let len = args.len();
+    args.retain(|arg| arg != flag);
+    args.len() != len
+}
+
+pub fn take_option(args: &mut Vec<String>, flag: &str) -> Option<String> {
+    let pos = args.iter().position(|arg| arg == flag)?;
+    args.remove(pos);
+
+    // a trailing flag with no value yields an empty one so callers can reject it
+    Some(if pos < args.len() { args.remove(pos) } else { String::new() })
+}
+
+/// Like `take_option`, for flags followed by `count` values.
+pub fn take_values(args: &mut Vec<String>, flag: &str, count: usize) -> Option<Vec<String>> {
+    let pos = args.iter().position(|arg| arg == flag)?;
+    args.remove(pos);
+    Some((0..count).map(|_| if pos < args.len() { args.remove(pos) } else { String::new() }).collect())
+}
+
+/// Reads a `<width>x<height>` size in pixels.
+pub fn parse_size(value: &str) -> Option<(usize, usize)> {
+    let (width, height) = value.split_once('x')?;
+    Some((width.parse().ok().filter(|width| *width > 0)?, height.parse().ok().filter(|height| *height > 0)?))
+}
+
+pub struct Options {
+    pub coloring: Coloring,
+    pub brightness: Option<Coloring>,
+    pub interior: Interior,
+    pub slope: bool,
+    pub light: (f64, f64),
+    pub gamma: f64,
+    pub exposure: f64,
+    pub dither: bool,
+    pub simd: bool,
+    pub tracing: bool,
+    pub bulbs: bool,
+    pub iteration_curve: f64,
+    pub adaptive: bool,
+    pub preview: bool,
+    pub crosshair_size: usize,
+    pub crosshair_color: u32,
+    pub screen: (usize, usize),
+    pub center: Option<Center>,
+    pub zoom: Option<f64>,
+    /// In degrees.
+    pub rotation: Option<f64>,
+    /// The view of the state loaded from `state_path`, which the other options move from.
+    pub view: Option<State>,
+    pub state_path: Option<String>,
+    /// A view from another program's file, which the other options move from too.
+    pub location: Option<Location>,
+    pub gpu: bool,
+    pub opencl: bool,
+    pub trap: Option<Trap>,
+    pub palettes: Vec<Palette>,
+    pub palette: usize,
+    pub palette_offset: f64,
+    /// Keyframes to play through.
+    pub tour: Option<Tour>
+}
+
+pub fn parse_options(args: &mut Vec<String>, config: &Config) -> Option<Options> {
+    // a saved state stands in for the options it has that aren't given, and the fractal, as
+    // do the last session's and the one kept in an exported image
+    let resume = take_flag(args, "--resume");
+    let state_path = match take_option(args, "--load-state") {
+        Some(path) => Some(path),
+        None if resume => match bookmarks::session_path() {
+            Some(path) if path.exists() => Some(path.to_string_lossy().into_owned()),
+            _ => {
+                println!("no session saved to resume");
+                return None;
+            }
+        },
+        None => None
+    };
+    let image = take_option(args, "--from-image");
+    let state = match (&state_path, &image) {
+        (Some(path), _) => Some(bookmarks::load_state(Path::new(path)).map_err(|err| println!("{}", err)).ok()?),
+        (None, Some(path)) => Some(bookmarks::load_image_state(Path::new(path)).map_err(|err| println!("{}", err)).ok()?),
+        (None, None) => None
+    };
+    let saved = state.as_ref();
+
+    let mut trap = match take_option(args, "--trap") {
+        Some(spec) => Some(Trap::parse(&spec)?),
+        None => None
+    };
+
+    let coloring = match take_option(args, "--coloring") {
+        Some(name) => Coloring::from_name(&name)?,
+        None if trap.is_some() => Coloring::Trap,
+        None => saved.map_or(Coloring::Banded, |state| state.coloring)
+    };
+    let brightness = match take_option(args, "--brightness") {
+        Some(name) => Some(Coloring::from_name(&name)?),
+        None => saved.and_then(|state| state.brightness)
+    };
+    if (coloring == Coloring::Trap || brightness == Some(Coloring::Trap)) && trap.is_none() {
+        trap = Some(Trap::Point(Complex::new(0., 0.)));
+    }
+
+    let interior = match take_option(args, "--interior") {
+        Some(name) => Interior::from_name(&name)?,
+        None => saved.map_or(Interior::Flat, |state| state.interior)
+    };
+    let slope = take_flag(args, "--slope") || saved.is_some_and(|state| state.slope);
+    let light = match take_option(args, "--light") {
+        Some(spec) => {
+            let (azimuth, elevation) = spec.split_once(',')?;
+            (azimuth.parse().ok()?, elevation.parse().ok()?)
+        }
+        None => coloring::LIGHT
+    };
+    let dither = take_flag(args, "--dither") || saved.is_some_and(|state| state.dither);
+    let simd = !take_flag(args, "--no-simd");
+    let tracing = !take_flag(args, "--no-tracing");
+    let bulbs = !take_flag(args, "--no-bulbs");
+    let iteration_curve = match take_option(args, "--iteration-curve") {
+        Some(value) => value.parse::<f64>().ok().filter(|exponent| *exponent >= 0.)?,
+        None => saved.and_then(|state| state.iteration_curve).unwrap_or(ITERATION_CURVE)
+    };
+    let adaptive = !take_flag(args, "--fixed-iterations") && saved.is_none_or(|state| state.iteration_curve.is_some());
+    let preview = !take_flag(args, "--no-preview");
+    let gpu = take_flag(args, "--gpu");
+    let opencl = take_flag(args, "--opencl");
+    let gamma = match take_option(args, "--gamma") {
+        Some(value) => value.parse::<f64>().ok().filter(|gamma| *gamma > 0.)?,
+        None => saved.map_or(coloring::GAMMA, |state| state.gamma)
+    };
+    let exposure = match take_option(args, "--exposure") {
+        Some(value) => value.parse().ok()?,
+        None => saved.map_or(0., |state| state.exposure)
+    };
+    let crosshair_size = match take_option(args, "--crosshair-size") {
+        Some(value) => value.parse().ok()?,
+        None => CROSSHAIR_SIZE
+    };
+    let crosshair_color = match take_option(args, "--crosshair-color") {
+        Some(value) => u32::from_str_radix(value.trim_start_matches('#'), 16).ok().filter(|color| *color <= 0xFFFFFF)?,
+        None => 0xFF0000
+    };
+    let screen = match take_option(args, "--screen") {
+        Some(value) => parse_size(&value)?,
+        None => SCREEN
+    };
+
+    let center = match take_values(args, "--center", 2) {
+        Some(values) => Some(Center::parse(&values[0], &values[1])?),
+        None => None
+    };
+    let zoom = match take_option(args, "--zoom") {
+        Some(value) => Some(goto::parse_zoom(&value)?),
+        None => None
+    };
+    let location = match take_option(args, "--location") {
+        Some(path) => Some(location::load(&path).map_err(|err| println!("{}", err)).ok()?),
+        None => None
+    };
+    let rotation = match take_option(args, "--rotate") {
+        Some(value) => Some(value.parse::<f64>().ok().filter(|degrees| degrees.is_finite())?),
+        None => None
+    };
+
+    let accessible = take_flag(args, "--accessible");
+    let mut palettes = if accessible { Palette::accessible() } else { Palette::builtin() };
+    // the state's palette only comes back if it's one of the viewer's or its file's still there
+    let saved_palette = saved.map(|state| state.palette.clone()).filter(|name| {
+        palettes.iter().any(|palette| palette.key() == *name) || (!accessible && Path::new(name).is_file())
+    });
+    let palette = match take_option(args, "--palette").or(saved_palette).or_else(|| config.palette.clone()) {
+        Some(name) => match palettes.iter().position(|palette| palette.key() == name) {
+            Some(index) => index,
+            None if accessible => {
+                println!("{} is not one of the accessible palettes", name);
+                return None;
+            }
+            None => palette_file::find(&mut palettes, &name).map_err(|err| println!("{}", err)).ok()?
+        },
+        None => 0
+    };
+    let palette_offset = saved.map_or(0., |state| state.palette_offset);
+    let view = saved.map(|state| state.view.clone());
+    // the location's or the state's fractal unless there's another on the command line, or
+    // else the config's
+    if let (Some(location), 1) = (&location, args.len()) {
+        args.extend(location.fractal.iter().cloned());
+    }
+    if let (Some(view), 1) = (&view, args.len()) {
+        args.extend(view.fractal[1..].iter().cloned());
+    }
+    if let (Some(fractal), 1) = (&config.fractal, args.len()) {
+        args.extend(fractal.iter().cloned());
+    }
+    let tour = match take_option(args, "--keyframes") {
+        Some(path) => Some(Tour::load(&path, &mut palettes).map_err(|err| println!("{}", err)).ok()?),
+        None => None
+    };
+
+    Some(Options {
+        coloring, brightness, interior, slope, light, gamma, exposure, dither, simd, tracing, bulbs, iteration_curve,
+        adaptive, preview, crosshair_size, crosshair_color, screen, center, zoom, rotation, view, state_path, location, gpu, opencl, trap,
+        palettes, palette, palette_offset, tour
+    })
+}
+
+pub fn parse_fractal(args: &[String]) -> Option<Fractal> {
+    let name = args.get(1)?;
+    // `--iterations <n>` anywhere after the name can stand in for the iterations, or points
+    let mut rest = args[2..].to_vec();
+    let named = take_option(&mut rest, "--iterations");
+    let rest = &rest[..];
+
+    let count = |n: &String| n.parse::<usize>().ok().filter(|n| *n > 0);
+    let iterations = |arg: Option<&String>| match (arg, &named) {
+        (Some(_), Some(_)) => None,
+        (Some(n), None) | (None, Some(n)) => count(n),
+        (None, None) => Some(30)
+    };
+
+    match name.as_str() {
+        "mandelbrot" if rest.len() <= 1 => Some(Fractal::Mandelbrot(iterations(rest.first())?)),
+        "burning-ship" if rest.len() <= 1 => Some(Fractal::BurningShip(iterations(rest.first())?)),
+        "tricorn" if rest.len() <= 1 => Some(Fractal::Tricorn(iterations(rest.first())?)),
+        "multibrot" if rest.len() == 1 || rest.len() == 2 => Some(Fractal::Multibrot {
+            power: rest[0].parse().ok()?,
+            maxiter: iterations(rest.get(1))?
+        }),
+        "newton" if rest.len() <= 2 => Some(Fractal::Newton {
+            degree: match rest.first() {
+                Some(degree) => degree.parse().ok().filter(|&d| d > 0)?,
+                None => 3
+            },
+            maxiter: iterations(rest.get(1))?
+        }),
+        "phoenix" if rest.len() == 1 || rest.len() == 2 => Some(Fractal::Phoenix {
+            p: rest[0].parse().ok()?,
+            maxiter: iterations(rest.get(1))?
+        }),
+        "celtic" if rest.len() <= 1 => Some(Fractal::Celtic(iterations(rest.first())?)),
+        "buffalo" if rest.len() <= 1 => Some(Fractal::Buffalo(iterations(rest.first())?)),
+        "perpendicular-burning-ship" if rest.len() <= 1 => Some(Fractal::PerpendicularBurningShip(iterations(rest.first())?)),
+        "magnet" if rest.len() == 1 || rest.len() == 2 => Some(Fractal::Magnet {
+            kind: rest[0].parse().ok().filter(|&k| k == 1 || k == 2)?,
+            maxiter: iterations(rest.get(1))?
+        }),
+        "nova" if rest.len() <= 3 => Some(Fractal::Nova {
+            relaxation: rest.first().map_or(Some(1.), |r| r.parse().ok())?,
+            power: rest.get(1).map_or(Some(3.), |p| p.parse().ok())?,
+            maxiter: iterations(rest.get(2))?
+        }),
+        "custom" if rest.len() == 1 || rest.len() == 2 => {
+            let formula = Formula::parse(&rest[0]).map_err(|err| println!("{}", err)).ok()?;
+            Some(Fractal::Custom(formula, iterations(rest.get(1))?))
+        }
+        "hybrid" if rest.len() == 1 || rest.len() == 2 => {
+            let steps = rest[0].chars().map(Step::from_letter).collect::<Option<Vec<_>>>()?;
+            if steps.is_empty() {
+                return None;
+            }
+            Some(Fractal::Hybrid(steps, iterations(rest.get(1))?))
+        }
+        "lambda" if rest.len() <= 1 => Some(Fractal::Lambda(iterations(rest.first())?)),
+        "spider" if rest.len() <= 1 => Some(Fractal::Spider(iterations(rest.first())?)),
+        "manowar" if rest.len() <= 1 => Some(Fractal::Manowar(iterations(rest.first())?)),
+        "collatz" if rest.len() <= 1 => Some(Fractal::Collatz(iterations(rest.first())?)),
+        "exp" if rest.len() <= 1 => Some(Fractal::Transcendental(Transcendental::Exp, iterations(rest.first())?)),
+        "sin" if rest.len() <= 1 => Some(Fractal::Transcendental(Transcendental::Sin, iterations(rest.first())?)),
+        "cos" if rest.len() <= 1 => Some(Fractal::Transcendental(Transcendental::Cos, iterations(rest.first())?)),
+        "clifford" | "de-jong" if matches!(rest.len(), 0 | 1 | 4 | 5) => {
+            let map = if name == "clifford" { attractor::Map::Clifford } else { attractor::Map::DeJong };
+            let params = if rest.len() >= 4 {
+                [rest[0].parse().ok()?, rest[1].parse().ok()?, rest[2].parse().ok()?, rest[3].parse().ok()?]
+            } else {
+                map.default_params()
+            };
+            let points = match (rest.len(), &named) {
+                (1 | 5, _) => iterations(rest.last())?,
+                (_, Some(n)) => count(n)?,
+                _ => 2_000_000
+            };
+            Some(Fractal::Attractor { map, params, points })
+        }
+        "ifs" if rest.len() == 1 || rest.len() == 2 => {
+            let system = match rest[0].as_str() {
+                "fern" => ifs::System::fern(),
+                "sierpinski" => ifs::System::sierpinski(),
+                path => ifs::System::load(path).map_err(|err| println!("{}", err)).ok()?
+            };
+            let points = match (rest.get(1), &named) {
+                (None, None) => 2_000_000,
+                (points, _) => iterations(points)?
+            };
+            Some(Fractal::Ifs(system, points))
+        }
+        "julia" if rest.len() == 2 || rest.len() == 3 => {
+            let c = Complex::new(rest[0].parse().ok()?, rest[1].parse().ok()?);
+            Some(Fractal::Julia(c, iterations(rest.get(2))?))
+        }
+        "burning-ship-julia" if rest.len() == 2 || rest.len() == 3 => {
+            let c = Complex::new(rest[0].parse().ok()?, rest[1].parse().ok()?);
+            Some(Fractal::BurningShipJulia(c, iterations(rest.get(2))?))
+        }
+        _ if rest.len() <= 1 => Some(Fractal::Plugin(plugin::find(name)?, iterations(rest.first())?)),
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use super::{double, renderer, Center, Fractal, FractalContext, Renderer, Seed};
+    use crate::orbit::Sample;
+
+    /// A 64x48 view of the whole Mandelbrot set, 16 pixels to a unit.
+    fn view() -> FractalContext {
+        let mut ctx = FractalContext::new();
+        ctx.dimensions = (64, 48);
+        ctx.pan = Center::new((0., 0.));
+        ctx.scale = 16.;
+        ctx
+    }
+
+    /// `view` of `fract` rendered to the end.
+    fn render(fract: &Fractal, tracing: bool) -> FractalContext {
+        let mut ctx = view();
+        ctx.tracing = tracing;
+        fract.render_all(&mut ctx);
+        ctx
+    }
+
+    fn escapes(ctx: &FractalContext) -> Vec<Option<usize>> {
+        ctx.samples.iter().map(|sample| sample.escape.map(|(i, _)| i)).collect()
+    }
+
+    /// A device that renders whole frames at once, but never manages to.
+    struct Declining;
+
+    impl Renderer for Declining {
+        fn name(&self) -> &'static str {
+            "declining"
+        }
+
+        fn progressive(&self) -> bool {
+            false
+        }
+
+        fn quadratic(&self, _: &FractalContext, _: Seed, _: usize, _: &[usize]) -> Option<Vec<Sample>> {
+            None
+        }
+    }
+
+    /// Index of the pixel at `(re, im)` in `render`'s view.
+    fn pixel(re: f64, im: f64) -> usize {
+        (32. + re * 16.) as usize + (24. + im * 16.) as usize * 64
+    }
+
+    #[test]
+    fn renders_the_mandelbrot_set() {
+        let ctx = render(&Fractal::Mandelbrot(200), true);
+        assert_eq!(ctx.samples.len(), 64 * 48);
+        // the main cardioid, the period 2 bulb and the period 3 bulb, which is iterated
+        for (re, im) in [(0., 0.), (-1., 0.), (-0.125, 0.75)] {
+            assert!(ctx.samples[pixel(re, im)].escape.is_none(), "{} {} escaped", re, im);
+        }
+        // 0, 1, 2, then 5 is past the bailout on the third iteration
+        assert_eq!(ctx.samples[pixel(1., 0.)].escape.map(|(i, _)| i), Some(2));
+        assert_eq!(ctx.samples[pixel(-1.75, -1.25)].escape.map(|(i, _)| i), Some(0));
+    }
+
+    #[test]
+    fn tracing_fills_what_iterating_finds() {
+        for fract in [Fractal::Mandelbrot(200), Fractal::BurningShip(200), Fractal::Lambda(200), Fractal::Multibrot { power: 3., maxiter: 200 }] {
+            assert!(escapes(&render(&fract, true)) == escapes(&render(&fract, false)));
+        }
+
+        // a view of z^4.7 + c where tracing would fill over pixels that escape
+        let fract = Fractal::Multibrot { power: 4.7, maxiter: 100 };
+        let near = |tracing| {
+            let mut ctx = view();
+            ctx.dimensions = (128, 96);
+            ctx.pan = Center::new((-0.7861331114533274, 0.2526802100084917));
+            ctx.scale = 1116.0580905259878;
+            ctx.tracing = tracing;
+            fract.render_all(&mut ctx);
+            escapes(&ctx)
+        };
+        assert!(near(true) == near(false));
+    }
+
+    /// A device that renders whole frames at once until it fails, as one can at any time.
+    struct Failing(Arc<AtomicBool>);
+
+    impl Renderer for Failing {
+        fn name(&self) -> &'static str {
+            "failing"
+        }
+
+        fn progressive(&self) -> bool {
+            false
+        }
+
+        fn quadratic(&self, ctx: &FractalContext, seed: Seed, maxiter: usize, pixels: &[usize]) -> Option<Vec<Sample>> {
+            (!self.0.load(Ordering::Relaxed)).then(|| double::quadratic(ctx, seed, maxiter, pixels))
+        }
+    }
+
+    /// `view` of `fract` at `pan` rendered on the scalar CPU kernels.
+    fn cpu(fract: &Fractal, pan: (f64, f64)) -> FractalContext {
+        let mut ctx = view();
+        ctx.pan = Center::new(pan);
+        ctx.renderer = Box::new(renderer::Cpu { simd: false });
+        fract.render_all(&mut ctx);
+        ctx
+    }
+
+    #[test]
+    fn failed_device_renders_are_left_to_the_cpu() {
+        let fract = Fractal::Mandelbrot(200);
+        let failed = Arc::new(AtomicBool::new(false));
+        let mut ctx = view();
+        ctx.renderer = Box::new(Failing(failed.clone()));
+        fract.render_all(&mut ctx);
+        assert!(escapes(&ctx) == escapes(&cpu(&fract, (0., 0.))));
+
+        // the next view, after the device had a whole frame to itself
+        failed.store(true, Ordering::Relaxed);
+        ctx.pan = Center::new((-0.5, 0.25));
+        ctx.updated = true;
+        fract.render_all(&mut ctx);
+        assert!(escapes(&ctx) == escapes(&cpu(&fract, (-0.5, 0.25))));
+    }
+
+    #[test]
+    fn declined_devices_leave_the_whole_view_to_the_cpu() {
+        let fract = Fractal::Mandelbrot(200);
+        let mut ctx = view();
+        ctx.renderer = Box::new(Declining);
+        fract.render_all(&mut ctx);
+        // the scalar kernel it falls back to, as the vector one iterates in f32 out here
+        assert!(escapes(&ctx) == escapes(&cpu(&fract, (0., 0.))));
+    }
+}
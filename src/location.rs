@@ -0,0 +1,181 @@
+use crate::fixed::Center;
+use crate::HEIGHT;
+
+/// A view to start at read from another program's parameter file, given with `--location`.
+pub struct Location {
+    pub center: Center,
+    /// Scale of the view at the window's initial size.
+    pub scale: f64,
+    /// The fractal and its arguments, as they'd be given on the command line, with the
+    /// iteration limit the file has for this view.
+    pub fractal: Vec<String>
+}
+
+pub fn load(path: &str) -> Result<Location, String> {
+    let source = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+    if path.to_ascii_lowercase().ends_with(".upr") {
+        parse_upr(&source).map_err(|err| format!("{}: {}", path, err))
+    } else {
+        parse_kfr(&source).map_err(|err| format!("{}: {}", path, err))
+    }
+}
+
+/// Kalles Fraktaler `.kfr` files are `Key: value` lines, the coordinates written out to as
+/// many digits as the zoom needs.
+fn parse_kfr(source: &str) -> Result<Location, String> {
+    let get = |key: &str| {
+        source
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .find(|(name, _)| name.trim() == key)
+            .map(|(_, value)| value.trim())
+    };
+    let required = |key: &str| get(key).ok_or(format!("missing {}", key));
+
+    let center = Center::parse(required("Re")?, required("Im")?).ok_or("bad center")?;
+    let zoom = required("Zoom")?;
+    // the zoom is 2 over the distance from the center to the top of the view
+    let scale = match zoom.parse::<f64>() {
+        Ok(value) if value.is_infinite() => return Err(format!("zoom {} is too deep", zoom)),
+        Ok(value) if value > 0. => HEIGHT as f64 * value / 4.,
+        _ => return Err(format!("bad zoom {}", zoom))
+    };
+    let iterations = required("Iterations")?;
+    let iterations = iterations.parse::<usize>().ok().filter(|n| *n > 0).ok_or(format!("bad iterations {}", iterations))?;
+
+    let power = match get("Power") {
+        Some(power) => power.parse::<u32>().ok().filter(|power| *power >= 2).ok_or(format!("bad power {}", power))?,
+        None => 2
+    };
+    let mut fractal: Vec<String> = match (get("FractalType").unwrap_or("0"), power) {
+        ("0", 2) => vec!["mandelbrot".into()],
+        ("0", power) => vec!["multibrot".into(), power.to_string()],
+        ("1", 2) => vec!["burning-ship".into()],
+        (kind, power) => return Err(format!("fractal type {} at power {} isn't one fractalv has", kind, power))
+    };
+    fractal.extend(["--iterations".to_string(), iterations.to_string()]);
+    Ok(Location { center, scale, fractal })
+}
+
+/// UltraFractal `.upr` files hold entries of `key=value` settings in sections, only the first
+/// of which is read, and only for the location and iterations of the standard Mandelbrot and
+/// Julia formulas. It's taken that magnification 1 is as tall as a Kalles Fraktaler zoom of 1.
+fn parse_upr(source: &str) -> Result<Location, String> {
+    let entry = source.split_once("\n}").map_or(source, |(entry, _)| entry);
+    let get = |key: &str| {
+        entry
+            .split_whitespace()
+            .filter_map(|setting| setting.split_once('='))
+            .find(|(name, _)| *name == key)
+            .map(|(_, value)| value.trim_matches('"'))
+    };
+    let required = |key: &str| get(key).ok_or(format!("missing {}", key));
+    // complex numbers are written `re/im`
+    let complex = |key: &str| required(key).and_then(|value| value.split_once('/').ok_or(format!("bad {} {}", key, value)));
+
+    let (re, im) = complex("center")?;
+    let center = Center::parse(re, im).ok_or("bad center")?;
+    let magn = required("magn")?;
+    let scale = match magn.parse::<f64>() {
+        Ok(value) if value.is_infinite() => return Err(format!("magnification {} is too deep", magn)),
+        Ok(value) if value > 0. => HEIGHT as f64 * value / 4.,
+        _ => return Err(format!("bad magnification {}", magn))
+    };
+    let iterations = match get("maxiter") {
+        Some(maxiter) => maxiter.parse::<usize>().ok().filter(|n| *n > 0).ok_or(format!("bad maxiter {}", maxiter))?,
+        None => 100
+    };
+
+    let formula = required("entry")?;
+    let mut fractal: Vec<String> = match formula.to_ascii_lowercase() {
+        name if name.contains("julia") => {
+            let (re, im) = complex("p_seed")?;
+            vec!["julia".into(), re.into(), im.into()]
+        }
+        name if name.contains("mandel") => vec!["mandelbrot".into()],
+        _ => return Err(format!("formula {} isn't one fractalv has", formula))
+    };
+    fractal.extend(["--iterations".to_string(), iterations.to_string()]);
+    Ok(Location { center, scale, fractal })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_kfr, parse_upr};
+    use crate::HEIGHT;
+
+    fn kfr(fractal: &str) -> String {
+        format!("Re: -0.75\r\nIm: 0.1\r\nZoom: 8\r\nIterations: 300\r\n{}", fractal)
+    }
+
+    #[test]
+    fn reads_kfr_locations() {
+        let location = parse_kfr(&kfr("")).unwrap();
+        assert_eq!(location.center.approx(), (-0.75, 0.1));
+        assert_eq!(location.scale, HEIGHT as f64 * 8. / 4.);
+        assert_eq!(location.fractal, ["mandelbrot", "--iterations", "300"]);
+
+        let fractal = |extra: &str| parse_kfr(&kfr(extra)).map(|location| location.fractal.join(" "));
+        assert_eq!(fractal("FractalType: 0\r\nPower: 3\r\n"), Ok("multibrot 3 --iterations 300".to_string()));
+        assert_eq!(fractal("FractalType: 1\r\nPower: 2\r\n"), Ok("burning-ship --iterations 300".to_string()));
+        assert_eq!(fractal("FractalType: 1\r\nPower: 3\r\n"), Err("fractal type 1 at power 3 isn't one fractalv has".to_string()));
+        assert_eq!(fractal("FractalType: 7\r\n"), Err("fractal type 7 at power 2 isn't one fractalv has".to_string()));
+        assert_eq!(fractal("Power: 1\r\n"), Err("bad power 1".to_string()));
+    }
+
+    #[test]
+    fn rejects_bad_kfr_views() {
+        let error = |source: &str| parse_kfr(source).err();
+        assert_eq!(error("Re: 0\nIm: 0\nIterations: 10"), Some("missing Zoom".to_string()));
+        assert_eq!(error("Re: 0\nIm: 0\nZoom: 0\nIterations: 10"), Some("bad zoom 0".to_string()));
+        assert_eq!(error("Re: 0\nIm: 0\nZoom: 1E400\nIterations: 10"), Some("zoom 1E400 is too deep".to_string()));
+        assert_eq!(error("Re: 0\nIm: 0\nZoom: 1\nIterations: 0"), Some("bad iterations 0".to_string()));
+    }
+    fn upr(formula: &str) -> String {
+        let lines = [
+            "Spot {",
+            "fractal:",
+            "  title=\"Spot\" width=640 height=480 layers=1",
+            "layer:",
+            "  caption=\"Background\" opacity=100",
+            "mapping:",
+            "  center=-0.75/0.1 magn=8",
+            "formula:",
+            formula,
+            "inside:",
+            "  transfer=none",
+            "}",
+            "Other {",
+            "formula:",
+            "  maxiter=5 filename=\"Standard.ufm\" entry=\"Newton\"",
+            "}"
+        ];
+        lines.join("\r\n")
+    }
+
+    #[test]
+    fn reads_upr_locations() {
+        let location = parse_upr(&upr("  maxiter=250 filename=\"Standard.ufm\" entry=\"Mandelbrot\"")).unwrap();
+        assert_eq!(location.center.approx(), (-0.75, 0.1));
+        assert_eq!(location.scale, HEIGHT as f64 * 8. / 4.);
+        assert_eq!(location.fractal, ["mandelbrot", "--iterations", "250"]);
+
+        let fractal = |formula: &str| parse_upr(&upr(formula)).map(|location| location.fractal.join(" "));
+        assert_eq!(
+            fractal("  filename=\"Standard.ufm\" entry=\"JuliaSet\" p_seed=-0.8/0.156"),
+            Ok("julia -0.8 0.156 --iterations 100".to_string())
+        );
+        assert_eq!(fractal("  entry=\"Julia\""), Err("missing p_seed".to_string()));
+        assert_eq!(fractal("  maxiter=5 entry=\"Newton\""), Err("formula Newton isn't one fractalv has".to_string()));
+        assert_eq!(fractal("  maxiter=0 entry=\"Mandelbrot\""), Err("bad maxiter 0".to_string()));
+    }
+
+    #[test]
+    fn rejects_bad_upr_views() {
+        let error = |mapping: &str| parse_upr(&format!("Spot {{\nmapping:\n  {}\nformula:\n  entry=\"Mandelbrot\"\n}}", mapping)).err();
+        assert_eq!(error("magn=1"), Some("missing center".to_string()));
+        assert_eq!(error("center=0.5 magn=1"), Some("bad center 0.5".to_string()));
+        assert_eq!(error("center=0/0 magn=-1"), Some("bad magnification -1".to_string()));
+        assert_eq!(error("center=0/0 magn=1E400"), Some("magnification 1E400 is too deep".to_string()));
+    }
+}
@@ -1,199 +1,384 @@
 extern crate minifb;
-extern crate rayon;
-extern crate num_complex;
-
-use minifb::{Key, Window, WindowOptions, ScaleMode};
-use rayon::prelude::*;
-use num_complex::Complex;
-
-const WIDTH: usize = 640;
-const HEIGHT: usize = 360;
-
-struct FractalContext {
-    dimensions: (usize, usize),
-    pan: (f64, f64),
-    scale: f64,
-    updated: bool,
-    pixels: Vec<u32>
-}
 
-impl FractalContext {
-    fn new() -> FractalContext {
-        FractalContext {
-            dimensions: (WIDTH, HEIGHT),
-            pan: (0.0, 0.0),
-            scale: 100.,
-            updated: true,
-            pixels: vec![0; WIDTH * HEIGHT]
-        }
-    }
-}
+mod clipboard;
+mod commands;
+mod crosshair;
+mod editor;
+mod glide;
+mod help;
+mod mouse;
+mod selection;
+mod split;
+mod viewer;
 
-enum Fractal {
-    Mandelbrot(usize),
-    BurningShip(usize)
-}
+use std::sync::mpsc;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::{Duration, Instant};
 
-impl Fractal {
-    fn render(&self, ctx: &mut FractalContext) {
-        ctx.pixels.resize(ctx.dimensions.0 * ctx.dimensions.1, 0);
-        
-        match self {
-            Fractal::Mandelbrot(max) => {
-                Self::mandelbrot(ctx, *max);
-            }
-            Fractal::BurningShip(max) => {
-                Self::burning_ship(ctx, *max);
-            }
-        }
+use minifb::{Window, WindowOptions, ScaleMode};
+use fractv::bookmarks::Bookmarks;
+use fractv::config::Config;
+use fractv::goto::Goto;
+use fractv::keys::{Action, Bindings};
+use fractv::worker::{self, Frame, Request};
+use fractv::{batch, bench, export, plugin};
+use fractv::{parse_fractal, parse_options, parse_size, take_flag, take_option, FractalContext};
+use commands::Command;
+use glide::Glide;
+use mouse::Mouse;
+use viewer::{save_frame, Viewer};
 
-        ctx.pixels[(ctx.dimensions.0 / 2)+(ctx.dimensions.1 / 2)*ctx.dimensions.0] = 0xFF0000;
+fn usage(program: &str) {
+    println!("Usage: {} [options] <fractal> [iterations]", program);
+    println!("       {} julia <re> <im> [iterations]", program);
+    println!("       {} burning-ship-julia <re> <im> [iterations]", program);
+    println!("       {} custom \"<formula>\" [iterations]", program);
+    println!("       {} hybrid <pattern> [iterations]", program);
+    println!("       {} <clifford|de-jong> [<a> <b> <c> <d>] [points]", program);
+    println!("       {} ifs <fern|sierpinski|file> [points]", program);
+    println!("       {} multibrot <power> [iterations]", program);
+    println!("       {} newton [degree] [iterations]", program);
+    println!("       {} phoenix <p> [iterations]", program);
+    println!("       {} magnet <1|2> [iterations]", program);
+    println!("       {} nova [relaxation] [power] [iterations]", program);
+    println!("       {} render [options] <fractal> ... --size <width>x<height> --output <file.png>", program);
+    println!("       {} animate [options] <fractal> ... --from <re> <im> <zoom> --frames <n> --output <prefix>", program);
+    println!("         zoom from the initial view or --from to --center, --zoom and --rotate, as <prefix>00000.png and on,");
+    println!("         or with --keyframes play the tour at --fps <rate> frames a second (default 30)");
+    println!("         --cycle  go round the palette once over the frames of the view instead");
+    println!("         --output <file.gif>  write the frames to a looping GIF, at --fps");
+    println!("         --output <file.exr>  write the distance estimate in pixels and the smooth iteration count");
+    println!("                              (-1 inside) as 32-bit floats instead of the colors");
+    println!("         --output <file.npy|file.csv>  write the iteration each point escaped at (-1 inside), for analysis;");
+    println!("                                       --modulus adds |z| when it escaped, or at the last iteration inside");
+    println!("         --strip <rows>  render that many rows at a time, as images over 4096x4096 are by default;");
+    println!("                         histogram coloring and attractors are then scaled strip by strip");
+    println!("         --checkpoint  keep each strip of a render as it's finished, in <file>.partial and <file>.job,");
+    println!("                       so the same command carries on from there if it's stopped");
+    println!("       {} batch <jobs.toml> [options] [fractal]  render each [[job]] in the file one after the other,", program);
+    println!("         or all at once with --parallel; each key = value in one is its --key value option to render,");
+    println!("         with a list for options of several values, true for ones of none, and fractal for the fractal");
+    println!("       {} bench [options]  time a fixed set of views", program);
+    println!("       {} help  show this", program);
+    println!("Hybrid patterns are sequences of M (mandelbrot), B (burning-ship), T (tricorn),");
+    println!("C (celtic), F (buffalo) and P (perpendicular-burning-ship), e.g. MMB");
+    println!("Options: --iterations <n>  the iteration limit, or points for attractors, instead of after the fractal");
+    println!("         --size <width>x<height>  size of the window to begin with");
+    println!("         --threads <n>  render on that many threads instead of one for each core");
+    println!("         --coloring <banded|smooth|histogram|trap|distance|stripe|tia|stalks|angle|angle-shaded|lighting>");
+    println!("         --brightness <coloring>  second channel driving brightness");
+    println!("         --palette <grayscale|fire|ocean|rainbow|viridis|cividis|magma|file.map|file.ugr>");
+    println!("         --accessible  only offer the colorblind-safe palettes");
+    println!("         --interior <flat|modulus|angle|period>");
+    println!("         --slope  light the image as a height field");
+    println!("         --light <azimuth>,<elevation>  light direction in degrees");
+    println!("         --gamma <gamma> --exposure <stops>");
+    println!("         --dither  dither gradients to hide 8-bit banding");
+    println!("         --no-simd  always use the scalar kernels");
+    println!("         --no-tracing  iterate every pixel instead of filling traced boundaries");
+    println!("         --no-bulbs  iterate the main cardioid and bulb instead of testing for them");
+    println!("         --iteration-curve <exponent>  zooming in n times gives n^exponent times the iterations");
+    println!("         --fixed-iterations  keep the iteration limit at every zoom, A toggles");
+    println!("         --no-preview  render at full resolution while panning and zooming too");
+    println!("         --center <re> <im> --zoom <factor>  start there instead, / types in somewhere to go");
+    println!("         --location <file.kfr|file.upr>  start at a Kalles Fraktaler or UltraFractal location, with its fractal");
+    println!("         and iterations");
+    println!("         --screen <width>x<height>  size of the screen, for F11 fullscreen (default 1920x1080)");
+    println!("         --rotate <degrees>  turn the view, , and . turn it from the keyboard");
+    println!("         --crosshair-size <pixels> --crosshair-color <rrggbb>  center mark, X toggles");
+    println!("         --gpu  render mandelbrot and julia on the GPU when available");
+    println!("         --opencl  the same through OpenCL, for drivers without wgpu support");
+    println!("         --trap <point|cross|ring>[:<re>,<im>[,<radius>]]");
+    println!("         --keyframes <file>  play a tour through the [[keyframe]] tables in the file, T plays it again;");
+    println!("                             each has time in seconds, re, im and zoom, and may have rotation, iterations");
+    println!("                             (a factor), palette and palette_offset");
+    println!("Over mandelbrot or burning-ship, J or a middle click opens the Julia set of the point");
+    println!("B bookmarks the view and V goes through the bookmarks, kept in ~/.config/fractalv/bookmarks.toml");
+    println!("Backspace goes back to the last view, Shift+Backspace forward again");
+    println!("M shows a minimap of where the view is");
+    println!("S splits the view with the Julia set of the point under the cursor on the right");
+    println!("Shift+S saves the view as fractalv-<date>-<time>.png");
+    println!("Shift+C copies the center and zoom to the clipboard as <re> <im> <zoom>, the way / takes them");
+    println!("Shift+L prints the command line that opens the view, with its iteration limit, and --print-location");
+    println!("prints it on closing");
+    println!("F1 or ? lists the keys and settings");
+    println!("F5 saves the whole state to --save-state <file>, or fractalv-<date>-<time>.toml, and F9 loads it back;");
+    println!("--save-state also saves it on closing, and --load-state <file> starts from one, other options moving off it;");
+    println!("exported PNGs keep theirs, which --from-image <file.png> starts from the same way, and closing keeps it");
+    println!("in ~/.local/share/fractalv/session.toml for --resume to carry on from");
+    println!("Keys can be rebound in ~/.config/fractalv/keys.toml with lines like zoom-in = \"W\"");
+    println!("--stdin takes a command a line: set center <re> <im>, set zoom <factor>, set rotation <degrees>,");
+    println!("set palette <name>, set fractal <fractal> [args], render <file.png> once the view's finished, quit,");
+    println!("or the name of any action there");
+    println!("Defaults go in ~/.config/fractalv/config.toml: width, height, fractal = \"<fractal> [args]\", iterations,");
+    println!("palette = \"<name or file>\", fps, threads, wheel_zoom (factor a notch) and cycle_speed (palette share a frame)");
+    println!("Available fractals: mandelbrot, burning-ship, julia, tricorn, multibrot, newton, phoenix, celtic, buffalo, perpendicular-burning-ship, magnet, nova, burning-ship-julia, custom, hybrid, lambda, spider, manowar, collatz, exp, sin, cos, clifford, de-jong, ifs");
+    let plugins = plugin::names();
+    let plugins = if plugins.is_empty() { String::new() } else { format!(": {}", plugins.join(", ")) };
+    println!("Shared libraries in ~/.config/fractalv/plugins exporting fractalv_plugin add fractals of their own{}", plugins);
+}
 
-        ctx.updated = false;
+/// A window for the viewer, covering the screen from its top left corner if `fullscreen`.
+fn open_window(title: &str, (width, height): (usize, usize), fullscreen: bool, frame_time: Duration) -> Window {
+    let mut window = Window::new(
+        title,
+        width,
+        height,
+        WindowOptions {
+            borderless: fullscreen,
+            title: !fullscreen,
+            resize: !fullscreen,
+            topmost: fullscreen,
+            scale_mode: ScaleMode::Stretch,
+            ..WindowOptions::default()
+        },
+    )
+    .expect("failed to create window");
+    if fullscreen {
+        window.set_position(0, 0);
     }
+    window.limit_update_rate(Some(frame_time));
+    window
+}
 
-    fn mandelbrot(ctx: &mut FractalContext, maxiter: usize) {
-        (0..ctx.pixels.len()).into_par_iter().for_each(|i| {
-            let (x, y) = ((i % ctx.dimensions.0) as f64 - (ctx.dimensions.0 as f64 / 2.), (i / ctx.dimensions.0) as f64 - (ctx.dimensions.1 as f64 / 2.));
-            
-            let c = Complex::new(x / ctx.scale + ctx.pan.0, y / ctx.scale + ctx.pan.1);
-            let mut z = Complex::new(0., 0.);
-
-            let mut escaped = 0;
-            for _ in 0..maxiter {
-                z = z * z + c;
-                if z.norm_sqr() > 4. {escaped += 1;}
-            }
-
-            unsafe {
-                let px_ptr = ctx.pixels.as_ptr() as *mut u32;
-
-                *px_ptr.add(i) = ((escaped as f64 / maxiter as f64).sqrt() * 255.) as u32 * 0x010101;
+/// Runs the subcommand `args` start with instead of the viewer, if they do. Returns whether
+/// there was one.
+fn subcommand(args: &mut Vec<String>, config: &Config) -> bool {
+    match args.get(1).map(String::as_str) {
+        Some("render" | "animate") => {
+            let done = match args.remove(1).as_str() {
+                "render" => export::run(args, config),
+                _ => export::animate(args, config)
+            };
+            if !done {
+                usage(&args[0]);
             }
-        });
-    }
-
-    fn burning_ship(ctx: &mut FractalContext, maxiter: usize) {
-        (0..ctx.pixels.len()).into_par_iter().for_each(|i| {
-            let (x, y) = ((i % ctx.dimensions.0) as f64 - (ctx.dimensions.0 as f64 / 2.), (i / ctx.dimensions.0) as f64 - (ctx.dimensions.1 as f64 / 2.));
-            
-            let c: Complex<f64> = Complex::new(x / ctx.scale + ctx.pan.0, y / ctx.scale + ctx.pan.1);
-            let mut z: Complex<f64> = Complex::new(0., 0.);
-
-            let mut escaped = 0;
-            for _ in 0..maxiter {
-                let abs_z = Complex::new(z.re.abs(), z.im.abs());  
-                z = (abs_z * abs_z) + c;
-                if z.norm_sqr() > 4. {escaped += 1;}
+            true
+        }
+        Some("batch") => {
+            args.remove(1);
+            if !batch::run(args, config) {
+                usage(&args[0]);
             }
-
-            unsafe {
-                let px_ptr = ctx.pixels.as_ptr() as *mut u32;
-
-                *px_ptr.add(i) = ((escaped as f64 / maxiter as f64).sqrt() * 255.) as u32 * 0x010101;
+            true
+        }
+        Some("bench") => {
+            match parse_options(args, config) {
+                Some(options) => bench::run(&options),
+                None => usage(&args[0])
             }
-        });
+            true
+        }
+        Some("help" | "--help" | "-h") => {
+            usage(&args[0]);
+            true
+        }
+        _ => false
     }
 }
 
 fn main() {
-    let args: Vec<String> = std::env::args().collect();
-    let fract = match args.len() {
-        1 => {
-            println!("Usage: {} <fractal> [iterations]", args[0]);
-            println!("Available fractals: mandelbrot, burning-ship");
+    let mut args: Vec<String> = std::env::args().collect();
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(err) => {
+            println!("{}", err);
             return;
-        },
-        2 => {
-            match args[1].as_str() {
-                "mandelbrot" => Fractal::Mandelbrot(30),
-                "burning-ship" => Fractal::BurningShip(30),
-                _ => {
-                    println!("Usage: {} <fractal> [iterations]", args[0]);
-                    println!("Available fractals: mandelbrot, burning-ship");
-                    return;
-                }
+        }
+    };
+    let threads = match take_option(&mut args, "--threads") {
+        Some(value) => match value.parse::<usize>() {
+            Ok(threads) if threads > 0 => Some(threads),
+            _ => {
+                usage(&args[0]);
+                return;
             }
         },
-        3 => {
-            let iterations = args[2].parse::<usize>().unwrap();
-            match args[1].as_str() {
-                "mandelbrot" => Fractal::Mandelbrot(iterations),
-                "burning-ship" => Fractal::BurningShip(iterations),
-                _ => {
-                    println!("Usage: {} <fractal> [iterations]", args[0]);
-                    println!("Available fractals: mandelbrot, burning-ship");
-                    return;
-                }
+        None => config.threads
+    };
+    if let Some(threads) = threads {
+        // only fails if rayon's already started, which it hasn't
+        let _ = rayon::ThreadPoolBuilder::new().num_threads(threads).build_global();
+    }
+
+    if subcommand(&mut args, &config) {
+        return;
+    }
+    let size = match take_option(&mut args, "--size") {
+        Some(value) => match parse_size(&value) {
+            Some(size) => size,
+            None => {
+                usage(&args[0]);
+                return;
             }
+        },
+        None => config.size
+    };
+    // where the state is saved to, and saved when the viewer's closed
+    let save_state = take_option(&mut args, "--save-state");
+    let print_location = take_flag(&mut args, "--print-location");
+    let commands = take_flag(&mut args, "--stdin").then(commands::listen);
+    let (options, fract) = match parse_options(&mut args, &config).zip(parse_fractal(&args)) {
+        Some(parsed) => parsed,
+        None => {
+            usage(&args[0]);
+            return;
         }
-        _ => {
-            println!("Usage: {} <fractal> [iterations]", args[0]);
-            println!("Available fractals: mandelbrot, burning-ship");
+    };
+    let bindings = match Bindings::load() {
+        Ok(bindings) => bindings,
+        Err(err) => {
+            println!("{}", err);
+            return;
+        }
+    };
+    let bookmarks = match Bookmarks::load() {
+        Ok(bookmarks) => bookmarks,
+        Err(err) => {
+            println!("{}", err);
             return;
         }
     };
 
-    let mut ctx = FractalContext::new();
+    let mut ctx = FractalContext::from_options(&options);
+    ctx.place(&fract, &options);
+    let (preview, screen) = (options.preview, options.screen);
 
-    let mut window = Window::new(
-        "Fractal Viewer",
-        WIDTH,
-        HEIGHT,
-        WindowOptions {
-            resize: true,
-            scale_mode: ScaleMode::Stretch,
-            ..WindowOptions::default()
-        },
-    )
-    .expect("failed to create window");
+    let renderer = ctx.renderer.name();
+    let title = format!("Fractal Viewer ({})", renderer);
+    let mut window = open_window(&title, size, false, config.frame_time);
+    // where the window was and how big, to go back to from fullscreen
+    let mut windowed: Option<((isize, isize), (usize, usize))> = None;
 
-    window.limit_update_rate(Some(std::time::Duration::from_micros(16600)));
+    // the view is rendered on its own thread, so input and repainting never wait for it
+    let request = Request::new(&ctx);
+    let cancel = ctx.cancel.clone();
+    let (requests, inbox) = mpsc::channel();
+    let (outbox, frames) = mpsc::channel();
+    let goto = Goto::new(&mut window);
+    let mut viewer = Viewer::new(&fract, args.clone(), request, options, bookmarks, save_state, goto);
+    thread::spawn(move || worker::run(fract, ctx, inbox, outbox));
+    let mut frame = Frame { pixels: vec![0; size.0 * size.1], dimensions: size, maxiter: 0, time: Duration::ZERO, minimap: None, request: 0, complete: false };
 
-    while window.is_open() && !window.is_key_down(Key::Escape) {
+    let mut last_input = Instant::now();
+    let mut mouse = Mouse::default();
+    // the last minimap the render thread sent
+    let mut map: Option<Vec<u32>> = None;
+    let mut glide = Glide::new();
+    // where to save the view from stdin's render command, once the requests up to the one
+    // with this sequence are rendered in full
+    let mut rendering: Option<(String, usize)> = None;
 
-        if window.get_size() != ctx.dimensions {
-            ctx.dimensions = window.get_size();
-            ctx.updated = true;
+    while window.is_open() {
+        // keys type into the goto prompt while it's open instead of doing what they're bound to
+        let mut actions = if viewer.goto.open { Vec::new() } else { bindings.triggered(&window) };
+        let mut render = None;
+        let mut quit = false;
+        for command in commands.iter().flat_map(mpsc::Receiver::try_iter) {
+            match command {
+                Command::Action(action) => actions.push(action),
+                Command::Render(path) => render = Some(path),
+                Command::Quit => quit = true,
+                command => viewer.command(command)
+            }
         }
-
-        if window.is_key_down(Key::I) {
-            ctx.scale *= 1.1;
-            ctx.updated = true;
+        if quit {
+            break;
         }
-
-        if window.is_key_down(Key::O) {
-            ctx.scale /= 1.1;
-            ctx.updated = true;
+        if actions.contains(&Action::Quit) {
+            // escape leaves the help before it leaves the viewer
+            if !viewer.help {
+                break;
+            }
+            viewer.help = false;
         }
 
-        if window.is_key_down(Key::Up) {
-            ctx.pan.1 -= 1. / ctx.scale;
-            ctx.updated = true;
+        if actions.contains(&Action::Fullscreen) {
+            // minifb can't change a window's decorations, so it's replaced with one that has
+            // the others; the view keeps going and takes on the new size below
+            window = match windowed.take() {
+                Some((position, size)) => {
+                    let mut window = open_window(&title, size, false, config.frame_time);
+                    window.set_position(position.0, position.1);
+                    window
+                }
+                None => {
+                    windowed = Some((window.get_position(), window.get_size()));
+                    open_window(&title, screen, true, config.frame_time)
+                }
+            };
+            viewer.goto.attach(&mut window);
+            mouse.grab = None;
         }
 
-        if window.is_key_down(Key::Down) {
-            ctx.pan.1 += 1. / ctx.scale;
-            ctx.updated = true;
+        let size = window.get_size();
+        viewer.resize(size);
+
+        let (zooming, panning) = viewer::held(&actions);
+        for &action in &actions {
+            viewer.act(action, size, &frame);
         }
 
-        if window.is_key_down(Key::Left) {
-            ctx.pan.0 -= 1. / ctx.scale;
-            ctx.updated = true;
+        // only the parameter plane has a Julia set to show
+        viewer.split &= viewer.current.julia.is_some();
+        let scrolled = mouse.handle(&window, &mut viewer, actions.contains(&Action::Julia), config.wheel_zoom);
+
+        let touring = viewer.play();
+
+        // while the view moves it's previewed at low resolution, once it settles in full
+        let gliding = glide.update(&mut viewer.request, zooming, panning);
+        let moving = touring || gliding || actions.iter().any(|action| action.moves());
+        viewer.request.settle(preview && (mouse.dragging() || scrolled || moving), &mut last_input);
+        viewer.update(&window, config.cycle_speed);
+
+        let pane_redrawn = match &mut viewer.pane {
+            Some(pane) if viewer.split => pane.update(&viewer.request, preview),
+            _ => false
+        };
+
+        if viewer.request.changed() {
+            // set before sending, so the render thread can't clear it before seeing why
+            if viewer.request.stale() {
+                cancel.store(true, Ordering::Relaxed);
+            }
+            if requests.send(viewer.request.take()).is_err() {
+                break;
+            }
+        }
+        if let Some(path) = render {
+            rendering = Some((path, viewer.request.sequence));
         }
 
-        if window.is_key_down(Key::Right) {
-            ctx.pan.0 += 1. / ctx.scale;
-            ctx.updated = true;
+        // only the latest pass is worth showing, but a minimap comes with only one of them
+        let mut redrawn = pane_redrawn;
+        for mut received in frames.try_iter() {
+            if let Some(received) = received.minimap.take() {
+                map = Some(received);
+            }
+            frame = received;
+            redrawn = true;
+        }
+        if let Some((path, sequence)) = &rendering {
+            if frame.complete && frame.request >= *sequence {
+                save_frame(path, &frame, &viewer.current.bookmark(&viewer.request));
+                rendering = None;
+            }
         }
 
-        if ctx.updated {
-            fract.render(&mut ctx);
+        let minimap = map.as_deref().filter(|_| viewer.request.minimap);
+        if let Some((pixels, dimensions)) = viewer.overlay(&frame, minimap, &bindings, renderer) {
             window
-                .update_with_buffer(&ctx.pixels, ctx.dimensions.0, ctx.dimensions.1)
+                .update_with_buffer(&pixels, dimensions.0, dimensions.1)
+                .unwrap();
+        } else if redrawn {
+            window
+                .update_with_buffer(&frame.pixels, frame.dimensions.0, frame.dimensions.1)
                 .unwrap();
         } else {
             window.update();
         }
     }
-}
\ No newline at end of file
+
+    viewer.close(print_location, &frame);
+}
@@ -0,0 +1,130 @@
+use crate::fixed::Center;
+use crate::worker::Request;
+use crate::{rotate, Fractal, FractalContext, WIDTH};
+
+/// Size of the map in pixels. It shows what a window this much narrower than the initial
+/// one shows at the fractal's initial view.
+pub const SIZE: (usize, usize) = (192, 108);
+const MARGIN: usize = 8;
+const BORDER: u32 = 0xFFFFFF;
+const VIEWPORT: u32 = 0xFF0000;
+/// The viewport rectangle never gets smaller than this, so it can still be found deep in.
+const MIN_VIEWPORT: f64 = 3.;
+
+/// Scale of the map of a fractal whose initial view is at `home_scale`.
+fn map_scale(home_scale: f64) -> f64 {
+    home_scale * SIZE.0 as f64 / WIDTH as f64
+}
+
+/// Renders `fract` at its initial view to completion with the coloring of `ctx`, on the CPU
+/// whatever renders the view.
+pub fn render(fract: &Fractal, ctx: &FractalContext) -> FractalContext {
+    let mut map = FractalContext::new();
+    map.dimensions = SIZE;
+    let (pan, scale) = fract.home();
+    (map.pan, map.scale) = (pan, map_scale(scale));
+    map.coloring = ctx.coloring;
+    map.brightness = ctx.brightness;
+    map.tracking = ctx.tracking;
+    map.light = ctx.light;
+    map.tracing = ctx.tracing;
+    map.bulbs = ctx.bulbs;
+    recolor(fract, &mut map, ctx);
+    map
+}
+
+/// Brings the map's coloring in line with `ctx`, rendering it again if its pixels can't be
+/// recolored.
+pub fn recolor(fract: &Fractal, map: &mut FractalContext, ctx: &FractalContext) {
+    map.interior = ctx.interior;
+    map.slope = ctx.slope;
+    map.gamma = ctx.gamma;
+    map.exposure = ctx.exposure;
+    map.dither = ctx.dither;
+    map.palette = ctx.palette.clone();
+    map.palette_offset = ctx.palette_offset;
+    if !Fractal::recolor(map) {
+        map.updated = true;
+        fract.render_all(map);
+    }
+}
+
+/// Puts `map` in the bottom right corner with a rectangle where `request` is looking, the
+/// fractal's initial view being `home`.
+pub fn draw(frame: &mut [u32], (width, height): (usize, usize), map: &[u32], home: &(Center, f64), request: &Request) {
+    if width < SIZE.0 + 2 * MARGIN || height < SIZE.1 + 2 * MARGIN {
+        return;
+    }
+    let (left, top) = (width - MARGIN - SIZE.0, height - MARGIN - SIZE.1);
+    for y in 0..SIZE.1 {
+        let row = (top + y) * width + left;
+        frame[row..row + SIZE.0].copy_from_slice(&map[y * SIZE.0..(y + 1) * SIZE.0]);
+    }
+    outline(frame, width, (left as f64 - 1., top as f64 - 1.), (SIZE.0 as f64 + 1., SIZE.1 as f64 + 1.), BORDER);
+
+    let (re, im) = request.pan.exact();
+    let (home_re, home_im) = home.0.exact();
+    let ratio = map_scale(home.1) / request.scale;
+    let center = (
+        left as f64 + SIZE.0 as f64 / 2. + (re - home_re).to_f64() * map_scale(home.1),
+        top as f64 + SIZE.1 as f64 / 2. + (im - home_im).to_f64() * map_scale(home.1)
+    );
+    let size = (
+        (request.dimensions.0 as f64 * ratio).max(MIN_VIEWPORT),
+        (request.dimensions.1 as f64 * ratio).max(MIN_VIEWPORT)
+    );
+    // turned with the view, and only the part over the map so a view off its edge doesn't
+    // spill across the frame
+    let corners = [(-1., -1.), (1., -1.), (1., 1.), (-1., 1.)].map(|(x, y)| {
+        let (dx, dy) = rotate((x * size.0 / 2., y * size.1 / 2.), request.rotation);
+        (center.0 + dx, center.1 + dy)
+    });
+    let bounds = ((left, top), (left + SIZE.0 - 1, top + SIZE.1 - 1));
+    for (i, &from) in corners.iter().enumerate() {
+        line(frame, width, from, corners[(i + 1) % 4], bounds, VIEWPORT);
+    }
+}
+
+/// Draws the part of the line from `from` to `to` inside `bounds`.
+fn line(frame: &mut [u32], width: usize, from: (f64, f64), to: (f64, f64), bounds: ((usize, usize), (usize, usize)), color: u32) {
+    let ((left, top), (right, bottom)) = bounds;
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    // cut down to the stretch inside, so far off views don't take stepping along the whole way
+    let (mut start, mut end) = (0f64, 1f64);
+    for (delta, from, low, high) in [(dx, from.0, left as f64, right as f64), (dy, from.1, top as f64, bottom as f64)] {
+        if delta == 0. {
+            if from < low || from > high {
+                return;
+            }
+            continue;
+        }
+        let (a, b) = ((low - from) / delta, (high - from) / delta);
+        (start, end) = (start.max(a.min(b)), end.min(a.max(b)));
+    }
+    if start > end {
+        return;
+    }
+    let steps = ((end - start) * dx.abs().max(dy.abs())).ceil().max(1.);
+    for step in 0..=steps as usize {
+        let t = start + (end - start) * step as f64 / steps;
+        let (x, y) = ((from.0 + dx * t).round(), (from.1 + dy * t).round());
+        if x >= left as f64 && x <= right as f64 && y >= top as f64 && y <= bottom as f64 {
+            frame[x as usize + y as usize * width] = color;
+        }
+    }
+}
+
+/// Draws the edges of the rectangle from `(x, y)` spanning `size`, which has to be inside
+/// the frame.
+fn outline(frame: &mut [u32], width: usize, (x, y): (f64, f64), size: (f64, f64), color: u32) {
+    let (left, top) = (x.round() as usize, y.round() as usize);
+    let (right, bottom) = ((x + size.0).round() as usize, (y + size.1).round() as usize);
+    for px in left..=right {
+        frame[px + top * width] = color;
+        frame[px + bottom * width] = color;
+    }
+    for py in top..=bottom {
+        frame[left + py * width] = color;
+        frame[right + py * width] = color;
+    }
+}
@@ -0,0 +1,81 @@
+use minifb::{MouseButton, MouseMode, Window};
+
+use crate::viewer::Viewer;
+
+/// What the mouse was doing on the frames before, to carry on with.
+#[derive(Default)]
+pub struct Mouse {
+    /// Where the view was grabbed, while the left button drags it.
+    pub grab: Option<(f32, f32)>,
+    /// Whether that's in the Julia pane.
+    grab_pane: bool,
+    was_middle: bool
+}
+
+impl Mouse {
+    /// Zooms with the wheel, drags, selects and picks Julia seeds from the views in `viewer`,
+    /// in the split view the one the mouse is over. `julia` is whether the key that opens the
+    /// Julia set under the cursor was pressed. Returns whether the wheel zoomed the main view.
+    pub fn handle(&mut self, window: &Window, viewer: &mut Viewer, julia: bool, wheel_zoom: f64) -> bool {
+        // in the split view the mouse works on the pane it's over
+        let mouse = window.get_mouse_pos(MouseMode::Discard);
+        let over_pane = viewer.split && mouse.is_some_and(|(x, _)| x as usize >= viewer.request.dimensions.0);
+
+        let scrolled = match window.get_scroll_wheel().zip(mouse) {
+            Some(((_, wheel), (x, y))) => match &mut viewer.pane {
+                Some(pane) if over_pane => {
+                    pane.zoom_at(wheel_zoom.powf(wheel as f64), (x as f64 - viewer.request.dimensions.0 as f64, y as f64));
+                    false
+                }
+                _ => {
+                    viewer.request.zoom_at(wheel_zoom.powf(wheel as f64), (x as f64, y as f64));
+                    true
+                }
+            },
+            None => false
+        };
+
+        if !viewer.editor.open && (!over_pane || viewer.selection.active()) {
+            viewer.selection.handle(window, &mut viewer.request);
+        }
+
+        // J or a middle click over the parameter plane opens the Julia set seeded there
+        let middle = window.get_mouse_down(MouseButton::Middle);
+        if !over_pane && (julia || (middle && !self.was_middle)) {
+            if let (Some(family), Some((x, y))) = (viewer.current.julia, window.get_mouse_pos(MouseMode::Discard)) {
+                viewer.current.switch(family.spec(viewer.request.point((x as f64, y as f64))), &mut viewer.request);
+            }
+        }
+        self.was_middle = middle;
+
+        // the editor takes the mouse while it's open
+        let dragging = !viewer.editor.open && window.get_mouse_down(MouseButton::Left);
+        self.grab = match (self.grab, window.get_mouse_pos(MouseMode::Pass)) {
+            (Some((x0, y0)), Some((x, y))) if dragging => {
+                // by whole pixels so a finished render can be shifted, carrying the rest over
+                let (dx, dy) = ((x0 - x).round(), (y0 - y).round());
+                match &mut viewer.pane {
+                    Some(pane) if self.grab_pane => pane.pan_pixels(dx as isize, dy as isize),
+                    _ => viewer.request.pan_pixels(dx as isize, dy as isize)
+                }
+                Some((x0 - dx, y0 - dy))
+            }
+            (None, position) if dragging => {
+                self.grab_pane = over_pane;
+                position
+            }
+            _ => None
+        };
+
+        if let (true, false, Some(pane), Some(family), Some((x, y))) = (viewer.split, over_pane, &mut viewer.pane, viewer.current.julia, mouse) {
+            pane.follow(family, viewer.request.point((x as f64, y as f64)));
+        }
+
+        scrolled
+    }
+
+    /// Whether the main view is being dragged, for it to be previewed.
+    pub fn dragging(&self) -> bool {
+        self.grab.is_some() && !self.grab_pane
+    }
+}
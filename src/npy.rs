@@ -0,0 +1,39 @@
+use std::io::{self, Write};
+
+/// A NumPy `.npy` array of each pixel's escape iteration, -1 for points that didn't escape,
+/// written a few rows at a time. The iterations are 32-bit ints, or with `modulus` records of
+/// them and the final |z| as a 64-bit float.
+pub struct Writer<W: Write> {
+    out: W,
+    modulus: bool
+}
+
+impl<W: Write> Writer<W> {
+    pub fn new(mut out: W, (width, height): (usize, usize), modulus: bool) -> io::Result<Writer<W>> {
+        let descr = if modulus { "[('iterations', '<i4'), ('modulus', '<f8')]" } else { "'<i4'" };
+        let mut header = format!("{{'descr': {}, 'fortran_order': False, 'shape': ({}, {}), }}", descr, height, width);
+        // padded with spaces and a newline so the data starts on a multiple of 64 bytes
+        let length = 10 + header.len() + 1;
+        header += &" ".repeat(length.next_multiple_of(64) - length);
+        header.push('\n');
+        out.write_all(b"\x93NUMPY\x01\x00")?;
+        out.write_all(&(header.len() as u16).to_le_bytes())?;
+        out.write_all(header.as_bytes())?;
+        Ok(Writer { out, modulus })
+    }
+
+    /// Adds the next whole rows of the image.
+    pub fn rows(&mut self, iterations: &[i32], modulus: &[f64]) -> io::Result<()> {
+        for (n, z) in iterations.iter().zip(modulus) {
+            self.out.write_all(&n.to_le_bytes())?;
+            if self.modulus {
+                self.out.write_all(&z.to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
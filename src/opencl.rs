@@ -0,0 +1,143 @@
+use std::ptr;
+use std::sync::Mutex;
+
+use opencl3::command_queue::CommandQueue;
+use opencl3::context::Context;
+use opencl3::device::{get_all_devices, Device, CL_DEVICE_TYPE_GPU};
+use opencl3::kernel::{ExecuteKernel, Kernel};
+use opencl3::memory::{Buffer, CL_MEM_WRITE_ONLY};
+use opencl3::program::Program;
+use opencl3::types::{cl_float, cl_uint, CL_BLOCKING};
+
+use crate::{simd, FractalContext, Seed};
+use crate::orbit::Sample;
+use crate::renderer::{self, Renderer};
+
+/// The wgpu shader's kernel in OpenCL C 1.2, single precision so it runs on older devices
+/// without fp64, writing the same eight floats per pixel.
+const SOURCE: &str = r#"
+__kernel void escape(
+    __global float *results,
+    float center_re, float center_im, float scale,
+    float seed_re, float seed_im, float cos_rotation, float sin_rotation,
+    uint maxiter, uint julia
+) {
+    size_t x = get_global_id(0), y = get_global_id(1);
+    size_t width = get_global_size(0), height = get_global_size(1);
+    float2 pixel = (float2)((float)x - width / 2.0f, (float)y - height / 2.0f);
+    float2 offset = (float2)(pixel.x * cos_rotation - pixel.y * sin_rotation, pixel.x * sin_rotation + pixel.y * cos_rotation);
+    float2 point = offset / scale + (float2)(center_re, center_im);
+
+    float2 z = (float2)(0.0f, 0.0f), c = point, dz = (float2)(0.0f, 0.0f), dc = (float2)(1.0f, 0.0f);
+    if (julia) {
+        z = point;
+        c = (float2)(seed_re, seed_im);
+        dz = (float2)(1.0f, 0.0f);
+        dc = (float2)(0.0f, 0.0f);
+    }
+
+    float escape = -1.0f;
+    for (uint i = 0; i < maxiter; i++) {
+        dz = 2.0f * (float2)(z.x * dz.x - z.y * dz.y, z.x * dz.y + z.y * dz.x) + dc;
+        z = (float2)(z.x * z.x - z.y * z.y, 2.0f * z.x * z.y) + c;
+        if (dot(z, z) > 4.0f) {
+            escape = (float)i;
+            break;
+        }
+    }
+
+    __global float *out = results + 8 * (x + y * width);
+    out[0] = escape;
+    out[1] = z.x;
+    out[2] = z.y;
+    out[3] = 0.0f;
+    out[4] = dz.x;
+    out[5] = dz.y;
+    out[6] = 0.0f;
+    out[7] = 0.0f;
+}
+"#;
+
+pub struct OpenCl {
+    context: Context,
+    queue: CommandQueue,
+    // arguments are set on the kernel object itself, so renders must not share it
+    kernel: Mutex<Kernel>
+}
+
+impl OpenCl {
+    /// Builds the kernel for the first GPU device, loading the OpenCL library at runtime.
+    pub fn new() -> Result<OpenCl, String> {
+        let device = *get_all_devices(CL_DEVICE_TYPE_GPU)
+            .map_err(|err| err.to_string())?
+            .first()
+            .ok_or("no OpenCL GPU devices")?;
+        let context = Context::from_device(&Device::new(device)).map_err(|err| err.to_string())?;
+        let queue = CommandQueue::create_default(&context, 0).map_err(|err| err.to_string())?;
+        let program = Program::create_and_build_from_source(&context, SOURCE, "")?;
+        let kernel = Kernel::create(&program, "escape").map_err(|err| err.to_string())?;
+
+        Ok(OpenCl { context, queue, kernel: Mutex::new(kernel) })
+    }
+
+    fn run(&self, ctx: &FractalContext, seed: Seed, maxiter: usize) -> Result<Vec<f32>, String> {
+        let (width, height) = ctx.dimensions;
+        let (julia, c): (cl_uint, _) = match seed {
+            Seed::Parameter => (0, (0., 0.)),
+            Seed::Julia(c) => (1, (c.re, c.im))
+        };
+        let (re, im) = ctx.pan.approx();
+        let (sin, cos) = ctx.rotation.sin_cos();
+        let floats = [re, im, ctx.scale, c.0, c.1, cos, sin].map(|value| value as cl_float);
+        let mut values = vec![0.; width * height * 8];
+
+        let kernel = self.kernel.lock().unwrap();
+        // SAFETY: the buffer is only written by the kernel and read back after it finished,
+        // and the arguments match the kernel signature above
+        unsafe {
+            let results = Buffer::<cl_float>::create(&self.context, CL_MEM_WRITE_ONLY, values.len(), ptr::null_mut())
+                .map_err(|err| err.to_string())?;
+
+            let mut execute = ExecuteKernel::new(&kernel);
+            execute.set_arg(&results);
+            for value in &floats {
+                execute.set_arg(value);
+            }
+            let event = execute
+                .set_arg(&(maxiter as cl_uint))
+                .set_arg(&julia)
+                .set_global_work_sizes(&[width, height])
+                .enqueue_nd_range(&self.queue)
+                .map_err(|err| err.to_string())?;
+
+            self.queue
+                .enqueue_read_buffer(&results, CL_BLOCKING, 0, &mut values, &[event.get()])
+                .map_err(|err| err.to_string())?;
+        }
+        Ok(values)
+    }
+}
+
+impl Renderer for OpenCl {
+    fn name(&self) -> &'static str {
+        "opencl"
+    }
+
+    fn progressive(&self) -> bool {
+        false
+    }
+
+    fn quadratic(&self, ctx: &FractalContext, seed: Seed, maxiter: usize, pixels: &[usize]) -> Option<Vec<Sample>> {
+        // single precision all the way, like the wgpu shader
+        if ctx.scale >= simd::SINGLE_SCALE {
+            return None;
+        }
+        match self.run(ctx, seed, maxiter) {
+            Ok(values) => Some(renderer::unpack(ctx, seed, &values, pixels)),
+            Err(err) => {
+                println!("OpenCL render failed ({}), using the CPU", err);
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use num_complex::Complex;
+
+/// How many recent iterates are kept for the fallback period search, which bounds the
+/// longest period it can find.
+pub const HISTORY: usize = 32;
+/// Distance at which a slowly converging cycle is taken to have closed.
+pub const PERIOD_TOLERANCE: f64 = 1e-4;
+/// Squared distance from the reference iterate at which an orbit has closed its cycle, only
+/// a few ulps, as orbits near deep minibrots pass much closer than that without settling.
+pub const CYCLE_TOLERANCE: f64 = 1e-30;
+
+/// What an escape-time kernel reports about a single pixel's orbit.
+#[derive(Clone, Copy, Default)]
+pub struct Sample {
+    /// Iteration at which the orbit left the bailout radius, and |z| at that point; `None`
+    /// for interior points that never escaped.
+    pub escape: Option<(usize, f64)>,
+    /// Argument of z at escape.
+    pub angle: f64,
+    /// Closest approach of the orbit to the trap shape before escaping.
+    pub trap: f64,
+    /// Estimated distance to the set boundary, for kernels that track their derivative.
+    pub distance: f64,
+    /// Unit direction of `z / dz` at escape, the surface normal of the potential field; zero
+    /// for kernels that don't track their derivative.
+    pub normal: Complex<f64>,
+    /// Last iterate inside the bailout radius.
+    pub last: Complex<f64>,
+    /// Period of the attracting cycle the orbit settled into, or 0 if none was found.
+    pub period: usize,
+    /// Stripe average of the orbit, blended by the smooth iteration fraction.
+    pub stripe: f64,
+    /// Triangle inequality average of the orbit, blended the same way.
+    pub tia: f64
+}
+
+/// Which optional per-iteration statistics an orbit accumulates.
+#[derive(Clone, Copy, Default)]
+pub struct Tracking {
+    pub trap: Option<Trap>,
+    /// Stripe density `k` in the `sin(k * arg(z))` stripe average.
+    pub stripes: Option<f64>,
+    pub tia: bool
+}
+
+impl Tracking {
+    pub fn is_empty(self) -> bool {
+        self.trap.is_none() && self.stripes.is_none() && !self.tia
+    }
+
+    /// Tracks everything either `self` or `other` needs.
+    pub fn union(self, other: Tracking) -> Tracking {
+        Tracking {
+            trap: self.trap.or(other.trap),
+            stripes: self.stripes.or(other.stripes),
+            tia: self.tia || other.tia
+        }
+    }
+}
+
+/// Running mean of a per-iteration term that remembers the latest term, so the mean can be
+/// interpolated between the last two iterations.
+#[derive(Default)]
+struct Average {
+    n: usize,
+    sum: f64,
+    last: f64
+}
+
+impl Average {
+    fn push(&mut self, term: f64) {
+        self.n += 1;
+        self.sum += term;
+        self.last = term;
+    }
+
+    /// Blends the means with and without the escaping iterate, so the value changes
+    /// continuously where the escape iteration count jumps.
+    fn blend(&self, modulus: f64) -> f64 {
+        if self.n < 2 {
+            return if self.n == 1 { self.sum } else { 0. };
+        }
+        let mean = self.sum / self.n as f64;
+        let previous = (self.sum - self.last) / (self.n - 1) as f64;
+
+        let fraction = (1. + (2f64.ln() / modulus.ln()).log2()).clamp(0., 1.);
+        fraction * mean + (1. - fraction) * previous
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Trap {
+    Point(Complex<f64>),
+    Cross(Complex<f64>),
+    Ring(Complex<f64>, f64)
+}
+
+impl Trap {
+    /// Parses `<point|cross|ring>[:<re>,<im>[,<radius>]]`, e.g. `ring:0,0,0.5`.
+    pub fn parse(spec: &str) -> Option<Trap> {
+        let (kind, args) = spec.split_once(':').unwrap_or((spec, ""));
+        let values = args
+            .split(',')
+            .filter(|arg| !arg.is_empty())
+            .map(|arg| arg.parse::<f64>().ok())
+            .collect::<Option<Vec<_>>>()?;
+
+        let center = match values.len() {
+            0 => Complex::new(0., 0.),
+            _ => Complex::new(values[0], *values.get(1)?)
+        };
+
+        match (kind, values.len()) {
+            ("point", 0 | 2) => Some(Trap::Point(center)),
+            ("cross", 0 | 2) => Some(Trap::Cross(center)),
+            ("ring", 0) => Some(Trap::Ring(center, 1.)),
+            ("ring", 3) => Some(Trap::Ring(center, values[2])),
+            _ => None
+        }
+    }
+
+    fn distance(self, z: Complex<f64>) -> f64 {
+        match self {
+            Trap::Point(p) => (z - p).norm(),
+            Trap::Cross(p) => (z.re - p.re).abs().min((z.im - p.im).abs()),
+            Trap::Ring(p, r) => ((z - p).norm() - r).abs()
+        }
+    }
+}
+
+pub struct Orbit {
+    tracking: Tracking,
+    c: Complex<f64>,
+    prev: Complex<f64>,
+    stripe: Average,
+    tia: Average,
+    dz: Option<Complex<f64>>,
+    reference: (usize, Complex<f64>),
+    history: [Complex<f64>; HISTORY],
+    visited: usize,
+    sample: Sample
+}
+
+impl Orbit {
+    pub fn new(tracking: Tracking, z: Complex<f64>, c: Complex<f64>) -> Orbit {
+        Orbit {
+            tracking,
+            c,
+            prev: z,
+            stripe: Average::default(),
+            tia: Average::default(),
+            dz: None,
+            reference: (0, Complex::new(f64::NAN, f64::NAN)),
+            history: [Complex::new(f64::NAN, f64::NAN); HISTORY],
+            visited: 0,
+            sample: Sample { trap: f64::INFINITY, ..Sample::default() }
+        }
+    }
+
+    /// Records the derivative of the current iterate, to be called before `visit`.
+    pub fn track_derivative(&mut self, dz: Complex<f64>) {
+        self.dz = Some(dz);
+    }
+
+    /// Records iterate `i`, returning whether the kernel should stop because the orbit
+    /// escaped or closed a cycle, which it would only repeat until the iteration limit.
+    pub fn visit(&mut self, i: usize, z: Complex<f64>, escaped: bool) -> bool {
+        if let Some(k) = self.tracking.stripes {
+            self.stripe.push(0.5 * (k * z.arg()).sin() + 0.5);
+        }
+        if self.tracking.tia {
+            // where |z| falls between the triangle inequality bounds on |prev^2 + c|
+            let (sq, c) = ((self.prev * self.prev).norm(), self.c.norm());
+            let (low, high) = ((sq - c).abs(), sq + c);
+            if high > low {
+                self.tia.push((z.norm() - low) / (high - low));
+            }
+        }
+        self.prev = z;
+
+        if escaped {
+            let modulus = z.norm();
+            self.sample.escape = Some((i, modulus));
+            self.sample.angle = z.arg();
+            if let Some(dz) = self.dz {
+                self.sample.distance = modulus * modulus.ln() / dz.norm();
+                let u = z / dz;
+                self.sample.normal = u / u.norm();
+            }
+            self.sample.stripe = self.stripe.blend(modulus);
+            self.sample.tia = self.tia.blend(modulus);
+            return true;
+        }
+
+        if let Some(trap) = self.tracking.trap {
+            self.sample.trap = self.sample.trap.min(trap.distance(z));
+        }
+        self.sample.last = z;
+        self.history[i % HISTORY] = z;
+        self.visited = i + 1;
+
+        // Brent's cycle detection: compare against a reference iterate that is moved
+        // forward whenever i reaches the next power of two
+        if (z - self.reference.1).norm_sqr() < CYCLE_TOLERANCE {
+            self.sample.period = i - self.reference.0;
+            return true;
+        }
+        if (i + 1).is_power_of_two() {
+            self.reference = (i, z);
+        }
+        false
+    }
+
+    pub fn sample(mut self) -> Sample {
+        if self.sample.escape.is_none() && self.sample.period == 0 {
+            self.sample.period = self.settled_period();
+        }
+        self.sample
+    }
+
+    /// Orbits near a bulb's boundary converge too slowly for the exact match above within
+    /// the iteration limit, so look for the shortest near-repeat among the last iterates.
+    fn settled_period(&self) -> usize {
+        if self.visited < HISTORY {
+            return 0;
+        }
+        let last = self.history[(self.visited - 1) % HISTORY];
+        (1..HISTORY)
+            .find(|&p| (last - self.history[(self.visited - 1 - p) % HISTORY]).norm() < PERIOD_TOLERANCE)
+            .unwrap_or(0)
+    }
+}
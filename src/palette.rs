@@ -0,0 +1,138 @@
+#[derive(Clone, Copy, PartialEq)]
+pub enum Interpolation {
+    Linear,
+    Smooth
+}
+
+use crate::oklab;
+
+/// A gradient over `[0, 1]` defined by color stops in ascending position order. Stops are
+/// given in sRGB but blended in Oklab so gradients change evenly in perceived lightness.
+#[derive(Clone)]
+pub struct Palette {
+    pub name: String,
+    /// The file it was loaded from, if it wasn't built in.
+    pub source: Option<String>,
+    stops: Vec<(f64, [f64; 3])>,
+    interpolation: Interpolation
+}
+
+impl Palette {
+    pub fn new(name: &str, stops: Vec<(f64, [f64; 3])>, interpolation: Interpolation) -> Palette {
+        let stops = stops.into_iter().map(|(pos, color)| (pos, oklab::from_srgb(color))).collect();
+        Palette { name: name.to_string(), source: None, stops, interpolation }
+    }
+
+    /// What names the palette to come back to it by: the file it was loaded from, or the
+    /// name of a built-in one.
+    pub fn key(&self) -> &str {
+        self.source.as_deref().unwrap_or(&self.name)
+    }
+
+    /// Evenly spaced stops from `0xRRGGBB` colors.
+    fn from_hex(name: &str, colors: &[u32]) -> Palette {
+        let last = (colors.len() - 1) as f64;
+        let channel = |color: u32, shift: u32| ((color >> shift) & 0xFF) as f64 / 255.;
+        let stops = colors
+            .iter()
+            .enumerate()
+            .map(|(i, &color)| (i as f64 / last, [channel(color, 16), channel(color, 8), channel(color, 0)]))
+            .collect();
+        Palette::new(name, stops, Interpolation::Linear)
+    }
+
+    pub fn builtin() -> Vec<Palette> {
+        let mut palettes = vec![
+            Palette::new("grayscale", vec![(0., [0., 0., 0.]), (1., [1., 1., 1.])], Interpolation::Linear),
+            Palette::new("fire", vec![
+                (0., [0., 0., 0.]),
+                (0.3, [0.7, 0., 0.]),
+                (0.6, [1., 0.5, 0.]),
+                (0.85, [1., 0.9, 0.2]),
+                (1., [1., 1., 1.])
+            ], Interpolation::Smooth),
+            Palette::new("ocean", vec![
+                (0., [0., 0., 0.1]),
+                (0.4, [0., 0.2, 0.5]),
+                (0.75, [0., 0.7, 0.8]),
+                (1., [0.9, 1., 1.])
+            ], Interpolation::Smooth),
+            Palette::new("rainbow", vec![
+                (0., [0., 0., 0.]),
+                (0.15, [0.5, 0., 1.]),
+                (0.3, [0., 0., 1.]),
+                (0.45, [0., 1., 1.]),
+                (0.6, [0., 1., 0.]),
+                (0.75, [1., 1., 0.]),
+                (0.9, [1., 0., 0.]),
+                (1., [1., 1., 1.])
+            ], Interpolation::Linear)
+        ];
+        palettes.extend(Palette::accessible());
+        palettes
+    }
+
+    /// Perceptually uniform palettes that stay readable with color vision deficiencies.
+    pub fn accessible() -> Vec<Palette> {
+        vec![
+            Palette::from_hex("viridis", &[
+                0x440154, 0x482878, 0x3E4989, 0x31688E, 0x26828E, 0x1F9E89, 0x35B779, 0x6ECE58, 0xFDE725
+            ]),
+            Palette::from_hex("cividis", &[
+                0x00204D, 0x00336F, 0x39486B, 0x575D6D, 0x707173, 0x8A8779, 0xA69D75, 0xD3C164, 0xFEE838
+            ]),
+            Palette::from_hex("magma", &[
+                0x000004, 0x1C1044, 0x4F127B, 0x812581, 0xB5367A, 0xE55064, 0xFB8761, 0xFEC287, 0xFCFDBF
+            ])
+        ]
+    }
+
+    pub fn stop_count(&self) -> usize {
+        self.stops.len()
+    }
+
+    /// Position and sRGB color of the `i`th stop.
+    pub fn stop(&self, i: usize) -> (f64, [f64; 3]) {
+        let (pos, color) = self.stops[i];
+        (pos, oklab::to_srgb(color))
+    }
+
+    /// Adds a stop, returning its index once placed in order.
+    pub fn insert_stop(&mut self, pos: f64, color: [f64; 3]) -> usize {
+        self.place((pos.clamp(0., 1.), oklab::from_srgb(color)))
+    }
+
+    /// Replaces the `i`th stop, returning its new index since moving it can reorder the stops.
+    pub fn set_stop(&mut self, i: usize, pos: f64, color: [f64; 3]) -> usize {
+        self.stops.remove(i);
+        self.insert_stop(pos, color)
+    }
+
+    pub fn remove_stop(&mut self, i: usize) {
+        self.stops.remove(i);
+    }
+
+    fn place(&mut self, stop: (f64, [f64; 3])) -> usize {
+        let i = self.stops.partition_point(|&(pos, _)| pos <= stop.0);
+        self.stops.insert(i, stop);
+        i
+    }
+
+    pub fn color(&self, t: f64) -> [f64; 3] {
+        let t = if t.is_nan() { 0. } else { t.clamp(0., 1.) };
+
+        let upper = self.stops.iter().position(|&(pos, _)| pos >= t).unwrap_or(self.stops.len() - 1);
+        if upper == 0 {
+            return oklab::to_srgb(self.stops[0].1);
+        }
+
+        let (p0, c0) = self.stops[upper - 1];
+        let (p1, c1) = self.stops[upper];
+        let mut f = if p1 > p0 { (t - p0) / (p1 - p0) } else { 1. };
+        if self.interpolation == Interpolation::Smooth {
+            f = f * f * (3. - 2. * f);
+        }
+
+        oklab::to_srgb([c0[0] + (c1[0] - c0[0]) * f, c0[1] + (c1[1] - c0[1]) * f, c0[2] + (c1[2] - c0[2]) * f])
+    }
+}
@@ -0,0 +1,121 @@
+use crate::palette::{Interpolation, Palette};
+
+/// Loads a Fractint `.map` or UltraFractal `.ugr` gradient, chosen by file extension. It
+/// keeps the full path of the file as its source.
+pub fn load(path: &str) -> Result<Palette, String> {
+    let source = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+    let name = std::path::Path::new(path)
+        .file_stem()
+        .map_or(path.to_string(), |stem| stem.to_string_lossy().into_owned());
+
+    let mut palette = if path.to_ascii_lowercase().ends_with(".ugr") {
+        parse_ugr(&name, &source).map_err(|err| format!("{}: {}", path, err))?
+    } else {
+        parse_map(&name, &source).map_err(|err| format!("{}: {}", path, err))?
+    };
+    palette.source = Some(std::fs::canonicalize(path).map_or(path.to_string(), |path| path.to_string_lossy().into_owned()));
+    Ok(palette)
+}
+
+/// Index in `palettes` of the one `name` names, a built-in's name or a palette file's path,
+/// loading the file and adding it the first time.
+pub fn find(palettes: &mut Vec<Palette>, name: &str) -> Result<usize, String> {
+    match palettes.iter().position(|palette| palette.key() == name) {
+        Some(index) => Ok(index),
+        None => {
+            palettes.push(load(name)?);
+            Ok(palettes.len() - 1)
+        }
+    }
+}
+
+/// Fractint maps are one `r g b` triple per line (0-255), optionally followed by a comment.
+fn parse_map(name: &str, source: &str) -> Result<Palette, String> {
+    let mut colors = Vec::new();
+    for (n, line) in source.lines().enumerate() {
+        let fields: Vec<&str> = line.split_whitespace().take(3).collect();
+        if fields.is_empty() {
+            continue;
+        }
+
+        let channels = fields
+            .iter()
+            .map(|field| field.parse::<u8>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| format!("line {}: {}", n + 1, err))?;
+        if channels.len() != 3 {
+            return Err(format!("line {}: expected an r g b triple", n + 1));
+        }
+        colors.push([channels[0] as f64 / 255., channels[1] as f64 / 255., channels[2] as f64 / 255.]);
+    }
+
+    if colors.len() < 2 {
+        return Err("a map needs at least two colors".to_string());
+    }
+
+    let last = (colors.len() - 1) as f64;
+    let stops = colors.into_iter().enumerate().map(|(i, color)| (i as f64 / last, color)).collect();
+    Ok(Palette::new(name, stops, Interpolation::Linear))
+}
+
+/// UltraFractal gradients list `index=<0-399> color=<0xBBGGRR as decimal>` stops; only the
+/// first gradient in the file is used.
+fn parse_ugr(name: &str, source: &str) -> Result<Palette, String> {
+    let body = source.split('}').next().unwrap_or("");
+
+    let mut title = name.to_string();
+    let mut interpolation = Interpolation::Linear;
+    let mut stops = Vec::new();
+    let mut index = None;
+
+    for token in tokens(body) {
+        let (key, value) = match token.split_once('=') {
+            Some(pair) => pair,
+            None => continue
+        };
+
+        match key {
+            "title" => title = value.trim_matches('"').to_string(),
+            "smooth" if value == "yes" => interpolation = Interpolation::Smooth,
+            "index" => index = Some(value.parse::<f64>().map_err(|err| format!("bad index '{}': {}", value, err))?),
+            "color" => {
+                let bgr = value.parse::<u32>().map_err(|err| format!("bad color '{}': {}", value, err))?;
+                let pos = index.take().ok_or("color without a preceding index")?;
+                let channel = |shift: u32| ((bgr >> shift) & 0xFF) as f64 / 255.;
+                stops.push((pos / 400., [channel(0), channel(8), channel(16)]));
+            }
+            _ => {}
+        }
+    }
+
+    if stops.is_empty() {
+        return Err("no gradient stops found".to_string());
+    }
+
+    stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+    Ok(Palette::new(&title, stops, interpolation))
+}
+
+/// Splits on whitespace, keeping double-quoted runs (like `title="Deep Sea"`) together.
+fn tokens(source: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+
+    for ch in source.chars() {
+        if ch == '"' {
+            quoted = !quoted;
+            current.push(ch);
+        } else if ch.is_whitespace() && !quoted {
+            if !current.is_empty() {
+                tokens.push(std::mem::take(&mut current));
+            }
+        } else {
+            current.push(ch);
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
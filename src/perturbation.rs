@@ -0,0 +1,114 @@
+use rayon::prelude::*;
+
+use num_complex::Complex;
+
+use crate::{simd, FractalContext};
+use crate::fixed::{self, Fixed};
+use crate::orbit::Sample;
+
+/// Largest error relative to the exact offset for the series to still stand in for it.
+const SERIES_TOLERANCE: f64 = 1e-9;
+
+/// The orbit of the view center under `z^2 + c`, iterated in fixed point so the pixels
+/// around it only need their difference from it in f64.
+pub struct Reference {
+    /// Each iterate from `z_0 = 0`, until it escaped or reached the iteration limit.
+    orbit: Vec<Complex<f64>>,
+    /// How many iterations every pixel can skip by evaluating the series.
+    skip: usize,
+    /// Coefficients of `dc`, `dc^2` and `dc^3` in the series for the offset after `skip`
+    /// iterations.
+    series: [Complex<f64>; 3]
+}
+
+impl Reference {
+    pub fn new(ctx: &FractalContext, maxiter: usize) -> Reference {
+        let bits = fixed::bits_for(ctx.scale);
+        let (cr, ci) = ctx.pan.exact();
+        let (mut zr, mut zi) = (Fixed::from_f64(0., bits), Fixed::from_f64(0., bits));
+
+        let mut orbit = vec![Complex::new(0., 0.)];
+        for _ in 0..maxiter {
+            let zri = &zr * &zi;
+            zr = &(&(&zr * &zr) - &(&zi * &zi)) + cr;
+            zi = &(&zri + &zri) + ci;
+
+            let z = Complex::new(zr.to_f64(), zi.to_f64());
+            orbit.push(z);
+            if z.norm_sqr() > 4. {
+                break;
+            }
+        }
+
+        let (skip, series) = approximate(ctx, &orbit);
+        Reference { orbit, skip, series }
+    }
+}
+
+/// Finds how far the cubic series for the offset `d_n` in `dc` follows the exact offsets:
+/// from `d' = 2Zd + d^2 + dc`, the coefficients go `A' = 2ZA + 1`, `B' = 2ZB + A^2` and
+/// `C' = 2ZC + 2AB`. It is checked against the view's corners, the pixels furthest out and
+/// so the first to leave it, stopping before any of them drifts past the tolerance.
+fn approximate(ctx: &FractalContext, orbit: &[Complex<f64>]) -> (usize, [Complex<f64>; 3]) {
+    let (width, height) = ctx.dimensions;
+    let probes = [0, width - 1, (height - 1) * width, width * height - 1].map(|i| ctx.offset(i));
+    let mut exact = [Complex::new(0., 0.); 4];
+    let zero = Complex::new(0., 0.);
+    let (mut a, mut b, mut c) = (zero, zero, zero);
+
+    // the reference's last iterate may have escaped, where there's nothing to follow
+    for (n, &z) in orbit.iter().enumerate().take(orbit.len().saturating_sub(2)) {
+        let next = (z * a * 2. + 1., z * b * 2. + a * a, z * c * 2. + a * b * 2.);
+        let mut stepped = exact;
+        for (d, dc) in stepped.iter_mut().zip(probes) {
+            *d = z * *d * 2. + *d * *d + dc;
+        }
+
+        let valid = stepped.iter().zip(probes).all(|(&d, dc)| {
+            let estimate = ((next.2 * dc + next.1) * dc + next.0) * dc;
+            (estimate - d).norm() <= SERIES_TOLERANCE * d.norm()
+        });
+        if !valid {
+            return (n, [a, b, c]);
+        }
+        (a, b, c) = next;
+        exact = stepped;
+    }
+    (orbit.len().saturating_sub(2), [a, b, c])
+}
+
+/// The Mandelbrot kernel for views too deep for f64, iterating each pixel's offset from the
+/// reference orbit: with `z = Z + d` and `c = C + dc`, `d' = 2Zd + d^2 + dc`. Pixels start
+/// from the series approximation rather than iterating the part of the orbit they all
+/// share. Where the pixel's orbit passes closer to 0 than its offset would keep accurate,
+/// or the reference ends, it is rebased onto the start of the reference, so one reference
+/// serves the whole view wherever it is.
+pub fn quadratic(ctx: &FractalContext, reference: &Reference, maxiter: usize, pixels: &[usize]) -> Vec<Sample> {
+    let orbit = &reference.orbit;
+    let skip = reference.skip.min(maxiter);
+    let [a, b, c] = reference.series;
+
+    pixels.par_iter().map(|&i| {
+        let dc = ctx.offset(i);
+        let (mut d, mut m) = (((c * dc + b) * dc + a) * dc, skip);
+        // dz/dc of the pixel's orbit is that of its offset, so the series' derivative
+        let mut dz = (c * dc * 3. + b * 2.) * dc + a;
+        let mut z = orbit[m] + d;
+        let mut escape = None;
+
+        for n in skip..maxiter {
+            dz = z * dz * 2. + 1.;
+            d = orbit[m] * d * 2. + d * d + dc;
+            m += 1;
+            z = orbit[m] + d;
+            if z.norm_sqr() > 4. {
+                escape = Some(n);
+                break;
+            }
+            if z.norm_sqr() < d.norm_sqr() || m == orbit.len() - 1 {
+                (d, m) = (z, 0);
+            }
+        }
+        simd::quadratic_sample(z, dz, escape, ctx.point(i))
+    }).collect()
+}
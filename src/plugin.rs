@@ -0,0 +1,171 @@
+use std::ffi::{c_char, CStr};
+use std::path::Path;
+use std::sync::{Arc, Mutex, Once};
+
+use libloading::{Library, Symbol};
+use num_complex::Complex;
+
+use crate::keys;
+
+/// Version of `Plugin` this build takes, which a plugin's has to match.
+pub const ABI: u32 = 1;
+
+/// A formula given by name on the command line like the built-in ones, iterated from z = 0 at
+/// each point c of the view until |z| passes `bailout`, and colored by its orbit the same.
+pub trait Kernel: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn step(&self, z: Complex<f64>, c: Complex<f64>) -> Complex<f64>;
+
+    fn bailout(&self) -> f64 {
+        2.
+    }
+}
+
+/// What a plugin's `extern "C" fn fractalv_plugin() -> *const Plugin` gives, to be there for
+/// as long as the library's loaded. A plugin is a shared library in the `plugins` directory
+/// beside `keys.toml`, each loaded once when a fractal that isn't built in is first asked for.
+#[repr(C)]
+pub struct Plugin {
+    /// `ABI` as the plugin was built.
+    pub abi: u32,
+    /// Nul-terminated name of the fractal on the command line.
+    pub name: *const c_char,
+    pub bailout: f64,
+    pub step: extern "C" fn(z: Complex<f64>, c: Complex<f64>) -> Complex<f64>
+}
+
+/// A plugin's kernel, with the library it's in kept loaded.
+struct Loaded {
+    name: String,
+    bailout: f64,
+    step: extern "C" fn(Complex<f64>, Complex<f64>) -> Complex<f64>,
+    _library: Library
+}
+
+impl Kernel for Loaded {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn step(&self, z: Complex<f64>, c: Complex<f64>) -> Complex<f64> {
+        (self.step)(z, c)
+    }
+
+    fn bailout(&self) -> f64 {
+        self.bailout
+    }
+}
+
+static KERNELS: Mutex<Vec<Arc<dyn Kernel>>> = Mutex::new(Vec::new());
+static LOAD: Once = Once::new();
+
+/// Makes `kernel` a fractal by its name, for programs using the renderer to add their own
+/// without a plugin. Kernels registered first win out over later ones of the same name.
+pub fn register(kernel: Arc<dyn Kernel>) {
+    KERNELS.lock().unwrap().push(kernel);
+}
+
+/// The kernel named `name`, loading the plugins the first time.
+pub fn find(name: &str) -> Option<Arc<dyn Kernel>> {
+    LOAD.call_once(load_all);
+    KERNELS.lock().unwrap().iter().find(|kernel| kernel.name() == name).cloned()
+}
+
+/// Names of the kernels there are, plugins and all.
+pub fn names() -> Vec<String> {
+    LOAD.call_once(load_all);
+    KERNELS.lock().unwrap().iter().map(|kernel| kernel.name().to_string()).collect()
+}
+
+/// Registers the kernel of each plugin there is, saying which couldn't be loaded and why.
+fn load_all() {
+    let dir = match keys::config_path("plugins") {
+        Some(dir) if dir.is_dir() => dir,
+        _ => return
+    };
+    let entries = match std::fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(err) => {
+            println!("{}: {}", dir.display(), err);
+            return;
+        }
+    };
+    let mut paths: Vec<_> = entries.filter_map(|entry| Some(entry.ok()?.path())).collect();
+    paths.retain(|path| path.extension().is_some_and(|extension| extension == std::env::consts::DLL_EXTENSION));
+    paths.sort();
+    for path in paths {
+        match load(&path) {
+            Ok(kernel) => register(Arc::new(kernel)),
+            Err(err) => println!("{}", err)
+        }
+    }
+}
+
+/// The kernel of the plugin at `path`, or why there isn't one.
+fn load(path: &Path) -> Result<Loaded, String> {
+    // SAFETY: a plugin's taken on trust to be a library that can be loaded and to export
+    // `fractalv_plugin` as `Plugin` says, pointing at a `Plugin` it keeps while it's loaded
+    let library = unsafe { Library::new(path) }.map_err(|err| err.to_string())?;
+    let error = |err: &str| format!("{}: {}", path.display(), err);
+    unsafe {
+        let plugin: Symbol<extern "C" fn() -> *const Plugin> = library.get(b"fractalv_plugin\0").map_err(|err| error(&err.to_string()))?;
+        let plugin = plugin().as_ref().ok_or_else(|| error("fractalv_plugin gave no plugin"))?;
+        if plugin.abi != ABI {
+            return Err(error(&format!("built for plugin ABI {}, fractalv has {}", plugin.abi, ABI)));
+        }
+        if plugin.name.is_null() {
+            return Err(error("plugin has no name"));
+        }
+        let name = CStr::from_ptr(plugin.name).to_str().map_err(|_| error("plugin's name isn't UTF-8"))?.to_string();
+        if !(plugin.bailout.is_finite() && plugin.bailout > 0.) {
+            return Err(error(&format!("bailout {} isn't a positive number", plugin.bailout)));
+        }
+        let (bailout, step) = (plugin.bailout, plugin.step);
+        Ok(Loaded { name, bailout, step, _library: library })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use num_complex::Complex;
+
+    use super::{find, names, register, Kernel};
+    use crate::{parse_fractal, Fractal};
+
+    struct Square(&'static str, f64);
+
+    impl Kernel for Square {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn step(&self, z: Complex<f64>, c: Complex<f64>) -> Complex<f64> {
+            z * z + c
+        }
+
+        fn bailout(&self) -> f64 {
+            self.1
+        }
+    }
+
+    #[test]
+    fn finds_registered_kernels_first_one_first() {
+        register(Arc::new(Square("test-square", 3.)));
+        register(Arc::new(Square("test-square", 5.)));
+
+        let kernel = find("test-square").unwrap();
+        assert_eq!(kernel.bailout(), 3.);
+        assert_eq!(kernel.step(Complex::new(1., 1.), Complex::new(0.5, 0.)), Complex::new(0.5, 2.));
+        assert!(names().iter().any(|name| name == "test-square"));
+        assert!(find("test-no-such-kernel").is_none());
+
+        let spec = ["", "test-square", "50"].map(String::from);
+        match parse_fractal(&spec) {
+            Some(Fractal::Plugin(kernel, 50)) => assert_eq!(kernel.name(), "test-square"),
+            _ => panic!("test-square isn't a fractal")
+        }
+    }
+}
@@ -0,0 +1,450 @@
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Length of the runs deflate can copy from earlier in the data, and how far back.
+const MAX_MATCH: usize = 258;
+const WINDOW: usize = 32768;
+const HASH_BITS: u32 = 15;
+
+const LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145,
+    8193, 12289, 16385, 24577
+];
+const DISTANCE_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+
+/// Writes `pixels`, `0xRRGGBB` a row at a time, as an RGB PNG with `text` in it as
+/// `(keyword, text)` pairs.
+pub fn save(path: &Path, pixels: &[u32], dimensions: (usize, usize), text: &[(&str, String)]) -> Result<(), String> {
+    std::fs::write(path, encode(pixels, dimensions, text)).map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+pub fn encode(pixels: &[u32], dimensions: (usize, usize), text: &[(&str, String)]) -> Vec<u8> {
+    let mut png = Vec::new();
+    // writing to memory can't fail
+    let _ = Writer::new(&mut png, dimensions).and_then(|mut writer| {
+        writer.rows(pixels)?;
+        writer.finish(text)
+    });
+    png
+}
+
+/// The `(keyword, text)` pairs of the text chunks in the PNG at `path`.
+pub fn text(path: &Path) -> Result<Vec<(String, String)>, String> {
+    let data = std::fs::read(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    let mut rest = data.strip_prefix(b"\x89PNG\r\n\x1a\n").ok_or(format!("{}: not a PNG", path.display()))?;
+    let mut text = Vec::new();
+    while rest.len() >= 12 {
+        let length = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+        let chunk = rest.get(8..8 + length).ok_or(format!("{}: cut short", path.display()))?;
+        if &rest[4..8] == b"tEXt" {
+            // Latin-1, which maps straight onto the first 256 code points
+            let (keyword, value) = chunk.split_at(chunk.iter().position(|&byte| byte == 0).unwrap_or(chunk.len()));
+            let latin = |bytes: &[u8]| bytes.iter().map(|&byte| byte as char).collect::<String>();
+            text.push((latin(keyword), latin(value.get(1..).unwrap_or_default())));
+        }
+        rest = rest.get(12 + length..).unwrap_or_default();
+    }
+    Ok(text)
+}
+
+/// Compressed data is written out in chunks of about this many bytes.
+const CHUNK: usize = 1 << 16;
+
+/// A PNG written a few rows at a time, so an image never has to be in memory whole.
+pub struct Writer<W: Write> {
+    out: W,
+    width: usize,
+    /// The last row written, which the next is filtered against.
+    above: Vec<u8>,
+    deflate: Deflate
+}
+
+impl<W: Write> Writer<W> {
+    pub fn new(mut out: W, (width, height): (usize, usize)) -> io::Result<Writer<W>> {
+        out.write_all(b"\x89PNG\r\n\x1a\n")?;
+        let mut header = Vec::new();
+        header.extend((width as u32).to_be_bytes());
+        header.extend((height as u32).to_be_bytes());
+        // 8 bits a channel, truecolor, no interlacing
+        header.extend([8, 2, 0, 0, 0]);
+        chunk(&mut out, b"IHDR", &header)?;
+        Ok(Writer { out, width, above: vec![0; width * 3], deflate: Deflate::new() })
+    }
+
+    /// Adds the next whole rows of the image.
+    pub fn rows(&mut self, pixels: &[u32]) -> io::Result<()> {
+        for row in pixels.chunks(self.width.max(1)) {
+            let row: Vec<u8> = row.iter().flat_map(|&px| [(px >> 16) as u8, (px >> 8) as u8, px as u8]).collect();
+            let (kind, filtered) = filter(&row, &self.above);
+            self.deflate.data.push(kind);
+            self.deflate.data.extend(filtered);
+            self.above = row;
+            self.deflate.compress(false);
+            if self.deflate.bits.bytes.len() >= CHUNK {
+                chunk(&mut self.out, b"IDAT", &std::mem::take(&mut self.deflate.bits.bytes))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Ends the image with `text` chunks after it, as `(keyword, text)` pairs.
+    pub fn finish(mut self, text: &[(&str, String)]) -> io::Result<()> {
+        self.deflate.compress(true);
+        chunk(&mut self.out, b"IDAT", &self.deflate.finish())?;
+        for (keyword, value) in text {
+            let data: Vec<u8> = keyword.chars().chain(std::iter::once('\0')).chain(value.chars()).map(|c| u8::try_from(c).unwrap_or(b'?')).collect();
+            chunk(&mut self.out, b"tEXt", &data)?;
+        }
+        chunk(&mut self.out, b"IEND", &[])?;
+        self.out.flush()
+    }
+}
+
+fn chunk(out: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    let mut crc = crc32(!0, kind);
+    crc = crc32(crc, data);
+    out.write_all(kind)?;
+    out.write_all(data)?;
+    out.write_all(&(!crc).to_be_bytes())
+}
+
+/// `row` of RGB bytes behind whichever filter leaves the smallest differences from `above`,
+/// which tends to compress best, and the filter's number.
+fn filter(row: &[u8], above: &[u8]) -> (u8, Vec<u8>) {
+    let candidates: [Vec<u8>; 4] = [0, 1, 2, 4].map(|kind| {
+        (0..row.len())
+            .map(|i| {
+                let (left, up, corner) = (if i >= 3 { row[i - 3] } else { 0 }, above[i], if i >= 3 { above[i - 3] } else { 0 });
+                let predicted = match kind {
+                    0 => 0,
+                    1 => left,
+                    2 => up,
+                    _ => paeth(left, up, corner)
+                };
+                row[i].wrapping_sub(predicted)
+            })
+            .collect()
+    });
+    let cost = |filtered: &Vec<u8>| filtered.iter().map(|&byte| (byte as i8).unsigned_abs() as usize).sum::<usize>();
+    let best = (0..4).min_by_key(|&n| cost(&candidates[n])).unwrap_or(0);
+    ([0, 1, 2, 4][best], candidates.into_iter().nth(best).unwrap_or_default())
+}
+
+fn paeth(left: u8, up: u8, corner: u8) -> u8 {
+    let estimate = left as i16 + up as i16 - corner as i16;
+    let (a, b, c) = ((estimate - left as i16).abs(), (estimate - up as i16).abs(), (estimate - corner as i16).abs());
+    if a <= b && a <= c {
+        left
+    } else if b <= c {
+        up
+    } else {
+        corner
+    }
+}
+
+/// A zlib stream deflated with the fixed Huffman codes and greedy matching, fed a little at
+/// a time.
+struct Deflate {
+    /// Data not yet compressed, after as much of what was as matches can reach back into.
+    data: Vec<u8>,
+    /// Position in the whole stream of `data[0]`.
+    offset: usize,
+    /// Index into `data` of the first byte not yet compressed.
+    next: usize,
+    /// Last stream position each hash of three bytes was seen at.
+    heads: Vec<usize>,
+    adler: (u32, u32),
+    bits: Bits
+}
+
+impl Deflate {
+    fn new() -> Deflate {
+        let mut bits = Bits { bytes: vec![0x78, 0x01], buffer: 0, count: 0 };
+        // one final block with the fixed codes
+        bits.write(1, 1);
+        bits.write(1, 2);
+        Deflate { data: Vec::new(), offset: 0, next: 0, heads: vec![usize::MAX; 1 << HASH_BITS], adler: (1, 0), bits }
+    }
+
+    /// Compresses what's been added, short of the last bytes a match could still carry on
+    /// into unless this is the `last` of it.
+    fn compress(&mut self, last: bool) {
+        let data = &self.data;
+        let end = if last { data.len() } else { data.len().saturating_sub(MAX_MATCH) };
+        let hash = |i: usize| ((u32::from(data[i]) << 16 | u32::from(data[i + 1]) << 8 | u32::from(data[i + 2])).wrapping_mul(0x9E3779B1) >> (32 - HASH_BITS)) as usize;
+        let mut i = self.next;
+        while i < end {
+            let mut found = (0, 0);
+            if i + 3 <= data.len() {
+                let slot = hash(i);
+                let start = self.heads[slot];
+                self.heads[slot] = self.offset + i;
+                if start != usize::MAX && self.offset + i - start <= WINDOW {
+                    let start = start - self.offset;
+                    let length = (0..MAX_MATCH.min(data.len() - i)).take_while(|&n| data[start + n] == data[i + n]).count();
+                    if length >= 3 {
+                        found = (length, i - start);
+                    }
+                }
+            }
+            match found {
+                (0, _) => {
+                    literal(&mut self.bits, data[i] as u16);
+                    i += 1;
+                }
+                (length, distance) => {
+                    let code = LENGTH_BASE.iter().rposition(|&base| base as usize <= length).unwrap_or(0);
+                    literal(&mut self.bits, 257 + code as u16);
+                    self.bits.write((length - LENGTH_BASE[code] as usize) as u32, LENGTH_EXTRA[code]);
+                    let code = DISTANCE_BASE.iter().rposition(|&base| base as usize <= distance).unwrap_or(0);
+                    self.bits.write(reverse(code as u32, 5), 5);
+                    self.bits.write((distance - DISTANCE_BASE[code] as usize) as u32, DISTANCE_EXTRA[code]);
+                    // later matches can start inside this one too
+                    for at in i + 1..(i + length).min(data.len().saturating_sub(2)) {
+                        self.heads[hash(at)] = self.offset + at;
+                    }
+                    i += length;
+                }
+            }
+        }
+
+        self.adler = adler32(self.adler, &self.data[self.next..i]);
+        // all that has to stay is what matches can reach back into
+        let keep = i.saturating_sub(WINDOW);
+        self.data.drain(..keep);
+        self.offset += keep;
+        self.next = i - keep;
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        literal(&mut self.bits, 256);
+        let (a, b) = self.adler;
+        let mut bytes = self.bits.finish();
+        bytes.extend((b << 16 | a).to_be_bytes());
+        bytes
+    }
+}
+
+/// Writes a literal, length or end symbol in its fixed Huffman code.
+fn literal(bits: &mut Bits, symbol: u16) {
+    let (code, length) = match symbol {
+        0..=143 => (0x30 + symbol as u32, 8),
+        144..=255 => (0x190 + symbol as u32 - 144, 9),
+        256..=279 => (symbol as u32 - 256, 7),
+        _ => (0xC0 + symbol as u32 - 280, 8)
+    };
+    bits.write(reverse(code, length), length);
+}
+
+/// Huffman codes go out most significant bit first, unlike everything else.
+fn reverse(code: u32, length: u8) -> u32 {
+    code.reverse_bits() >> (32 - length as u32)
+}
+
+/// Packs values into bytes from the least significant bit up, the way deflate and GIF's LZW
+/// want them.
+#[derive(Default)]
+pub struct Bits {
+    pub bytes: Vec<u8>,
+    buffer: u32,
+    count: u8
+}
+
+impl Bits {
+    pub fn write(&mut self, value: u32, length: u8) {
+        self.buffer |= value << self.count;
+        self.count += length;
+        while self.count >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+/// Carries `crc`, started at all ones and inverted at the end, on over `data`.
+fn crc32(mut crc: u32, data: &[u8]) -> u32 {
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB88320 } else { crc >> 1 };
+        }
+    }
+    crc
+}
+
+/// Carries the two sums of an Adler-32 checksum on over `data`.
+fn adler32((mut a, mut b): (u32, u32), data: &[u8]) -> (u32, u32) {
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        (a, b) = (a % 65521, b % 65521);
+    }
+    (a, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{adler32, crc32, encode, Bits, Deflate, LENGTH_BASE, LENGTH_EXTRA, DISTANCE_BASE, DISTANCE_EXTRA};
+
+    /// Reads bits back the way `Bits` packs them.
+    struct Reader<'a> {
+        bytes: &'a [u8],
+        at: usize
+    }
+
+    impl Reader<'_> {
+        fn bits(&mut self, length: u8) -> usize {
+            let mut value = 0;
+            for n in 0..length {
+                value |= ((self.bytes[self.at / 8] >> (self.at % 8)) as usize & 1) << n;
+                self.at += 1;
+            }
+            value
+        }
+
+        /// A Huffman code `length` bits long, most significant bit first.
+        fn code(&mut self, code: usize, length: u8) -> usize {
+            (0..length).fold(code, |code, _| code << 1 | self.bits(1))
+        }
+    }
+
+    /// Inflates a zlib stream of fixed Huffman blocks, checking its Adler-32.
+    fn inflate(stream: &[u8]) -> Vec<u8> {
+        assert_eq!(u16::from_be_bytes([stream[0], stream[1]]) % 31, 0);
+        let mut reader = Reader { bytes: &stream[2..], at: 0 };
+        let mut out: Vec<u8> = Vec::new();
+        loop {
+            let last = reader.bits(1);
+            assert_eq!(reader.bits(2), 1, "only fixed codes are written");
+            loop {
+                let symbol = match reader.code(0, 7) {
+                    code @ 0..=0x17 => 256 + code,
+                    code => match reader.code(code, 1) {
+                        code @ 0x30..=0xBF => code - 0x30,
+                        code @ 0xC0..=0xC7 => 280 + code - 0xC0,
+                        code => 144 + reader.code(code, 1) - 0x190
+                    }
+                };
+                match symbol {
+                    0..=255 => out.push(symbol as u8),
+                    256 => break,
+                    _ => {
+                        let code = symbol - 257;
+                        let length = LENGTH_BASE[code] as usize + reader.bits(LENGTH_EXTRA[code]);
+                        let code = reader.code(0, 5);
+                        let distance = DISTANCE_BASE[code] as usize + reader.bits(DISTANCE_EXTRA[code]);
+                        for _ in 0..length {
+                            out.push(out[out.len() - distance]);
+                        }
+                    }
+                }
+            }
+            if last == 1 {
+                break;
+            }
+        }
+        let trailer = &stream[2 + reader.at.div_ceil(8)..];
+        let (a, b) = adler32((1, 0), &out);
+        assert_eq!(trailer, (b << 16 | a).to_be_bytes());
+        out
+    }
+
+    #[test]
+    fn checksums_match_known_answers() {
+        assert_eq!(!crc32(!0, b"123456789"), 0xCBF43926);
+        assert_eq!(!crc32(crc32(!0, b"1234"), b"56789"), 0xCBF43926);
+        let (a, b) = adler32((1, 0), b"Wikipedia");
+        assert_eq!(b << 16 | a, 0x11E60398);
+        // past the 5552 bytes the sums are reduced after
+        let (a, b) = adler32((1, 0), &[0xFF; 10000]);
+        let sums = (0..10000u64).fold((1, 0), |(a, b), _| (a + 0xFF, b + a + 0xFF));
+        assert_eq!((a as u64, b as u64), (sums.0 % 65521, sums.1 % 65521));
+    }
+    /// Deflates `pieces` one `compress(false)` after another, the way rows are fed in.
+    fn deflate(pieces: &[&[u8]]) -> Vec<u8> {
+        let mut deflate = Deflate::new();
+        for piece in pieces {
+            deflate.data.extend_from_slice(piece);
+            deflate.compress(false);
+        }
+        deflate.compress(true);
+        deflate.finish()
+    }
+
+    #[test]
+    fn inflates_back() {
+        assert_eq!(inflate(&deflate(&[])), b"");
+        assert_eq!(inflate(&deflate(&[b"ab"])), b"ab");
+
+        // noise with repeats of all lengths, far enough apart to drain the window between
+        let mut seed = 1u32;
+        let mut data = Vec::new();
+        while data.len() < 200_000 {
+            seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+            if seed >> 30 == 0 && data.len() > 1000 {
+                let (length, distance) = ((seed >> 8) as usize % 600 + 1, (seed >> 2) as usize % 1000 + 1);
+                for _ in 0..length {
+                    data.push(data[data.len() - distance]);
+                }
+            } else {
+                data.push((seed >> 24) as u8);
+            }
+        }
+        assert_eq!(inflate(&deflate(&[&data])), data);
+        let pieces: Vec<&[u8]> = data.chunks(777).collect();
+        assert_eq!(inflate(&deflate(&pieces)), data);
+    }
+
+    #[test]
+    fn matches_across_the_hold_back() {
+        // the second run matches the first right where the first call has to stop short
+        let run: Vec<u8> = (0..=255).chain(0..100).map(|n: u32| (n * 7) as u8).collect();
+        let first = [&run[..], &run[..200]].concat();
+        let pieces = [&first[..], &run[200..], &run[..]];
+        let stream = deflate(&pieces);
+        assert_eq!(inflate(&stream), pieces.concat());
+        // a whole run's worth fits in a few matches rather than going out byte by byte
+        assert!(stream.len() < run.len() + 40, "{} bytes", stream.len());
+    }
+
+    #[test]
+    fn packs_bits_from_the_bottom() {
+        let mut bits = Bits::default();
+        bits.write(0b101, 3);
+        bits.write(0x1FF, 9);
+        assert_eq!(bits.finish(), [0xFD, 0x0F]);
+    }
+
+    #[test]
+    fn chunks_have_their_checksums() {
+        let png = encode(&[0xFF0000, 0x00FF00, 0x0000FF, 0xFFFFFF], (2, 2), &[("Comment", "café".to_string())]);
+        let mut rest = &png[8..];
+        let (mut kinds, mut idat) = (Vec::new(), Vec::new());
+        while !rest.is_empty() {
+            let length = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+            let crc = u32::from_be_bytes([rest[8 + length], rest[9 + length], rest[10 + length], rest[11 + length]]);
+            assert_eq!(!crc32(!0, &rest[4..8 + length]), crc);
+            if &rest[4..8] == b"IDAT" {
+                idat.extend_from_slice(&rest[8..8 + length]);
+            }
+            kinds.push(rest[4..8].to_vec());
+            rest = &rest[12 + length..];
+        }
+        assert_eq!(kinds, [&b"IHDR"[..], b"IDAT", b"tEXt", b"IEND"]);
+        // a filter byte then three per pixel for each row
+        assert_eq!(inflate(&idat).len(), 2 * (1 + 2 * 3));
+    }
+}
@@ -0,0 +1,77 @@
+use crate::{simd, FractalContext, Seed};
+use crate::orbit::Sample;
+
+/// A backend that can take over the quadratic `z^2 + c` kernel behind the Mandelbrot and
+/// Julia sets, the ones worth offloading. Every other kernel, and any render needing orbit
+/// tracking, runs on the scalar CPU path.
+pub trait Renderer {
+    fn name(&self) -> &'static str;
+
+    /// Whether renders should refine progressively; devices that finish a whole frame
+    /// quickly render it in one pass.
+    fn progressive(&self) -> bool {
+        true
+    }
+
+    /// Iterates the given pixels of the current view, or returns `None` to leave them to the
+    /// scalar kernel.
+    fn quadratic(&self, ctx: &FractalContext, seed: Seed, maxiter: usize, pixels: &[usize]) -> Option<Vec<Sample>>;
+}
+
+pub struct Cpu {
+    pub simd: bool
+}
+
+impl Renderer for Cpu {
+    fn name(&self) -> &'static str {
+        if self.simd { "cpu (avx2)" } else { "cpu" }
+    }
+
+    fn quadratic(&self, ctx: &FractalContext, seed: Seed, maxiter: usize, pixels: &[usize]) -> Option<Vec<Sample>> {
+        self.simd.then(|| simd::quadratic(ctx, seed, maxiter, pixels))
+    }
+}
+
+/// Picks the requested backend, falling back to the CPU when it is unavailable or not
+/// compiled in.
+pub fn select(gpu: bool, opencl: bool, simd: bool) -> Box<dyn Renderer + Send + Sync> {
+    if gpu {
+        #[cfg(feature = "gpu")]
+        match crate::gpu::Gpu::new() {
+            Some(gpu) => return Box::new(gpu),
+            None => println!("No GPU adapter found, rendering on the CPU")
+        }
+        #[cfg(not(feature = "gpu"))]
+        println!("Built without the gpu feature, rendering on the CPU");
+    }
+    if opencl {
+        #[cfg(feature = "opencl")]
+        match crate::opencl::OpenCl::new() {
+            Ok(opencl) => return Box::new(opencl),
+            Err(err) => println!("OpenCL unavailable ({}), rendering on the CPU", err)
+        }
+        #[cfg(not(feature = "opencl"))]
+        println!("Built without the opencl feature, rendering on the CPU");
+    }
+    Box::new(Cpu { simd: simd && simd::available() })
+}
+
+/// Converts the device kernels' output for the given pixels into samples. Devices write eight
+/// floats per pixel: the escape iteration (negative if none), the final z, padding and the
+/// final derivative.
+#[cfg(any(feature = "gpu", feature = "opencl"))]
+pub fn unpack(ctx: &FractalContext, seed: Seed, values: &[f32], pixels: &[usize]) -> Vec<Sample> {
+    use num_complex::Complex;
+
+    pixels
+        .iter()
+        .map(|&i| {
+            let v = &values[i * 8..i * 8 + 8];
+            let (_, c) = seed.start(ctx.point(i));
+            let z = Complex::new(v[1] as f64, v[2] as f64);
+            let dz = Complex::new(v[4] as f64, v[5] as f64);
+            let escape = (v[0] >= 0.).then_some(v[0] as usize);
+            simd::quadratic_sample(z, dz, escape, c)
+        })
+        .collect()
+}
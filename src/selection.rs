@@ -0,0 +1,64 @@
+use minifb::{MouseButton, MouseMode, Window};
+
+use fractv::worker::Request;
+
+/// Rectangles narrower or shorter than this are taken as a stray click.
+const MIN_SIZE: f32 = 4.;
+
+/// A rectangle dragged out with the right mouse button, which the view zooms to fit once
+/// it's released.
+#[derive(Default)]
+pub struct Selection {
+    corners: Option<((f32, f32), (f32, f32))>
+}
+
+impl Selection {
+    /// Follows this frame's right mouse button, zooming `request` to the rectangle when it's
+    /// released.
+    pub fn handle(&mut self, window: &Window, request: &mut Request) {
+        if window.get_mouse_down(MouseButton::Right) {
+            if let Some(position) = window.get_mouse_pos(MouseMode::Clamp) {
+                self.corners = Some(match self.corners {
+                    Some((start, _)) => (start, position),
+                    None => (position, position)
+                });
+            }
+            return;
+        }
+
+        if let Some(((x0, y0), (x1, y1))) = self.corners.take() {
+            let size = ((x1 - x0).abs(), (y1 - y0).abs());
+            if size.0 >= MIN_SIZE && size.1 >= MIN_SIZE {
+                let origin = (x0.min(x1) as f64, y0.min(y1) as f64);
+                request.zoom_to(origin, (size.0 as f64, size.1 as f64));
+            }
+        }
+    }
+
+    pub fn active(&self) -> bool {
+        self.corners.is_some()
+    }
+
+    /// Outlines the rectangle by inverting the pixels under it, so it shows on any coloring.
+    pub fn draw(&self, frame: &mut [u32], (width, height): (usize, usize)) {
+        let ((x0, y0), (x1, y1)) = match self.corners {
+            Some(corners) => corners,
+            None => return
+        };
+        if width == 0 || height == 0 {
+            return;
+        }
+        let clamp = |v: f32, len: usize| (v.max(0.) as usize).min(len - 1);
+        let (left, right) = (clamp(x0.min(x1), width), clamp(x0.max(x1), width));
+        let (top, bottom) = (clamp(y0.min(y1), height), clamp(y0.max(y1), height));
+
+        let mut outline: Vec<usize> = (left..=right).flat_map(|x| [x + top * width, x + bottom * width]).collect();
+        outline.extend((top + 1..bottom).flat_map(|y| [left + y * width, right + y * width]));
+        // corners and degenerate edges would otherwise be inverted twice
+        outline.sort_unstable();
+        outline.dedup();
+        for i in outline {
+            frame[i] ^= 0xFFFFFF;
+        }
+    }
+}
@@ -0,0 +1,355 @@
+use rayon::prelude::*;
+
+use num_complex::Complex;
+
+use crate::{FractalContext, Seed};
+use crate::orbit::{Sample, CYCLE_TOLERANCE, HISTORY, PERIOD_TOLERANCE};
+
+const LANES: usize = 4;
+/// An AVX2 register holds twice as many f32 lanes.
+const SINGLE_LANES: usize = 8;
+/// Scale below which pixels are hundreds of f32 ulps apart even at the bailout radius, so
+/// single precision places them as well as double does. Devices iterating in f32 leave deeper
+/// views to the CPU.
+pub const SINGLE_SCALE: f64 = 1e4;
+
+/// Whether the vector kernels can run on this CPU.
+pub fn available() -> bool {
+    #[cfg(target_arch = "x86_64")]
+    {
+        is_x86_feature_detected!("avx2")
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        false
+    }
+}
+
+/// What one lane reports when its orbit finishes, by escaping, closing a cycle or reaching
+/// the iteration limit: the final iterate and derivative, and the escape iteration if it
+/// left the bailout radius.
+#[derive(Clone, Copy, Default)]
+struct Lane {
+    z: Complex<f64>,
+    dz: Complex<f64>,
+    escape: Option<usize>
+}
+
+/// `z^2 + c` with derivative tracking, iterating four pixels at once with AVX2, or eight in
+/// f32 at zooms shallow enough for it. Only the statistics the plain colorings need are
+/// produced, so it is only used when orbit tracking is off.
+pub fn quadratic(ctx: &FractalContext, seed: Seed, maxiter: usize, pixels: &[usize]) -> Vec<Sample> {
+    let (dz0, dc) = match seed {
+        Seed::Parameter => (Complex::new(0., 0.), Complex::new(1., 0.)),
+        Seed::Julia(_) => (Complex::new(1., 0.), Complex::new(0., 0.))
+    };
+
+    if ctx.scale < SINGLE_SCALE {
+        chunked(ctx, seed, pixels, |z, c| iterate_single(z, c, dz0, dc, maxiter))
+    } else {
+        chunked(ctx, seed, pixels, |z, c| iterate(z, c, dz0, dc, maxiter))
+    }
+}
+
+/// Runs `iterate` over the pixels `N` at a time.
+fn chunked<const N: usize, F>(ctx: &FractalContext, seed: Seed, pixels: &[usize], iterate: F) -> Vec<Sample>
+where
+    F: Fn([Complex<f64>; N], [Complex<f64>; N]) -> [Lane; N] + Sync
+{
+    pixels
+        .par_chunks(N)
+        .flat_map_iter(|chunk| {
+            // a short last chunk leaves lanes iterating from the origin, which are discarded
+            let (mut z, mut c) = ([Complex::new(0., 0.); N], [Complex::new(0., 0.); N]);
+            for (lane, &i) in chunk.iter().enumerate() {
+                (z[lane], c[lane]) = seed.start(ctx.point(i));
+            }
+
+            let lanes = iterate(z, c);
+            lanes
+                .into_iter()
+                .zip(c)
+                .take(chunk.len())
+                .map(|(lane, c)| quadratic_sample(lane.z, lane.dz, lane.escape, c))
+        })
+        .collect()
+}
+
+fn iterate(
+    z: [Complex<f64>; LANES],
+    c: [Complex<f64>; LANES],
+    dz0: Complex<f64>,
+    dc: Complex<f64>,
+    maxiter: usize
+) -> [Lane; LANES] {
+    #[cfg(target_arch = "x86_64")]
+    {
+        // SAFETY: `quadratic` is only selected when `available` detected AVX2
+        unsafe { avx2::iterate(z, c, dz0, dc, maxiter) }
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        let _ = (z, c, dz0, dc, maxiter);
+        unreachable!("vector kernels are only selected when available")
+    }
+}
+
+fn iterate_single(
+    z: [Complex<f64>; SINGLE_LANES],
+    c: [Complex<f64>; SINGLE_LANES],
+    dz0: Complex<f64>,
+    dc: Complex<f64>,
+    maxiter: usize
+) -> [Lane; SINGLE_LANES] {
+    #[cfg(target_arch = "x86_64")]
+    {
+        // SAFETY: as for `iterate`
+        unsafe { avx2::iterate_single(z, c, dz0, dc, maxiter) }
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        let _ = (z, c, dz0, dc, maxiter);
+        unreachable!("vector kernels are only selected when available")
+    }
+}
+
+/// Builds the sample for a `z^2 + c` orbit from its final iterate and derivative, for kernels
+/// that iterate outside `Orbit`.
+pub fn quadratic_sample(z: Complex<f64>, dz: Complex<f64>, escape: Option<usize>, c: Complex<f64>) -> Sample {
+    let mut sample = Sample { trap: f64::INFINITY, ..Sample::default() };
+
+    match escape {
+        Some(i) => {
+            let modulus = z.norm();
+            sample.escape = Some((i, modulus));
+            sample.angle = z.arg();
+            sample.distance = modulus * modulus.ln() / dz.norm();
+            let u = z / dz;
+            sample.normal = u / u.norm();
+        }
+        None => {
+            sample.last = z;
+            // Orbit's cycle detection runs per iteration, so instead look for the period by
+            // continuing the settled orbit a little
+            let mut w = z;
+            sample.period = (1..HISTORY)
+                .find(|_| {
+                    w = w * w + c;
+                    (w - z).norm() < PERIOD_TOLERANCE
+                })
+                .unwrap_or(0);
+        }
+    }
+    sample
+}
+
+#[cfg(target_arch = "x86_64")]
+mod avx2 {
+    use std::arch::x86_64::*;
+
+    use num_complex::Complex;
+
+    use super::{Lane, CYCLE_TOLERANCE, LANES, SINGLE_LANES};
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn iterate(
+        z: [Complex<f64>; LANES],
+        c: [Complex<f64>; LANES],
+        dz0: Complex<f64>,
+        dc: Complex<f64>,
+        maxiter: usize
+    ) -> [Lane; LANES] {
+        let load = |values: [f64; LANES]| _mm256_loadu_pd(values.as_ptr());
+        let store = |v: __m256d| {
+            let mut values = [0.; LANES];
+            _mm256_storeu_pd(values.as_mut_ptr(), v);
+            values
+        };
+
+        let (mut zr, mut zi) = (load(z.map(|z| z.re)), load(z.map(|z| z.im)));
+        let (cr, ci) = (load(c.map(|c| c.re)), load(c.map(|c| c.im)));
+        let (mut dzr, mut dzi) = (_mm256_set1_pd(dz0.re), _mm256_set1_pd(dz0.im));
+        let (dcr, dci) = (_mm256_set1_pd(dc.re), _mm256_set1_pd(dc.im));
+        let (two, bailout) = (_mm256_set1_pd(2.), _mm256_set1_pd(4.));
+        // Brent's cycle detection as in `Orbit::visit`, against one reference per lane
+        let (mut rr, mut ri) = (_mm256_set1_pd(f64::NAN), _mm256_set1_pd(f64::NAN));
+        let tolerance = _mm256_set1_pd(CYCLE_TOLERANCE);
+
+        let mut lanes = [Lane::default(); LANES];
+        // all bits set for lanes still iterating
+        let mut active = _mm256_castsi256_pd(_mm256_set1_epi64x(-1));
+
+        for i in 0..maxiter {
+            let ndzr = _mm256_add_pd(_mm256_mul_pd(two, _mm256_sub_pd(_mm256_mul_pd(zr, dzr), _mm256_mul_pd(zi, dzi))), dcr);
+            let ndzi = _mm256_add_pd(_mm256_mul_pd(two, _mm256_add_pd(_mm256_mul_pd(zr, dzi), _mm256_mul_pd(zi, dzr))), dci);
+            let nzr = _mm256_add_pd(_mm256_sub_pd(_mm256_mul_pd(zr, zr), _mm256_mul_pd(zi, zi)), cr);
+            let nzi = _mm256_add_pd(_mm256_mul_pd(two, _mm256_mul_pd(zr, zi)), ci);
+
+            // finished lanes keep their last values instead of overflowing
+            zr = _mm256_blendv_pd(zr, nzr, active);
+            zi = _mm256_blendv_pd(zi, nzi, active);
+            dzr = _mm256_blendv_pd(dzr, ndzr, active);
+            dzi = _mm256_blendv_pd(dzi, ndzi, active);
+
+            let modulus = _mm256_add_pd(_mm256_mul_pd(zr, zr), _mm256_mul_pd(zi, zi));
+            let escaped = _mm256_and_pd(_mm256_cmp_pd(modulus, bailout, _CMP_GT_OQ), active);
+            let mask = _mm256_movemask_pd(escaped);
+            if mask != 0 {
+                for (lane, result) in lanes.iter_mut().enumerate() {
+                    if mask & (1 << lane) != 0 {
+                        result.escape = Some(i);
+                    }
+                }
+                active = _mm256_andnot_pd(escaped, active);
+                if _mm256_movemask_pd(active) == 0 {
+                    break;
+                }
+            }
+
+            let (dr, di) = (_mm256_sub_pd(zr, rr), _mm256_sub_pd(zi, ri));
+            let distance = _mm256_add_pd(_mm256_mul_pd(dr, dr), _mm256_mul_pd(di, di));
+            let cycled = _mm256_and_pd(_mm256_cmp_pd(distance, tolerance, _CMP_LT_OQ), active);
+            if _mm256_movemask_pd(cycled) != 0 {
+                active = _mm256_andnot_pd(cycled, active);
+                if _mm256_movemask_pd(active) == 0 {
+                    break;
+                }
+            }
+            if (i + 1).is_power_of_two() {
+                (rr, ri) = (zr, zi);
+            }
+        }
+
+        let (zr, zi, dzr, dzi) = (store(zr), store(zi), store(dzr), store(dzi));
+        for (lane, result) in lanes.iter_mut().enumerate() {
+            result.z = Complex::new(zr[lane], zi[lane]);
+            result.dz = Complex::new(dzr[lane], dzi[lane]);
+        }
+        lanes
+    }
+    /// `iterate` in f32, eight lanes to a register. Results are widened back to f64.
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn iterate_single(
+        z: [Complex<f64>; SINGLE_LANES],
+        c: [Complex<f64>; SINGLE_LANES],
+        dz0: Complex<f64>,
+        dc: Complex<f64>,
+        maxiter: usize
+    ) -> [Lane; SINGLE_LANES] {
+        let load = |values: [f64; SINGLE_LANES]| _mm256_loadu_ps(values.map(|value| value as f32).as_ptr());
+        let store = |v: __m256| {
+            let mut values = [0f32; SINGLE_LANES];
+            _mm256_storeu_ps(values.as_mut_ptr(), v);
+            values
+        };
+
+        let (mut zr, mut zi) = (load(z.map(|z| z.re)), load(z.map(|z| z.im)));
+        let (cr, ci) = (load(c.map(|c| c.re)), load(c.map(|c| c.im)));
+        let (mut dzr, mut dzi) = (_mm256_set1_ps(dz0.re as f32), _mm256_set1_ps(dz0.im as f32));
+        let (dcr, dci) = (_mm256_set1_ps(dc.re as f32), _mm256_set1_ps(dc.im as f32));
+        let (two, bailout) = (_mm256_set1_ps(2.), _mm256_set1_ps(4.));
+        let (mut rr, mut ri) = (_mm256_set1_ps(f32::NAN), _mm256_set1_ps(f32::NAN));
+        let tolerance = _mm256_set1_ps(CYCLE_TOLERANCE as f32);
+
+        let mut lanes = [Lane::default(); SINGLE_LANES];
+        let mut active = _mm256_castsi256_ps(_mm256_set1_epi32(-1));
+
+        for i in 0..maxiter {
+            let ndzr = _mm256_add_ps(_mm256_mul_ps(two, _mm256_sub_ps(_mm256_mul_ps(zr, dzr), _mm256_mul_ps(zi, dzi))), dcr);
+            let ndzi = _mm256_add_ps(_mm256_mul_ps(two, _mm256_add_ps(_mm256_mul_ps(zr, dzi), _mm256_mul_ps(zi, dzr))), dci);
+            let nzr = _mm256_add_ps(_mm256_sub_ps(_mm256_mul_ps(zr, zr), _mm256_mul_ps(zi, zi)), cr);
+            let nzi = _mm256_add_ps(_mm256_mul_ps(two, _mm256_mul_ps(zr, zi)), ci);
+
+            zr = _mm256_blendv_ps(zr, nzr, active);
+            zi = _mm256_blendv_ps(zi, nzi, active);
+            dzr = _mm256_blendv_ps(dzr, ndzr, active);
+            dzi = _mm256_blendv_ps(dzi, ndzi, active);
+
+            let modulus = _mm256_add_ps(_mm256_mul_ps(zr, zr), _mm256_mul_ps(zi, zi));
+            let escaped = _mm256_and_ps(_mm256_cmp_ps(modulus, bailout, _CMP_GT_OQ), active);
+            let mask = _mm256_movemask_ps(escaped);
+            if mask != 0 {
+                for (lane, result) in lanes.iter_mut().enumerate() {
+                    if mask & (1 << lane) != 0 {
+                        result.escape = Some(i);
+                    }
+                }
+                active = _mm256_andnot_ps(escaped, active);
+                if _mm256_movemask_ps(active) == 0 {
+                    break;
+                }
+            }
+
+            let (dr, di) = (_mm256_sub_ps(zr, rr), _mm256_sub_ps(zi, ri));
+            let distance = _mm256_add_ps(_mm256_mul_ps(dr, dr), _mm256_mul_ps(di, di));
+            let cycled = _mm256_and_ps(_mm256_cmp_ps(distance, tolerance, _CMP_LT_OQ), active);
+            if _mm256_movemask_ps(cycled) != 0 {
+                active = _mm256_andnot_ps(cycled, active);
+                if _mm256_movemask_ps(active) == 0 {
+                    break;
+                }
+            }
+            if (i + 1).is_power_of_two() {
+                (rr, ri) = (zr, zi);
+            }
+        }
+
+        let (zr, zi, dzr, dzi) = (store(zr), store(zi), store(dzr), store(dzi));
+        for (lane, result) in lanes.iter_mut().enumerate() {
+            result.z = Complex::new(zr[lane] as f64, zi[lane] as f64);
+            result.dz = Complex::new(dzr[lane] as f64, dzi[lane] as f64);
+        }
+        lanes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num_complex::Complex;
+
+    use crate::{FractalContext, Seed};
+    use crate::fixed::Center;
+    use super::{available, chunked, iterate, iterate_single};
+
+    /// The f32 kernel at shallow zooms should agree with f64 everywhere but the chaotic
+    /// pixels right at the boundary, where neither is exact.
+    #[test]
+    fn single_precision_matches_double() {
+        if !available() {
+            return;
+        }
+
+        let julia = Seed::Julia(Complex::new(-0.4, 0.6));
+        let views = [((-0.5, 0.), 80., Seed::Parameter), ((-0.7435, 0.1314), 5000., Seed::Parameter), ((0., 0.), 9000., julia)];
+        for (center, scale, seed) in views {
+            let mut ctx = FractalContext::new();
+            ctx.dimensions = (160, 90);
+            (ctx.pan, ctx.scale) = (Center::new(center), scale);
+            let (dz0, dc) = match seed {
+                Seed::Parameter => (Complex::new(0., 0.), Complex::new(1., 0.)),
+                Seed::Julia(_) => (Complex::new(1., 0.), Complex::new(0., 0.))
+            };
+            let pixels: Vec<usize> = (0..160 * 90).collect();
+            let single = chunked(&ctx, seed, &pixels, |z, c| iterate_single(z, c, dz0, dc, 200));
+            let double = chunked(&ctx, seed, &pixels, |z, c| iterate(z, c, dz0, dc, 200));
+
+            let smooth = |escape: Option<(usize, f64)>| escape.map(|(n, modulus)| n as f64 + 1. - modulus.ln().log2());
+            let (mut agree, mut close) = (0, 0);
+            for (a, b) in single.iter().zip(&double) {
+                match (smooth(a.escape), smooth(b.escape)) {
+                    (Some(a), Some(b)) => {
+                        agree += 1;
+                        close += ((a - b).abs() < 0.05) as usize;
+                    }
+                    (None, None) => {
+                        agree += 1;
+                        close += 1;
+                    }
+                    _ => {}
+                }
+            }
+            assert!(agree * 100 >= pixels.len() * 99, "{} of {} agree at {}", agree, pixels.len(), scale);
+            assert!(close * 100 >= pixels.len() * 95, "{} of {} close at {}", close, pixels.len(), scale);
+        }
+    }
+}
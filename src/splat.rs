@@ -0,0 +1,106 @@
+use rayon::prelude::*;
+
+use crate::{rotate, FractalContext};
+use crate::coloring;
+
+const BATCHES: usize = 64;
+const WARMUP: usize = 100;
+
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed.wrapping_mul(0x9E3779B97F4A7C15) | 1)
+    }
+
+    pub fn next_f64(&mut self) -> f64 {
+        // xorshift64*
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        (self.0.wrapping_mul(0x2545F4914F6CDD1D) >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Accumulates `points` iterations of `step` into a density buffer and writes it to
+/// `ctx.pixels` with log-scaled brightness.
+pub fn render<F>(ctx: &mut FractalContext, points: usize, step: F)
+where
+    F: Fn(&mut (f64, f64), &mut Rng) -> (f64, f64) + Sync
+{
+    let (width, height) = ctx.dimensions;
+    let (pan, scale, rotation) = (ctx.pan.approx(), ctx.scale, ctx.rotation);
+
+    let density = (0..BATCHES)
+        .into_par_iter()
+        .map(|batch| {
+            let mut density = vec![0u64; width * height];
+            let mut rng = Rng::new(batch as u64 + 1);
+            let mut state = (rng.next_f64(), rng.next_f64());
+            // the first batches take a point each of what doesn't divide evenly between them
+            let share = points / BATCHES + (batch < points % BATCHES) as usize;
+
+            for i in 0..(share + WARMUP) {
+                let p = step(&mut state, &mut rng);
+                if i < WARMUP {
+                    continue;
+                }
+
+                let (dx, dy) = rotate((p.0 - pan.0, p.1 - pan.1), -rotation);
+                let x = dx * scale + width as f64 / 2.;
+                let y = dy * scale + height as f64 / 2.;
+                if x >= 0. && y >= 0. && (x as usize) < width && (y as usize) < height {
+                    density[x as usize + y as usize * width] += 1;
+                }
+            }
+            density
+        })
+        .reduce(
+            || vec![0u64; width * height],
+            |mut acc, density| {
+                acc.iter_mut().zip(density).for_each(|(a, d)| *a += d);
+                acc
+            }
+        );
+
+    let max = (*density.iter().max().unwrap_or(&0) as f64 + 1.).ln();
+    if max == 0. {
+        // nothing landed in view
+        ctx.pixels.fill(0);
+        return;
+    }
+    let palette = &ctx.palette;
+    let (gamma, exposure, dither) = (ctx.gamma, ctx.exposure, ctx.dither);
+    ctx.pixels.par_iter_mut().zip(density).enumerate().for_each(|(i, (px, count))| {
+        // log density is already perceptually spread, so only the deviation from the default
+        // gamma applies to it
+        let value = (count as f64 + 1.).ln() / max;
+        let color = palette.color(coloring::tone_map(value, gamma / coloring::GAMMA, exposure));
+        *px = coloring::quantize(color, i % width, i / width, dither);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::FractalContext;
+    use super::render;
+
+    #[test]
+    fn splats_fewer_points_than_batches() {
+        let mut ctx = FractalContext::new();
+        ctx.dimensions = (9, 9);
+        ctx.pixels = vec![1; 81];
+        // every point lands on the center pixel
+        render(&mut ctx, 10, |_, _| (0., 0.));
+        assert_ne!(ctx.pixels[40], ctx.pixels[0]);
+    }
+
+    #[test]
+    fn nothing_in_view_is_black() {
+        let mut ctx = FractalContext::new();
+        ctx.dimensions = (9, 9);
+        ctx.pixels = vec![1; 81];
+        render(&mut ctx, 1000, |_, _| (1e9, 1e9));
+        assert!(ctx.pixels.iter().all(|&pixel| pixel == 0));
+    }
+}
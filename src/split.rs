@@ -0,0 +1,125 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use num_complex::Complex;
+
+use fractv::worker::{self, Frame, Request};
+use fractv::{FractalContext, JuliaFamily};
+
+/// The right half of the split view, the Julia set of the point under the cursor in the
+/// left half, rendered on a thread of its own.
+pub struct JuliaPane {
+    pub request: Request,
+    pub frame: Frame,
+    requests: Sender<Request>,
+    frames: Receiver<Frame>,
+    cancel: Arc<AtomicBool>,
+    /// Point the pane's Julia set is of, `None` to render the next one whatever it is.
+    seed: Option<Complex<f64>>,
+    /// Whether the seed or the view changed this frame, for previewing.
+    moving: bool,
+    last_input: Instant
+}
+
+impl JuliaPane {
+    pub fn new(ctx: FractalContext, family: JuliaFamily, seed: Complex<f64>) -> JuliaPane {
+        let request = Request::new(&ctx);
+        let cancel = ctx.cancel.clone();
+        let (requests, inbox) = mpsc::channel();
+        let (outbox, frames) = mpsc::channel();
+        let dimensions = ctx.dimensions;
+        thread::spawn(move || worker::run(family.at(seed), ctx, inbox, outbox));
+        let frame = Frame { pixels: vec![0; dimensions.0 * dimensions.1], dimensions, maxiter: 0, time: Duration::ZERO, minimap: None, request: 0, complete: false };
+        JuliaPane { request, frame, requests, frames, cancel, seed: Some(seed), moving: false, last_input: Instant::now() }
+    }
+
+    /// Shows the Julia set seeded at `seed`, keeping the pane's own view of it.
+    pub fn follow(&mut self, family: JuliaFamily, seed: Complex<f64>) {
+        if Some(seed) != self.seed {
+            self.seed = Some(seed);
+            self.request.fractal = Some(family.at(seed));
+            self.request.updated = true;
+            self.moving = true;
+        }
+    }
+
+    /// Makes the next seed render even if it's the same point, as when the fractal it's
+    /// picked from changes.
+    pub fn reseed(&mut self) {
+        self.seed = None;
+    }
+
+    pub fn resize(&mut self, dimensions: (usize, usize)) {
+        if dimensions != self.request.dimensions {
+            self.request.dimensions = dimensions;
+            self.request.updated = true;
+        }
+    }
+
+    pub fn zoom_at(&mut self, factor: f64, position: (f64, f64)) {
+        self.request.zoom_at(factor, position);
+        self.moving = true;
+    }
+
+    pub fn pan_pixels(&mut self, dx: isize, dy: isize) {
+        self.request.pan_pixels(dx, dy);
+        self.moving = true;
+    }
+
+    /// Takes on the coloring and iteration settings of `view`, sends the render thread what
+    /// changed this frame, and picks up what it rendered. Returns whether there's a new frame.
+    pub fn update(&mut self, view: &Request, preview: bool) -> bool {
+        if view.recolor {
+            self.request.interior = view.interior;
+            self.request.slope = view.slope;
+            self.request.gamma = view.gamma;
+            self.request.exposure = view.exposure;
+            self.request.dither = view.dither;
+            self.request.palette = view.palette.clone();
+            self.request.palette_offset = view.palette_offset;
+            self.request.recolor = true;
+        }
+        if (view.iteration_curve, view.iteration_factor) != (self.request.iteration_curve, self.request.iteration_factor) {
+            (self.request.iteration_curve, self.request.iteration_factor) = (view.iteration_curve, view.iteration_factor);
+            self.request.updated = true;
+        }
+        self.request.settle(preview && std::mem::take(&mut self.moving), &mut self.last_input);
+
+        if self.request.changed() {
+            if self.request.stale() {
+                self.cancel.store(true, Ordering::Relaxed);
+            }
+            // a render thread that's gone leaves the last frame up
+            let _ = self.requests.send(self.request.take());
+        }
+        match self.frames.try_iter().last() {
+            Some(frame) => {
+                self.frame = frame;
+                true
+            }
+            None => false
+        }
+    }
+}
+
+/// Puts the rows of `left` and those of the pane side by side.
+pub fn compose(left: &[u32], (left_width, left_height): (usize, usize), pane: &Frame) -> (Vec<u32>, (usize, usize)) {
+    let (right_width, right_height) = pane.dimensions;
+    let (width, height) = (left_width + right_width, left_height.max(right_height));
+    let mut pixels = vec![0; width * height];
+    if width == 0 {
+        return (pixels, (width, height));
+    }
+    for (y, row) in pixels.chunks_exact_mut(width).enumerate() {
+        if y < left_height {
+            row[..left_width].copy_from_slice(&left[y * left_width..(y + 1) * left_width]);
+        }
+        if y < right_height {
+            row[left_width..].copy_from_slice(&pane.pixels[y * right_width..(y + 1) * right_width]);
+        }
+    }
+    (pixels, (width, height))
+}
@@ -0,0 +1,562 @@
+use std::path::Path;
+use std::time::Instant;
+
+use minifb::Window;
+use num_complex::Complex;
+use fractv::bookmarks::{self, Bookmark, Bookmarks};
+use fractv::fixed::Center;
+use fractv::goto::{self, Goto};
+use fractv::history::{History, State};
+use fractv::keyframes::Tour;
+use fractv::keys::{Action, Bindings};
+use fractv::palette::Palette;
+use fractv::worker::{Frame, Request};
+use fractv::{hud, minimap, palette_file, parse_fractal, png, timestamp, Fractal, FractalContext, JuliaFamily, Options, HOME_SCALE};
+use crate::clipboard;
+use crate::commands::Command;
+use crate::crosshair::Crosshair;
+use crate::editor::GradientEditor;
+use crate::selection::Selection;
+use crate::split::{self, JuliaPane};
+use crate::help;
+
+/// Fractals the number keys switch to, as they'd be given on the command line.
+const SWITCHABLE: [&str; 9] = [
+    "mandelbrot 256",
+    "burning-ship 256",
+    "julia -0.4 0.6 256",
+    "tricorn 256",
+    "multibrot 3 256",
+    "newton 3 64",
+    "celtic 256",
+    "buffalo 256",
+    "lambda 256"
+];
+/// Degrees the view turns by each frame a rotate key is held.
+const ROTATION_STEP: f64 = 0.5;
+
+/// What the UI thread knows of the fractal the render thread has, which can't be shared.
+pub struct Current {
+    /// The command line arguments that give it, program name first.
+    pub spec: Vec<String>,
+    /// Its initial view, where the reset key goes back to.
+    pub home: (Center, f64),
+    /// What picking a Julia seed needs of it.
+    pub julia: Option<JuliaFamily>
+}
+
+impl Current {
+    pub fn new(fract: &Fractal, spec: Vec<String>) -> Current {
+        Current { spec, home: fract.home(), julia: fract.julia() }
+    }
+
+    /// Has the render thread switch to the fractal `spec` gives, at its initial view. Returns
+    /// false if it doesn't give one.
+    pub fn switch(&mut self, spec: Vec<String>, request: &mut Request) -> bool {
+        match parse_fractal(&spec) {
+            Some(fract) => {
+                *self = Current::new(&fract, spec);
+                request.switch(fract);
+                true
+            }
+            None => false
+        }
+    }
+
+    /// Moves `request` to `state`, switching fractals if it's of another one. Returns
+    /// whether it did.
+    pub fn go(&mut self, state: State, request: &mut Request) -> bool {
+        let switched = state.fractal != self.spec && self.switch(state.fractal, request);
+        (request.pan, request.scale, request.rotation) = (state.pan, state.scale, state.rotation);
+        request.iteration_factor = state.iteration_factor;
+        request.updated = true;
+        switched
+    }
+
+    /// Where `request` is, for the history.
+    pub fn state(&self, request: &Request) -> State {
+        State {
+            fractal: self.spec.clone(),
+            pan: request.pan.clone(),
+            scale: request.scale,
+            rotation: request.rotation,
+            iteration_factor: request.iteration_factor
+        }
+    }
+
+    /// Where `request` is and how it's colored, to come back to.
+    pub fn bookmark(&self, request: &Request) -> Bookmark {
+        Bookmark {
+            view: self.state(request),
+            coloring: request.coloring,
+            brightness: request.brightness,
+            interior: request.interior,
+            slope: request.slope,
+            gamma: request.gamma,
+            exposure: request.exposure,
+            dither: request.dither,
+            palette: request.palette.key().to_string(),
+            palette_offset: request.palette_offset,
+            iteration_curve: request.iteration_curve
+        }
+    }
+
+    /// Moves `request` to `bookmark` and colors it the same, with its palette if that's one
+    /// of `palettes` or can be loaded into them. Returns whether it switched fractals.
+    pub fn restore(&mut self, bookmark: &Bookmark, request: &mut Request, palettes: &mut Vec<Palette>, palette: &mut usize) -> bool {
+        let switched = self.go(bookmark.view.clone(), request);
+        (request.coloring, request.brightness) = (bookmark.coloring, bookmark.brightness);
+        request.interior = bookmark.interior;
+        request.slope = bookmark.slope;
+        request.gamma = bookmark.gamma;
+        request.exposure = bookmark.exposure;
+        request.dither = bookmark.dither;
+        request.palette_offset = bookmark.palette_offset;
+        request.iteration_curve = bookmark.iteration_curve;
+        match palette_file::find(palettes, &bookmark.palette) {
+            Ok(index) => {
+                *palette = index;
+                request.palette = palettes[index].clone();
+            }
+            Err(err) => println!("{}", err)
+        }
+        switched
+    }
+}
+
+/// What the HUD shows: the view center to a little finer than a pixel, the zoom from the
+/// initial view, and how the last frame was rendered.
+fn hud_lines(request: &Request, frame: &Frame) -> Vec<String> {
+    let (re, im) = request.pan.exact();
+    let digits = (request.scale.log10().ceil() as usize + 1).max(3);
+    vec![
+        format!("re {:+.*}", digits, re),
+        format!("im {:+.*}", digits, im),
+        format!("zoom {:.3e}", request.scale / HOME_SCALE),
+        format!("rotation {:.1} deg", request.rotation.to_degrees()),
+        format!("iterations {}", frame.maxiter),
+        format!("time {:.0} ms", frame.time.as_secs_f64() * 1e3)
+    ]
+}
+
+/// What the help lists above the key bindings: the fractal, how it's rendered and colored.
+fn help_settings(current: &Current, request: &Request, renderer: &str) -> Vec<String> {
+    let on = |flag| if flag { "on" } else { "off" };
+    let curve = match request.iteration_curve {
+        Some(exponent) => format!("growing with zoom, exponent {}", exponent),
+        None => "fixed".to_string()
+    };
+    let coloring = match request.brightness {
+        Some(brightness) => format!("coloring {}, brightness {}", request.coloring.name(), brightness.name()),
+        None => format!("coloring {}", request.coloring.name())
+    };
+    vec![
+        format!("fractal {}", current.spec[1..].join(" ")),
+        format!("renderer {}", renderer),
+        coloring,
+        format!("palette {}, offset {:.2}", request.palette.name, request.palette_offset),
+        format!("interior {}, slope {}, dither {}", request.interior.name(), on(request.slope), on(request.dither)),
+        format!("gamma {:.2}, exposure {:+.2}", request.gamma, request.exposure),
+        format!("iterations x{}, {}", request.iteration_factor, curve),
+        format!("rotation {:.1} deg", request.rotation.to_degrees())
+    ]
+}
+
+/// Saves `frame`'s pixels as a PNG, without the overlays as it never has them, and with where
+/// it is to come back to with `--from-image`.
+pub fn save_frame(path: &str, frame: &Frame, bookmark: &Bookmark) {
+    let text = bookmarks::metadata(bookmark, Some(frame.maxiter).filter(|&maxiter| maxiter > 0));
+    match png::save(Path::new(path), &frame.pixels, frame.dimensions, &text) {
+        Ok(()) => println!("saved {}", path),
+        Err(err) => println!("{}", err)
+    }
+}
+
+/// Everything the viewer's keys, commands and overlays work on, apart from the window.
+pub struct Viewer {
+    pub request: Request,
+    pub current: Current,
+    pub palettes: Vec<Palette>,
+    pub palette: usize,
+    /// What the iteration curve key turns the curve back on at.
+    iteration_curve: f64,
+    pub history: History,
+    bookmarks: Bookmarks,
+    /// Where the state is saved to, and saved when the viewer's closed.
+    save_state: Option<String>,
+    /// The state file F9 loads: the last saved, or the one loaded at the start.
+    state_path: Option<String>,
+    tour: Option<Tour>,
+    /// When the tour started playing, which it does from the start.
+    playing: Option<Instant>,
+    pub pane: Option<JuliaPane>,
+    /// For the Julia pane, set up from the options until it's first opened.
+    pane_ctx: Option<FractalContext>,
+    pub split: bool,
+    pub cycling: bool,
+    pub hud: bool,
+    pub help: bool,
+    pub crosshair: Crosshair,
+    pub editor: GradientEditor,
+    pub selection: Selection,
+    pub goto: Goto
+}
+
+impl Viewer {
+    pub fn new(fract: &Fractal, spec: Vec<String>, request: Request, options: Options, bookmarks: Bookmarks, save_state: Option<String>, goto: Goto) -> Viewer {
+        let pane_ctx = Some(FractalContext::from_options(&options));
+        let Options { iteration_curve, crosshair_size, crosshair_color, palettes, palette, tour, state_path, .. } = options;
+        let current = Current::new(fract, spec);
+        Viewer {
+            history: History::new(current.state(&request)),
+            request,
+            current,
+            palettes,
+            palette,
+            iteration_curve,
+            bookmarks,
+            state_path: state_path.or(save_state.clone()),
+            save_state,
+            playing: tour.as_ref().map(|_| Instant::now()),
+            tour,
+            pane: None,
+            pane_ctx,
+            split: false,
+            cycling: false,
+            hud: false,
+            help: false,
+            crosshair: Crosshair { visible: true, size: crosshair_size, color: crosshair_color },
+            editor: GradientEditor::default(),
+            selection: Selection::default(),
+            goto
+        }
+    }
+
+    /// Has the Julia pane render its next seed even if it's the same point, after the
+    /// fractal it's picked from changed.
+    fn reseed(&mut self) {
+        if let Some(pane) = &mut self.pane {
+            pane.reseed();
+        }
+    }
+
+    /// Carries out a command from stdin, other than the ones the main loop sees to.
+    pub fn command(&mut self, command: Command) {
+        let request = &mut self.request;
+        match command {
+            Command::Center(center) => {
+                request.pan = center;
+                request.updated = true;
+            }
+            Command::Zoom(zoom) => {
+                request.scale = HOME_SCALE * zoom;
+                request.updated = true;
+            }
+            Command::Rotation(degrees) => {
+                request.rotation = degrees.to_radians();
+                request.updated = true;
+            }
+            Command::Palette(name) => {
+                match palette_file::find(&mut self.palettes, &name) {
+                    Ok(index) => {
+                        self.palette = index;
+                        request.palette = self.palettes[index].clone();
+                        request.recolor = true;
+                    }
+                    Err(err) => println!("{}", err)
+                }
+            }
+            Command::Fractal(fractal) => {
+                let spec: Vec<String> = std::iter::once(String::new()).chain(fractal).collect();
+                if !self.current.switch(spec.clone(), request) {
+                    println!("no fractal {}", spec[1..].join(" "));
+                } else {
+                    self.reseed();
+                }
+            }
+            Command::Action(_) | Command::Render(_) | Command::Quit => {}
+        }
+    }
+
+    /// Does what `action` is bound to, other than the zooming and panning the glide eases
+    /// along and what the window has to see to itself. `size` is the window's and `frame`
+    /// the one shown.
+    pub fn act(&mut self, action: Action, size: (usize, usize), frame: &Frame) {
+        let request = &mut self.request;
+        match action {
+            Action::RotateLeft | Action::RotateRight => {
+                let step = if action == Action::RotateLeft { -ROTATION_STEP } else { ROTATION_STEP };
+                request.rotation = (request.rotation + step.to_radians()).rem_euclid(std::f64::consts::TAU);
+                request.updated = true;
+            }
+            Action::IterationCurve => {
+                request.iteration_curve = match request.iteration_curve {
+                    Some(_) => None,
+                    None => Some(self.iteration_curve)
+                };
+                request.updated = true;
+            }
+            Action::IterationsUp => {
+                request.iteration_factor *= 2.;
+                request.updated = true;
+            }
+            Action::IterationsDown => {
+                request.iteration_factor /= 2.;
+                request.updated = true;
+            }
+            Action::Reset => {
+                (request.pan, request.scale) = self.current.home.clone();
+                request.rotation = 0.;
+                request.iteration_factor = 1.;
+                request.updated = true;
+            }
+            Action::NextPalette => {
+                self.palette = (self.palette + 1) % self.palettes.len();
+                request.palette = self.palettes[self.palette].clone();
+                request.recolor = true;
+            }
+            Action::NextInterior => {
+                request.interior = request.interior.next();
+                request.recolor = true;
+            }
+            Action::Slope => {
+                request.slope = !request.slope;
+                request.recolor = true;
+            }
+            Action::Dither => {
+                request.dither = !request.dither;
+                request.recolor = true;
+            }
+            Action::GammaUp => {
+                request.gamma *= 1.1;
+                request.recolor = true;
+            }
+            Action::GammaDown => {
+                request.gamma /= 1.1;
+                request.recolor = true;
+            }
+            Action::ExposureUp => {
+                request.exposure += 0.25;
+                request.recolor = true;
+            }
+            Action::ExposureDown => {
+                request.exposure -= 0.25;
+                request.recolor = true;
+            }
+            Action::Editor => self.editor.open = !self.editor.open,
+            Action::PaletteCycle => self.cycling = !self.cycling,
+            Action::Fractal(n) if !self.editor.open => {
+                let spec = std::iter::once(String::new())
+                    .chain(SWITCHABLE[n].split_whitespace().map(String::from))
+                    .collect();
+                if self.current.switch(spec, request) {
+                    self.reseed();
+                }
+            }
+            Action::Fractal(_) => {}
+            Action::Undo | Action::Redo => {
+                let state = match action {
+                    Action::Undo => self.history.undo(self.current.state(request)),
+                    _ => self.history.redo()
+                };
+                if let Some(state) = state {
+                    if self.current.go(state, request) {
+                        self.reseed();
+                    }
+                }
+            }
+            Action::Bookmark => match self.bookmarks.add(self.current.bookmark(request)) {
+                Ok(()) => println!("bookmarked the view as number {}", self.bookmarks.len()),
+                Err(err) => println!("{}", err)
+            },
+            Action::NextBookmark => {
+                if let Some(bookmark) = self.bookmarks.cycle() {
+                    if self.current.restore(bookmark, request, &mut self.palettes, &mut self.palette) {
+                        self.reseed();
+                    }
+                }
+            }
+            Action::SaveState => {
+                let path = self.save_state.clone().unwrap_or_else(|| format!("fractalv-{}.toml", timestamp()));
+                match bookmarks::save_state(Path::new(&path), &self.current.bookmark(request)) {
+                    Ok(()) => println!("saved the state to {}", path),
+                    Err(err) => println!("{}", err)
+                }
+                self.state_path = Some(path);
+            }
+            Action::LoadState => match self.state_path.clone() {
+                Some(path) => match bookmarks::load_state(Path::new(&path)) {
+                    Ok(state) => {
+                        if self.current.restore(&state, request, &mut self.palettes, &mut self.palette) {
+                            self.reseed();
+                        }
+                        println!("loaded the state from {}", path);
+                    }
+                    Err(err) => println!("{}", err)
+                },
+                None => println!("no state to load, F5 saves one")
+            },
+            Action::Split => match (&self.pane, self.current.julia) {
+                (Some(_), Some(_)) => self.split = !self.split,
+                (None, Some(family)) => {
+                    if let Some(mut ctx) = self.pane_ctx.take() {
+                        ctx.dimensions = (size.0 - size.0 / 2, size.1);
+                        let (re, im) = request.pan.approx();
+                        self.pane = Some(JuliaPane::new(ctx, family, Complex::new(re, im)));
+                        self.split = true;
+                    }
+                }
+                _ => {}
+            },
+            Action::Minimap => request.minimap = !request.minimap,
+            Action::Goto => self.goto.open(),
+            Action::Hud => self.hud = !self.hud,
+            Action::Crosshair => self.crosshair.visible = !self.crosshair.visible,
+            Action::Help => self.help = !self.help,
+            Action::Tour => {
+                self.playing = match (&self.tour, self.playing) {
+                    (None, _) => {
+                        println!("no tour to play, give one with --keyframes <file>");
+                        None
+                    }
+                    (Some(_), Some(_)) => None,
+                    (Some(_), None) => Some(Instant::now())
+                };
+            }
+            Action::CopyLocation => {
+                let location = goto::format(&request.pan, request.scale);
+                match clipboard::copy(&location) {
+                    Ok(()) => println!("copied {}", location),
+                    Err(err) => println!("{}", err)
+                }
+            }
+            Action::PrintLocation => println!("{}", bookmarks::command(&self.current.bookmark(request), Some(frame.maxiter).filter(|&maxiter| maxiter > 0))),
+            Action::Screenshot => save_frame(&format!("fractalv-{}.png", timestamp()), frame, &self.current.bookmark(request)),
+            Action::ZoomIn | Action::ZoomOut | Action::PanUp | Action::PanDown | Action::PanLeft | Action::PanRight => {}
+            Action::Julia | Action::Fullscreen | Action::Quit => {}
+        }
+    }
+
+    /// Fits the views to a window of `size`, halved across in the split view.
+    pub fn resize(&mut self, size: (usize, usize)) {
+        let left = if self.split { (size.0 / 2, size.1) } else { size };
+        if left != self.request.dimensions {
+            self.request.dimensions = left;
+            self.request.updated = true;
+        }
+        if let (true, Some(pane)) = (self.split, &mut self.pane) {
+            pane.resize((size.0 - left.0, size.1));
+        }
+    }
+
+    /// Takes what's typed into the goto prompt and done in the gradient editor this frame,
+    /// turns the palette by `cycle_speed` while it cycles, and keeps the history up with the
+    /// view.
+    pub fn update(&mut self, window: &Window, cycle_speed: f64) {
+        let request = &mut self.request;
+        self.history.track(self.current.state(request));
+
+        self.goto.handle(window, request);
+        if self.editor.open && !self.goto.open && self.editor.handle(window, &mut request.palette, request.dimensions) {
+            self.palettes[self.palette] = request.palette.clone();
+            request.recolor = true;
+        }
+
+        if self.cycling {
+            request.palette_offset = (request.palette_offset + cycle_speed).fract();
+            request.recolor = true;
+        }
+    }
+
+    /// `frame` with the overlays that are open drawn over it, the `minimap` among them if
+    /// it's shown, and the Julia pane beside it in the split view, or `None` if it's to be
+    /// shown as it is.
+    pub fn overlay(&self, frame: &Frame, minimap: Option<&[u32]>, bindings: &Bindings, renderer: &str) -> Option<(Vec<u32>, (usize, usize))> {
+        let overlaid = self.editor.open || self.selection.active() || self.hud || self.crosshair.visible || self.goto.open || minimap.is_some();
+        if !overlaid && !self.help && !self.split {
+            return None;
+        }
+
+        // overlays go on a copy so recoloring never has to undo them
+        let mut pixels = frame.pixels.clone();
+        if self.help {
+            // the help is drawn over the bare view, the other overlays held back until it's closed
+            help::draw(&mut pixels, frame.dimensions, &help_settings(&self.current, &self.request, renderer), &bindings.list());
+        } else {
+            self.crosshair.draw(&mut pixels, frame.dimensions);
+            if self.hud {
+                hud::draw(&mut pixels, frame.dimensions, &hud_lines(&self.request, frame));
+            }
+            if self.editor.open {
+                self.editor.draw(&mut pixels, frame.dimensions, &self.request.palette);
+            }
+            if let Some(map) = minimap {
+                minimap::draw(&mut pixels, frame.dimensions, map, &self.current.home, &self.request);
+            }
+            self.selection.draw(&mut pixels, frame.dimensions);
+            self.goto.draw(&mut pixels, frame.dimensions);
+        }
+        Some(match &self.pane {
+            Some(pane) if self.split => split::compose(&pixels, frame.dimensions, &pane.frame),
+            _ => (pixels, frame.dimensions)
+        })
+    }
+
+    /// Moves the view along the tour while it's playing. Returns whether it did.
+    pub fn play(&mut self) -> bool {
+        let (tour, started) = match (&self.tour, self.playing) {
+            (Some(tour), Some(started)) => (tour, started),
+            _ => return false
+        };
+        let request = &mut self.request;
+        let time = started.elapsed().as_secs_f64();
+        let view = tour.at(time);
+        (request.pan, request.scale, request.rotation, request.iteration_factor) = (view.pan, view.scale, view.rotation, view.iteration_factor);
+        if let Some(index) = view.palette.filter(|index| *index != self.palette) {
+            self.palette = index;
+            request.palette = self.palettes[index].clone();
+        }
+        request.palette_offset = view.palette_offset;
+        request.updated = true;
+        if time >= tour.duration() {
+            self.playing = None;
+        }
+        true
+    }
+
+    /// Saves where the viewer was closed at, as `--save-state` and `--print-location` ask and
+    /// for `--resume`, with the iteration limit of the last `frame`.
+    pub fn close(&self, print_location: bool, frame: &Frame) {
+        let state = self.current.bookmark(&self.request);
+        if print_location {
+            println!("{}", bookmarks::command(&state, Some(frame.maxiter).filter(|&maxiter| maxiter > 0)));
+        }
+        if let Some(path) = &self.save_state {
+            match bookmarks::save_state(Path::new(path), &state) {
+                Ok(()) => println!("saved the state to {}", path),
+                Err(err) => println!("{}", err)
+            }
+        }
+        if let Err(err) = bookmarks::save_session(&state) {
+            println!("{}", err);
+        }
+    }
+}
+
+/// How much the zoom and pan keys in `actions` zoom and pan by, for the glide to ease the view
+/// along with.
+pub fn held(actions: &[Action]) -> (f64, (f64, f64)) {
+    let (mut zooming, mut panning) = (0., (0., 0.));
+    for action in actions {
+        match action {
+            Action::ZoomIn => zooming += 1.,
+            Action::ZoomOut => zooming -= 1.,
+            Action::PanUp => panning.1 -= 1.,
+            Action::PanDown => panning.1 += 1.,
+            Action::PanLeft => panning.0 -= 1.,
+            Action::PanRight => panning.0 += 1.,
+            _ => {}
+        }
+    }
+    (zooming, panning)
+}
@@ -0,0 +1,323 @@
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{Receiver, Sender, TryRecvError};
+use std::time::{Duration, Instant};
+
+use num_complex::Complex;
+
+use crate::{rotate, Fractal, FractalContext, PREVIEW, SETTLE};
+use crate::coloring::{Coloring, Interior};
+use crate::fixed::Center;
+use crate::minimap;
+use crate::palette::Palette;
+
+/// The view settings input can change, as the UI thread last set them, and what the changes
+/// since the previous request need from the render thread.
+pub struct Request {
+    pub dimensions: (usize, usize),
+    pub pan: Center,
+    pub scale: f64,
+    /// Angle the view is turned by, in radians.
+    pub rotation: f64,
+    pub coloring: Coloring,
+    pub brightness: Option<Coloring>,
+    pub interior: Interior,
+    pub slope: bool,
+    pub gamma: f64,
+    pub exposure: f64,
+    pub dither: bool,
+    pub palette: Palette,
+    pub palette_offset: f64,
+    pub iteration_curve: Option<f64>,
+    pub iteration_factor: f64,
+    /// Divisor of `dimensions` to render at, above 1 for quick previews.
+    pub resolution: usize,
+    /// The view changed and has to be rendered from scratch.
+    pub updated: bool,
+    /// Whole pixels the view was panned by, so a finished render can be shifted along.
+    pub moved: (isize, isize),
+    /// Only the coloring changed.
+    pub recolor: bool,
+    /// A fractal to switch to.
+    pub fractal: Option<Fractal>,
+    /// Whether to render the minimap along with the view.
+    pub minimap: bool,
+    /// What `minimap` was when last sent.
+    minimap_sent: bool,
+    /// How many requests have been taken to send, counting this one once it is.
+    pub sequence: usize
+}
+
+impl Request {
+    pub fn new(ctx: &FractalContext) -> Request {
+        Request {
+            dimensions: ctx.dimensions,
+            pan: ctx.pan.clone(),
+            scale: ctx.scale,
+            rotation: ctx.rotation,
+            coloring: ctx.coloring,
+            brightness: ctx.brightness,
+            interior: ctx.interior,
+            slope: ctx.slope,
+            gamma: ctx.gamma,
+            exposure: ctx.exposure,
+            dither: ctx.dither,
+            palette: ctx.palette.clone(),
+            palette_offset: ctx.palette_offset,
+            iteration_curve: ctx.iteration_curve,
+            iteration_factor: ctx.iteration_factor,
+            resolution: ctx.resolution,
+            updated: false,
+            moved: (0, 0),
+            recolor: false,
+            fractal: None,
+            minimap: false,
+            minimap_sent: false,
+            sequence: 0
+        }
+    }
+
+    /// The settings to send, with the changes made since the last call, which are cleared.
+    pub fn take(&mut self) -> Request {
+        let request = Request {
+            dimensions: self.dimensions,
+            pan: self.pan.clone(),
+            scale: self.scale,
+            rotation: self.rotation,
+            coloring: self.coloring,
+            brightness: self.brightness,
+            interior: self.interior,
+            slope: self.slope,
+            gamma: self.gamma,
+            exposure: self.exposure,
+            dither: self.dither,
+            palette: self.palette.clone(),
+            palette_offset: self.palette_offset,
+            iteration_curve: self.iteration_curve,
+            iteration_factor: self.iteration_factor,
+            resolution: self.resolution,
+            updated: self.updated,
+            moved: self.moved,
+            recolor: self.recolor,
+            fractal: self.fractal.take(),
+            minimap: self.minimap,
+            minimap_sent: self.minimap,
+            sequence: self.sequence + 1
+        };
+        self.sequence += 1;
+        (self.updated, self.moved, self.recolor) = (false, (0, 0), false);
+        self.minimap_sent = self.minimap;
+        request
+    }
+
+    /// Switches to `fract` at its initial view.
+    pub fn switch(&mut self, fract: Fractal) {
+        (self.pan, self.scale) = fract.home();
+        self.rotation = 0.;
+        self.fractal = Some(fract);
+        self.updated = true;
+    }
+
+    pub fn pan_pixels(&mut self, dx: isize, dy: isize) {
+        let (re, im) = rotate((dx as f64, dy as f64), self.rotation);
+        self.pan.shift((re / self.scale, im / self.scale), self.scale);
+        self.moved = (self.moved.0 + dx, self.moved.1 + dy);
+    }
+
+    /// The point of the plane at the window position `(x, y)`.
+    pub fn point(&self, (x, y): (f64, f64)) -> Complex<f64> {
+        let (re, im) = self.pan.approx();
+        let (dx, dy) = rotate((x - self.dimensions.0 as f64 / 2., y - self.dimensions.1 as f64 / 2.), self.rotation);
+        Complex::new(re + dx / self.scale, im + dy / self.scale)
+    }
+
+    /// Zooms in by `factor` around the window position `(x, y)`, keeping the point under it
+    /// where it is.
+    pub fn zoom_at(&mut self, factor: f64, (x, y): (f64, f64)) {
+        let (dx, dy) = rotate((x - self.dimensions.0 as f64 / 2., y - self.dimensions.1 as f64 / 2.), self.rotation);
+        let scale = self.scale * factor;
+        let moved = 1. / self.scale - 1. / scale;
+        self.pan.shift((dx * moved, dy * moved), scale);
+        self.scale = scale;
+        self.updated = true;
+    }
+
+    /// Centers the view on the window rectangle at `origin` and zooms in until it just fits.
+    pub fn zoom_to(&mut self, origin: (f64, f64), size: (f64, f64)) {
+        let (width, height) = (self.dimensions.0 as f64, self.dimensions.1 as f64);
+        let (dx, dy) = rotate((origin.0 + (size.0 - width) / 2., origin.1 + (size.1 - height) / 2.), self.rotation);
+        let scale = self.scale * (width / size.0).min(height / size.1);
+        self.pan.shift((dx / self.scale, dy / self.scale), scale);
+        self.scale = scale;
+        self.updated = true;
+    }
+
+    /// Drops to preview resolution while the view keeps `moving`, going back to full once
+    /// it's been still for a while.
+    pub fn settle(&mut self, moving: bool, last_input: &mut Instant) {
+        if moving {
+            *last_input = Instant::now();
+            if self.resolution != PREVIEW {
+                self.resolution = PREVIEW;
+                self.updated = true;
+            }
+        } else if self.resolution != 1 && last_input.elapsed() > SETTLE {
+            self.resolution = 1;
+            self.updated = true;
+        }
+    }
+
+    /// Whether the render thread has anything to do for this request.
+    pub fn changed(&self) -> bool {
+        self.updated || self.moved != (0, 0) || self.recolor || self.minimap != self.minimap_sent
+    }
+
+    /// Whether the pass in progress is out of date.
+    pub fn stale(&self) -> bool {
+        self.updated || self.moved != (0, 0)
+    }
+
+    /// Copies the settings into `ctx` and `fract`, adding to what `ctx` still has to do.
+    /// Returns whether the pixels need recoloring.
+    fn apply(self, ctx: &mut FractalContext, fract: &mut Fractal) -> bool {
+        if let Some(fractal) = self.fractal {
+            *fract = fractal;
+        }
+        let resolution = self.resolution;
+        ctx.dimensions = (self.dimensions.0.div_ceil(resolution), self.dimensions.1.div_ceil(resolution));
+        ctx.pan = self.pan;
+        ctx.scale = self.scale / resolution as f64;
+        ctx.rotation = self.rotation;
+        if (self.coloring, self.brightness) != (ctx.coloring, ctx.brightness) {
+            // the kernels may have to track something else, so it's rendered again
+            ctx.set_coloring(self.coloring, self.brightness);
+            ctx.updated = true;
+        }
+        ctx.interior = self.interior;
+        ctx.slope = self.slope;
+        ctx.gamma = self.gamma;
+        ctx.exposure = self.exposure;
+        ctx.dither = self.dither;
+        ctx.palette = self.palette;
+        ctx.palette_offset = self.palette_offset;
+        ctx.iteration_curve = self.iteration_curve;
+        ctx.iteration_factor = self.iteration_factor;
+        ctx.updated |= self.updated || resolution != ctx.resolution;
+        ctx.resolution = resolution;
+        // pans less than a preview pixel can't be shifted, but previews are quick to redo
+        if resolution == 1 {
+            ctx.moved = (ctx.moved.0 + self.moved.0, ctx.moved.1 + self.moved.1);
+        } else {
+            ctx.updated |= self.moved != (0, 0);
+        }
+        // density renders use the coloring too but keep no samples to recolor from
+        ctx.updated |= self.recolor && ctx.samples.is_empty();
+        self.recolor
+    }
+}
+
+/// Rendered pixels handed back to the UI thread.
+pub struct Frame {
+    pub pixels: Vec<u32>,
+    pub dimensions: (usize, usize),
+    /// Iteration limit the pixels were rendered with.
+    pub maxiter: usize,
+    /// Time spent rendering the view so far.
+    pub time: Duration,
+    /// The minimap, when it's been rendered or recolored since the last frame.
+    pub minimap: Option<Vec<u32>>,
+    /// The `sequence` of the last request the pixels are rendered for.
+    pub request: usize,
+    /// Whether the pixels are the view in full, with nothing left to refine.
+    pub complete: bool
+}
+
+impl Frame {
+    /// The pixels of `ctx`, scaled back up to `dimensions` if it's a preview.
+    fn new(ctx: &FractalContext, dimensions: (usize, usize), time: Duration, request: usize) -> Frame {
+        if ctx.resolution == 1 {
+            let complete = !ctx.refining();
+            return Frame { pixels: ctx.pixels.clone(), dimensions: ctx.dimensions, maxiter: ctx.maxiter, time, minimap: None, request, complete };
+        }
+        let (width, resolution) = (ctx.dimensions.0, ctx.resolution);
+        let pixels = (0..dimensions.0 * dimensions.1)
+            .map(|i| ctx.pixels[i % dimensions.0 / resolution + i / dimensions.0 / resolution * width])
+            .collect();
+        Frame { pixels, dimensions, maxiter: ctx.maxiter, time, minimap: None, request, complete: false }
+    }
+}
+
+/// Renders `fract` on the calling thread until the UI hangs up: applies every request
+/// queued since the last pass, then renders the next pass and sends the pixels back. While
+/// there's nothing left to refine it waits for the next request. The minimap is rendered
+/// whenever it's wanted and out of date, which for a fractal at its initial view at that
+/// size takes no time worth cancelling for.
+pub fn run(mut fract: Fractal, mut ctx: FractalContext, requests: Receiver<Request>, frames: Sender<Frame>) {
+    let mut dimensions = ctx.dimensions;
+    let mut time = Duration::ZERO;
+    let (mut minimap, mut map) = (false, None);
+    let mut sequence = 0;
+    loop {
+        let idle = !(ctx.updated || ctx.moved != (0, 0) || ctx.refining());
+        let first = if idle {
+            match requests.recv() {
+                Ok(request) => Some(request),
+                Err(_) => return
+            }
+        } else {
+            match requests.try_recv() {
+                Ok(request) => Some(request),
+                Err(TryRecvError::Empty) => None,
+                Err(TryRecvError::Disconnected) => return
+            }
+        };
+
+        let mut recolor = false;
+        if let Some(first) = first {
+            for request in std::iter::once(first).chain(requests.try_iter()) {
+                dimensions = request.dimensions;
+                minimap = request.minimap;
+                sequence = request.sequence;
+                if request.fractal.is_some() {
+                    map = None;
+                }
+                recolor |= request.apply(&mut ctx, &mut fract);
+            }
+            // the UI sets it before sending, so every request that cancelled is already in
+            ctx.cancel.store(false, Ordering::Relaxed);
+        }
+
+        let redrawn = if ctx.updated || ctx.moved != (0, 0) || ctx.refining() {
+            if ctx.updated || ctx.moved != (0, 0) {
+                time = Duration::ZERO;
+            }
+            let start = Instant::now();
+            fract.render(&mut ctx);
+            time += start.elapsed();
+            true
+        } else {
+            recolor && Fractal::recolor(&mut ctx)
+        };
+
+        let map_pixels = match &mut map {
+            Some(map) if minimap && recolor => {
+                minimap::recolor(&fract, map, &ctx);
+                Some(map.pixels.clone())
+            }
+            None if minimap => {
+                let rendered = minimap::render(&fract, &ctx);
+                let pixels = rendered.pixels.clone();
+                map = Some(rendered);
+                Some(pixels)
+            }
+            _ => None
+        };
+
+        if redrawn || map_pixels.is_some() {
+            let mut frame = Frame::new(&ctx, dimensions, time, sequence);
+            frame.minimap = map_pixels;
+            if frames.send(frame).is_err() {
+                return;
+            }
+        }
+    }
+}